@@ -0,0 +1,313 @@
+use anyhow::{Error, Result, anyhow};
+use bicit::map::MapOptions;
+use bicit::render::{ExportSize, OutputFormat};
+use bicit::template::{AssetProvider, Color, MapImageRequest};
+use bicit::zones::ZoneOptions;
+use bicit::{Context, Locale, Template, Units};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::write_output;
+
+/// Every combination of data files and templates, rendered into `outdir`.
+pub struct Batch {
+    pub datafiles: Vec<PathBuf>,
+    /// `(name, template)`, the name is used for the output file.
+    pub templates: Vec<(String, Template)>,
+    pub outdir: PathBuf,
+    pub jobs: usize,
+    pub units: Units,
+    pub locale: Locale,
+    pub zone_options: ZoneOptions,
+    pub format: OutputFormat,
+    pub size: ExportSize,
+    pub map_options: MapOptions,
+    /// Use the map style each template asks for instead of `map_options.tiles`.
+    pub template_styles: bool,
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    pub datafile: PathBuf,
+    /// `None` when the data file itself failed, e.g. a parse error.
+    pub template: Option<String>,
+    pub error: Error,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub rendered: usize,
+    pub failures: Vec<Failure>,
+}
+
+/// Map snapshots of one data file, rendered once per distinct size, color and map options.
+#[derive(Default)]
+struct MapCache {
+    maps: Vec<(MapImageRequest, MapOptions, String)>,
+    /// Map options of the template being rendered.
+    options: MapOptions,
+}
+
+impl AssetProvider for MapCache {
+    fn get_image(
+        &self,
+        id: &str,
+        w_px: u32,
+        h_px: u32,
+        track_color: Option<Color>,
+    ) -> Option<String> {
+        if id != "image_map" {
+            return None;
+        }
+        let request = MapImageRequest {
+            w_px,
+            h_px,
+            track_color,
+        };
+        self.maps
+            .iter()
+            .find(|(r, o, _)| *r == request && *o == self.options)
+            .map(|(_, _, href)| href.clone())
+    }
+}
+
+impl Batch {
+    pub fn output_path(&self, datafile: &Path, template: &str) -> PathBuf {
+        let stem = datafile
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "output".to_string());
+        self.outdir
+            .join(format!("{stem}_{template}.{}", self.format.extension()))
+    }
+
+    fn map_options(&self, template: &Template) -> MapOptions {
+        match self.template_styles {
+            true => self.map_options.clone().with_template_style(template),
+            false => self.map_options.clone().with_template_settings(template),
+        }
+    }
+
+    /// Fail when two renders would write the same file, e.g. `a/ride.gpx` and `b/ride.fit`.
+    fn check_outputs(&self) -> Result<()> {
+        let mut outputs: HashMap<PathBuf, (&Path, &str)> = HashMap::new();
+        for datafile in &self.datafiles {
+            for (name, _) in &self.templates {
+                let path = self.output_path(datafile, name);
+                if let Some((other, other_name)) = outputs.insert(path.clone(), (datafile, name)) {
+                    return Err(anyhow!(
+                        "{} [{}] and {} [{}] would both be written to {}, rename one of them",
+                        other.display(),
+                        other_name,
+                        datafile.display(),
+                        name,
+                        path.display()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render everything, spreading data files over `jobs` threads.
+    pub fn run(&self) -> Result<BatchReport> {
+        self.check_outputs()?;
+        fs::create_dir_all(&self.outdir)?;
+
+        let next = AtomicUsize::new(0);
+        let report = Mutex::new(BatchReport::default());
+        thread::scope(|s| {
+            for _ in 0..self.jobs.clamp(1, self.datafiles.len().max(1)) {
+                s.spawn(|| {
+                    while let Some(datafile) =
+                        self.datafiles.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let (rendered, failures) = self.render_datafile(datafile);
+                        let mut report = report.lock().unwrap_or_else(|e| e.into_inner());
+                        report.rendered += rendered;
+                        report.failures.extend(failures);
+                    }
+                });
+            }
+        });
+        Ok(report.into_inner().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn render_datafile(&self, datafile: &Path) -> (usize, Vec<Failure>) {
+        let failure = |template: Option<&str>, error: Error| Failure {
+            datafile: datafile.to_path_buf(),
+            template: template.map(str::to_string),
+            error,
+        };
+
+        // Parse once for all the templates.
+        let mut ctx = Context::new(datafile.to_string_lossy())
+            .with_units(self.units)
+            .with_locale(self.locale)
+            .with_zone_options(self.zone_options);
+        if let Err(e) = ctx.load() {
+            return (0, vec![failure(None, e)]);
+        }
+
+        let mut failures = vec![];
+        let mut maps = MapCache::default();
+        // Maps that couldn't be rendered, with the error.
+        let mut failed_maps = vec![];
+        for (_, template) in &self.templates {
+            let scale = template.export_scale(self.size);
+            let Some(request) = template.desired_map_image_request_with_scale(scale) else {
+                continue;
+            };
+            let options = self.map_options(template);
+            if maps
+                .maps
+                .iter()
+                .any(|(r, o, _)| *r == request && *o == options)
+            {
+                continue;
+            }
+            ctx.set_map_options(options.clone());
+            match ctx.get_image("image_map", request.w_px, request.h_px, request.track_color) {
+                Some(href) => maps.maps.push((request, options, href)),
+                None => {
+                    let error = format!(
+                        "map {}x{} failed: {}",
+                        request.w_px,
+                        request.h_px,
+                        ctx.map_error().unwrap_or_default()
+                    );
+                    failed_maps.push((request, options, error));
+                }
+            }
+        }
+        ctx.cleanup_temp_files();
+
+        let mut rendered = 0;
+        for (name, template) in &self.templates {
+            let scale = template.export_scale(self.size);
+            let options = self.map_options(template);
+            // Don't write an image without its map.
+            if let Some(request) = template.desired_map_image_request_with_scale(scale)
+                && let Some((_, _, error)) = failed_maps
+                    .iter()
+                    .find(|(r, o, _)| *r == request && *o == options)
+            {
+                failures.push(failure(Some(name), anyhow!("{error}")));
+                continue;
+            }
+            maps.options = options.clone();
+            ctx.set_map_options(options);
+            let result = template
+                .apply_with_scale(&ctx, &maps, scale)
+                .and_then(|svg| {
+                    write_output(
+                        &svg,
+                        &self.output_path(datafile, name).to_string_lossy(),
+                        Some(self.format),
+                        self.size,
+                    )
+                });
+            match result {
+                Ok(()) => rendered += 1,
+                Err(e) => failures.push(failure(Some(name), e)),
+            }
+        }
+        (rendered, failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_template(markers: &str) -> Template {
+        Template::new(format!(
+            "<svg width='200' height='200'><image id='image_map' width='200' height='200' \
+             data-map-markers='{markers}'/></svg>"
+        ))
+    }
+
+    fn batch(name: &str, datafiles: &[&str], templates: Vec<(String, Template)>) -> Batch {
+        Batch {
+            datafiles: datafiles.iter().map(PathBuf::from).collect(),
+            templates,
+            outdir: std::env::temp_dir().join(format!("bicit-{name}-{}", std::process::id())),
+            jobs: 1,
+            units: Units::default(),
+            locale: Locale::default(),
+            zone_options: ZoneOptions::default(),
+            format: OutputFormat::Svg,
+            size: ExportSize::Scale(1.0),
+            map_options: MapOptions::default().with_tiles("../bicit/test/tiles".parse().unwrap()),
+            template_styles: false,
+        }
+    }
+
+    #[test]
+    fn maps_follow_template_settings() {
+        let batch = batch(
+            "batch",
+            &["../bicit/test/t1.gpx"],
+            vec![
+                ("plain".to_string(), map_template("")),
+                ("markers".to_string(), map_template("start finish")),
+                ("markers_again".to_string(), map_template("finish start")),
+            ],
+        );
+        let report = batch.run().unwrap();
+        let map = |name| {
+            let svg = fs::read_to_string(batch.outdir.join(format!("t1_{name}.svg"))).unwrap();
+            let start = svg.find("data:image/png;base64,").unwrap();
+            svg[start..].split(['"', '\'']).next().unwrap().to_string()
+        };
+        let maps = ["plain", "markers", "markers_again"].map(map);
+        fs::remove_dir_all(&batch.outdir).unwrap();
+
+        assert_eq!(report.rendered, 3);
+        assert!(report.failures.is_empty());
+        // Same size and track color, but only one template asks for markers.
+        assert_ne!(maps[0], maps[1]);
+        assert_eq!(maps[1], maps[2]);
+    }
+
+    #[test]
+    fn same_output_twice_is_an_error() {
+        let batch = batch(
+            "batch-conflict",
+            &["a/ride.gpx", "b/ride.fit"],
+            vec![("plain".to_string(), map_template(""))],
+        );
+        let err = batch.run().unwrap_err().to_string();
+        assert!(err.contains("ride_plain.svg"), "{err}");
+        assert!(!batch.outdir.exists());
+    }
+
+    #[test]
+    fn failed_map_skips_its_templates() {
+        let mut batch = batch(
+            "batch-map-error",
+            &["../bicit/test/t1.gpx"],
+            vec![
+                ("map".to_string(), map_template("")),
+                (
+                    "text".to_string(),
+                    Template::new("<svg width='10' height='10'/>"),
+                ),
+            ],
+        );
+        batch.map_options = batch.map_options.with_tiles("dir:missing".parse().unwrap());
+        let report = batch.run().unwrap();
+        let written =
+            ["map", "text"].map(|name| batch.outdir.join(format!("t1_{name}.svg")).exists());
+        fs::remove_dir_all(&batch.outdir).unwrap();
+
+        assert_eq!(report.rendered, 1);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].template.as_deref(), Some("map"));
+        assert_eq!(written, [false, true]);
+    }
+}
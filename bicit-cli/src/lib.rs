@@ -1,26 +1,95 @@
+pub mod batch;
+
 use anyhow::Result;
-use bicit::render::render_svg_to_png_bytes;
-use bicit::{Context, Template};
+use bicit::animation::{Animation, AnimationOptions};
+use bicit::render::{ExportSize, OutputFormat, render_svg};
+use bicit::{Context, Summary, Template};
 use std::fs;
 use std::path::PathBuf;
 
-/// Export a GPX file to PNG using a template.
-pub fn export_to_file(template: &Template, context: &Context, outfile: &str) -> Result<()> {
-    let svg = template.apply_context(context)?;
+/// Export a GPX file using a template. The format is taken from `outfile` when `None`.
+pub fn export_to_file(
+    template: &Template,
+    context: &Context,
+    outfile: &str,
+    format: Option<OutputFormat>,
+    size: ExportSize,
+) -> Result<()> {
+    let svg = template.apply_with_scale(context, context, template.export_scale(size))?;
+    write_output(&svg, outfile, format, size)?;
+    if let Some(e) = context.map_error() {
+        eprintln!("Warning: map not rendered: {e}");
+    }
+
+    context.cleanup_temp_files();
+
+    Ok(())
+}
+
+/// Export a multi-activity summary using a template.
+pub fn export_summary_to_file(
+    template: &Template,
+    summary: &Summary,
+    outfile: &str,
+    format: Option<OutputFormat>,
+    size: ExportSize,
+) -> Result<()> {
+    let svg = template.apply_with_scale(summary, summary, template.export_scale(size))?;
+    write_output(&svg, outfile, format, size)?;
+    if let Some(e) = summary.map_error() {
+        eprintln!("Warning: map not rendered: {e}");
+    }
+
+    summary.cleanup_temp_files();
 
+    Ok(())
+}
+
+/// Export an animated replay: a GIF, an APNG (`.png`) or a directory of PNG frames,
+/// depending on the `outfile` extension.
+pub fn export_animation_to_file(
+    template: &Template,
+    context: &Context,
+    outfile: &str,
+    options: AnimationOptions,
+    size: ExportSize,
+) -> Result<()> {
+    Animation::new(template, context)
+        .with_options(options)
+        .with_size(size)
+        .write_to_file(outfile)?;
+
+    context.cleanup_temp_files();
+
+    Ok(())
+}
+
+/// Output file and format: `format` wins and replaces the extension, otherwise it's
+/// guessed from the extension, falling back to PNG.
+pub fn output_path(outfile: &str, format: Option<OutputFormat>) -> (PathBuf, OutputFormat) {
     let outfile = PathBuf::from(outfile);
+    let format = format
+        .or_else(|| OutputFormat::from_path(&outfile))
+        .unwrap_or_default();
     let outbase = if outfile.extension().is_some() {
         outfile.with_extension("")
     } else {
         outfile
     };
 
-    let outpng = outbase.with_extension("png");
+    (outbase.with_extension(format.extension()), format)
+}
 
-    let data = render_svg_to_png_bytes(&svg, 1.0)?;
-    fs::write(outpng, data)?;
+pub(crate) fn write_output(
+    svg: &str,
+    outfile: &str,
+    format: Option<OutputFormat>,
+    size: ExportSize,
+) -> Result<()> {
+    let (outfile, format) = output_path(outfile, format);
 
-    context.cleanup_temp_files();
+    let data = render_svg(svg, size, format)?;
+    fs::write(outfile, data)?;
 
     Ok(())
 }
@@ -1,55 +1,446 @@
-use anyhow::Result;
-use bicit::{Context, Template, get_template_by_name};
-use bicit_cli::export_to_file;
-use clap::Parser;
+use anyhow::{Result, anyhow};
+use bicit::animation::AnimationOptions;
+use bicit::map::{MapOptions, MapRenderer};
+use bicit::render::{DEFAULT_JPEG_QUALITY, ExportSize, OutputFormat};
+use bicit::summary::expand_inputs;
+use bicit::tiles::TileSource;
+use bicit::zones::{ZoneModel, ZoneOptions};
+use bicit::{Context, Locale, Summary, Template, Units, get_template_by_name};
+use bicit_cli::batch::Batch;
+use bicit_cli::{export_animation_to_file, export_summary_to_file, export_to_file};
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::thread;
 
 #[derive(Parser, Debug)]
-#[command(version = "0.1", author = "Luca Ognibene <luca.ognibene@gmail.com>")]
+#[command(
+    version = "0.1",
+    author = "Luca Ognibene <luca.ognibene@gmail.com>",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Opts {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Template name (embedded) or path to SVG file
     #[arg(short, long, default_value = "story_split")]
     template: String,
-    /// Path to GPX data file
+    /// Path to GPX, FIT or TCX data file
+    #[arg(short, long, required = true)]
+    datafile: Option<String>,
+    /// Output file, default value is same name as gpx data file
+    #[arg(short, long, default_value = "")]
+    outfile: String,
+    #[command(flatten)]
+    output: OutputOpts,
+    #[command(flatten)]
+    map: MapOpts,
+    #[command(flatten)]
+    zones: ZoneOpts,
+    /// Unit system: metric, imperial, nautical or mixed
+    #[arg(short, long, default_value = "metric")]
+    units: Units,
+    /// Locale for numbers, dates and labels: en, it or de
+    #[arg(short, long, default_value = "en")]
+    locale: Locale,
+    /// strftime-like format for the ride date, e.g. "%A %d/%m/%Y"
+    #[arg(long)]
+    date_format: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Render one image summarizing many activities, e.g. a month or a year
+    Summary(SummaryOpts),
+    /// Render every combination of data files and templates into a directory
+    Batch(BatchOpts),
+    /// Render an animated replay of a ride: GIF, APNG or numbered PNG frames
+    Animate(AnimateOpts),
+}
+
+#[derive(Args, Debug)]
+struct SummaryOpts {
+    /// Data files, directories or file name patterns like "rides/*.gpx"
+    #[arg(required = true)]
+    inputs: Vec<String>,
+    /// Template name (embedded) or path to SVG file
+    #[arg(short, long, default_value = "summary_card")]
+    template: String,
+    /// Output file
+    #[arg(short, long, default_value = "summary.png")]
+    outfile: String,
+    #[command(flatten)]
+    output: OutputOpts,
+    #[command(flatten)]
+    map: MapOpts,
+    /// Title, default value is the month, year or date range of the rides
+    #[arg(long)]
+    title: Option<String>,
+    /// Only rides from this date, as YYYY-MM-DD
+    #[arg(long)]
+    since: Option<NaiveDate>,
+    /// Only rides until this date (included), as YYYY-MM-DD
+    #[arg(long)]
+    until: Option<NaiveDate>,
+    /// Unit system: metric, imperial, nautical or mixed
+    #[arg(short, long, default_value = "metric")]
+    units: Units,
+    /// Locale for numbers, dates and labels: en, it or de
+    #[arg(short, long, default_value = "en")]
+    locale: Locale,
+}
+
+#[derive(Args, Debug)]
+struct BatchOpts {
+    /// Data files, directories or file name patterns like "rides/*.gpx"
+    #[arg(short, long, required = true)]
+    datafile: Vec<String>,
+    /// Template names (embedded) or paths to SVG files, default is all embedded templates
+    #[arg(short, long)]
+    template: Vec<String>,
+    /// Output directory
+    #[arg(short, long, default_value = "out")]
+    outdir: PathBuf,
+    #[command(flatten)]
+    output: OutputOpts,
+    #[command(flatten)]
+    map: MapOpts,
+    #[command(flatten)]
+    zones: ZoneOpts,
+    /// Parallel jobs, default is the number of cores
+    #[arg(short, long)]
+    jobs: Option<usize>,
+    /// Unit system: metric, imperial, nautical or mixed
+    #[arg(short, long, default_value = "metric")]
+    units: Units,
+    /// Locale for numbers, dates and labels: en, it or de
+    #[arg(short, long, default_value = "en")]
+    locale: Locale,
+}
+
+#[derive(Args, Debug)]
+struct AnimateOpts {
+    /// Path to GPX, FIT or TCX data file
     #[arg(short, long)]
     datafile: String,
-    /// Output basename, default value is same name as gpx data file
+    /// Template name (embedded) or path to SVG file
+    #[arg(short, long, default_value = "story_split")]
+    template: String,
+    /// Output .gif or .png (APNG) file, or a directory for numbered PNG frames, default
+    /// value is same name as the data file with .gif extension
     #[arg(short, long, default_value = "")]
     outfile: String,
+    /// Number of frames
+    #[arg(long, default_value_t = 60)]
+    frames: usize,
+    /// Frames per second
+    #[arg(long, default_value_t = 20)]
+    fps: u16,
+    /// Seconds the last frame stays on screen
+    #[arg(long, default_value_t = 2.0)]
+    hold: f64,
+    #[command(flatten)]
+    size: SizeOpts,
+    #[command(flatten)]
+    map: MapOpts,
+    #[command(flatten)]
+    zones: ZoneOpts,
+    /// Unit system: metric, imperial, nautical or mixed
+    #[arg(short, long, default_value = "metric")]
+    units: Units,
+    /// Locale for numbers, dates and labels: en, it or de
+    #[arg(short, long, default_value = "en")]
+    locale: Locale,
 }
 
-fn main() -> Result<()> {
-    let opts: Opts = Opts::parse();
+#[derive(Args, Debug)]
+struct OutputOpts {
+    /// Output format: png, jpg, webp, pdf or svg, default from the output file extension
+    #[arg(short, long)]
+    format: Option<OutputFormat>,
+    /// JPEG quality, 1 to 100
+    #[arg(long, default_value_t = DEFAULT_JPEG_QUALITY, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+    #[command(flatten)]
+    size: SizeOpts,
+}
 
-    let outfile = if opts.outfile == "" {
+#[derive(Args, Debug)]
+struct SizeOpts {
+    /// Image scale factor, e.g. 2 for retina screens
+    #[arg(long, conflicts_with_all = ["width", "height"])]
+    scale: Option<f32>,
+    /// Image width in px, e.g. 1080 for Instagram
+    #[arg(long)]
+    width: Option<u32>,
+    /// Image height in px, with --width the template is fitted and centered
+    #[arg(long)]
+    height: Option<u32>,
+}
+
+#[derive(Args, Debug)]
+struct MapOpts {
+    /// Map tiles: osm, topo, cycle, satellite, dark, light, a tile URL like
+    /// "https://tile.example.com/{z}/{x}/{y}.png?key={key}", an .mbtiles file or a
+    /// {z}/{x}/{y}.png directory for offline maps. Vector tile URLs end in .pbf or are
+    /// prefixed by "vector:". Default is the template style or osm
+    #[arg(long)]
+    tiles: Option<TileSource>,
+    /// API key for the {key} placeholder of tile URLs
+    #[arg(long, env = "BICIT_TILES_KEY", hide_env_values = true)]
+    tiles_key: Option<String>,
+    /// Map renderer: auto (GPU, CPU when no GPU is available), gpu or cpu
+    #[arg(long, default_value = "auto")]
+    map_renderer: MapRenderer,
+}
+
+#[derive(Args, Debug)]
+struct ZoneOpts {
+    /// Max heart rate in bpm, for the heart rate zones
+    #[arg(long, conflicts_with = "lthr")]
+    hr_max: Option<f64>,
+    /// Lactate threshold heart rate in bpm, for the heart rate zones
+    #[arg(long)]
+    lthr: Option<f64>,
+    /// Functional threshold power in W, for the power zones
+    #[arg(long)]
+    ftp: Option<f64>,
+    /// Zone model: 5 or 7 zones
+    #[arg(long, default_value = "5")]
+    zones: ZoneModel,
+}
+
+impl ZoneOpts {
+    fn options(&self) -> ZoneOptions {
+        let mut options = ZoneOptions::default().with_model(self.zones);
+        if let Some(bpm) = self.hr_max {
+            options = options.with_hr_max(bpm);
+        }
+        if let Some(bpm) = self.lthr {
+            options = options.with_lthr(bpm);
+        }
+        if let Some(watts) = self.ftp {
+            options = options.with_ftp(watts);
+        }
+        options
+    }
+}
+
+impl MapOpts {
+    fn options(&self) -> MapOptions {
+        let mut options = MapOptions::default()
+            .with_tiles(self.tiles.clone().unwrap_or_default())
+            .with_renderer(self.map_renderer);
+        if let Some(key) = &self.tiles_key {
+            options = options.with_api_key(key);
+        }
+        options
+    }
+
+    /// `--tiles`, else the style asked by `template`, with the template map colors and
+    /// markers.
+    fn options_for(&self, template: &Template) -> MapOptions {
+        match self.tiles {
+            Some(_) => self.options().with_template_settings(template),
+            None => self.options().with_template_style(template),
+        }
+    }
+}
+
+impl OutputOpts {
+    /// `--format`, or the format of the `outfile` extension, with `--quality` applied.
+    fn format(&self, outfile: &str) -> Option<OutputFormat> {
+        self.format
+            .or_else(|| OutputFormat::from_path(outfile))
+            .map(|f| f.with_quality(self.quality))
+    }
+
+    fn size(&self) -> ExportSize {
+        self.size.size()
+    }
+}
+
+impl SizeOpts {
+    fn size(&self) -> ExportSize {
+        match (self.scale, self.width, self.height) {
+            (_, Some(width), Some(height)) => ExportSize::Exact { width, height },
+            (_, Some(width), None) => ExportSize::Width(width),
+            (_, None, Some(height)) => ExportSize::Height(height),
+            (Some(scale), None, None) => ExportSize::Scale(scale),
+            (None, None, None) => ExportSize::default(),
+        }
+    }
+}
+
+fn load_template(name: &str, datafile: &str, outfile: &str) -> Result<Template> {
+    // Try to find embedded template first, then fall back to file path
+    if let Some(embedded) = get_template_by_name(name) {
+        println!("Using embedded template '{name}' for {datafile} -> {outfile}");
+        Ok(Template::new(embedded.content))
+    } else {
+        // Assume it's a file path
+        println!("Using template file '{name}' for {datafile} -> {outfile}");
+        let content = fs::read_to_string(name)?;
+        Ok(Template::new(content))
+    }
+}
+
+fn summary(opts: SummaryOpts) -> Result<()> {
+    let mut summary = Summary::new()
+        .with_units(opts.units)
+        .with_locale(opts.locale)
+        .with_period(opts.since, opts.until);
+    if let Some(title) = opts.title {
+        summary = summary.with_title(title);
+    }
+    summary.load_inputs(&opts.inputs)?;
+    if summary.ride_count() == 0 {
+        return Err(anyhow!("no rides found"));
+    }
+
+    let template = load_template(
+        &opts.template,
+        &format!("{} rides", summary.ride_count()),
+        &opts.outfile,
+    )?;
+    let summary = summary.with_map_options(opts.map.options_for(&template));
+    export_summary_to_file(
+        &template,
+        &summary,
+        &opts.outfile,
+        opts.output.format(&opts.outfile),
+        opts.output.size(),
+    )
+}
+
+fn batch(opts: BatchOpts) -> Result<()> {
+    let mut templates = vec![];
+    if opts.template.is_empty() {
+        for t in bicit::get_templates() {
+            templates.push((t.name.to_string(), Template::new(t.content)));
+        }
+    }
+    for name in &opts.template {
+        if let Some(embedded) = get_template_by_name(name) {
+            templates.push((name.clone(), Template::new(embedded.content)));
+            continue;
+        }
+        // A file path or a pattern like "templates/*.svg".
+        for path in expand_inputs(&[name])? {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| name.clone());
+            templates.push((stem, Template::new(fs::read_to_string(&path)?)));
+        }
+    }
+
+    let batch = Batch {
+        datafiles: expand_inputs(&opts.datafile)?,
+        templates,
+        outdir: opts.outdir,
+        jobs: opts.jobs.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        }),
+        units: opts.units,
+        locale: opts.locale,
+        zone_options: opts.zones.options(),
+        format: opts.output.format("").unwrap_or_default(),
+        size: opts.output.size(),
+        map_options: opts.map.options(),
+        template_styles: opts.map.tiles.is_none(),
+    };
+    println!(
+        "Rendering {} data files x {} templates into {}",
+        batch.datafiles.len(),
+        batch.templates.len(),
+        batch.outdir.display()
+    );
+
+    let report = batch.run()?;
+    println!("{} images rendered", report.rendered);
+    if report.failures.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("{} failures:", report.failures.len());
+    for f in &report.failures {
+        match &f.template {
+            Some(t) => eprintln!("  {} [{}]: {:#}", f.datafile.display(), t, f.error),
+            None => eprintln!("  {}: {:#}", f.datafile.display(), f.error),
+        }
+    }
+    Err(anyhow!("{} failures", report.failures.len()))
+}
+
+fn animate(opts: AnimateOpts) -> Result<()> {
+    let outfile = if opts.outfile.is_empty() {
         Path::new(&opts.datafile)
             .file_stem()
-            .map(|s| format!("{}.png", s.to_string_lossy()))
-            .unwrap_or_else(|| "output.png".to_string())
+            .map(|s| format!("{}.gif", s.to_string_lossy()))
+            .unwrap_or_else(|| "replay.gif".to_string())
     } else {
         opts.outfile
     };
+    let template = load_template(&opts.template, &opts.datafile, &outfile)?;
 
-    // Try to find embedded template first, then fall back to file path
-    let template = if let Some(embedded) = get_template_by_name(&opts.template) {
-        println!(
-            "Using embedded template '{}' for {} -> {}",
-            opts.template, opts.datafile, outfile
-        );
-        Template::new(embedded.content)
+    let mut ctx = Context::new(&opts.datafile)
+        .with_units(opts.units)
+        .with_locale(opts.locale)
+        .with_zone_options(opts.zones.options())
+        .with_map_options(opts.map.options_for(&template));
+    ctx.load()?;
+
+    let options = AnimationOptions::default()
+        .with_frames(opts.frames)
+        .with_fps(opts.fps)
+        .with_hold(opts.hold);
+    export_animation_to_file(&template, &ctx, &outfile, options, opts.size.size())
+}
+
+fn main() -> Result<()> {
+    let opts: Opts = Opts::parse();
+
+    match opts.command {
+        Some(Command::Summary(summary_opts)) => return summary(summary_opts),
+        Some(Command::Batch(batch_opts)) => return batch(batch_opts),
+        Some(Command::Animate(animate_opts)) => return animate(animate_opts),
+        None => {}
+    }
+    let datafile = opts.datafile.unwrap_or_default();
+
+    let ext = opts.output.format.unwrap_or_default().extension();
+    let outfile = if opts.outfile == "" {
+        Path::new(&datafile)
+            .file_stem()
+            .map(|s| format!("{}.{ext}", s.to_string_lossy()))
+            .unwrap_or_else(|| format!("output.{ext}"))
     } else {
-        // Assume it's a file path
-        println!(
-            "Using template file '{}' for {} -> {}",
-            opts.template, opts.datafile, outfile
-        );
-        let content = fs::read_to_string(&opts.template)?;
-        Template::new(content)
+        opts.outfile
     };
 
-    let mut ctx = Context::new(&opts.datafile);
+    let template = load_template(&opts.template, &datafile, &outfile)?;
+
+    let mut ctx = Context::new(&datafile)
+        .with_units(opts.units)
+        .with_locale(opts.locale)
+        .with_zone_options(opts.zones.options())
+        .with_map_options(opts.map.options_for(&template));
+    if let Some(f) = opts.date_format {
+        ctx = ctx.with_date_format(f);
+    }
     ctx.load()?;
 
-    export_to_file(&template, &ctx, &outfile)
+    export_to_file(
+        &template,
+        &ctx,
+        &outfile,
+        opts.output.format(&outfile),
+        opts.output.size(),
+    )
 }
@@ -3,7 +3,7 @@ use crate::BicitApp;
 use crate::CartesianSize;
 use crate::ImageMapAssetProvider;
 use crate::Template;
-use bicit::render::render_svg_to_png_bytes;
+use bicit::render::render_svg;
 
 #[cfg(target_arch = "wasm32")]
 use crate::MapJobKind;
@@ -77,15 +77,16 @@ impl BicitApp {
             }
         };
 
-        let png = match render_svg_to_png_bytes(&svg, 1.0) {
-            Ok(png) => png,
+        let format = self.export_format;
+        let data = match render_svg(&svg, self.export_size, format) {
+            Ok(data) => data,
             Err(e) => {
                 self.status_message = Some(format!("SVG render error: {e}"));
                 return;
             }
         };
 
-        if let Err(e) = download_bytes_as_file(filename, &png, "image/png") {
+        if let Err(e) = download_bytes_as_file(filename, &data, format.mime_type()) {
             self.status_message = Some(format!("Export failed: {e}"));
         } else {
             self.status_message = Some(format!("Downloaded {filename}"));
@@ -107,16 +108,18 @@ impl BicitApp {
             return;
         };
 
+        let ext = self.export_format.extension();
         let filename = self
             .gpx_path
             .as_ref()
             .and_then(|p| p.file_stem())
-            .map(|s| format!("{}.png", s.to_string_lossy()))
-            .unwrap_or_else(|| "output.png".to_string());
+            .map(|s| format!("{}.{ext}", s.to_string_lossy()))
+            .unwrap_or_else(|| format!("output.{ext}"));
 
         let template = &self.templates[self.selected_template_idx];
         let bicit_template = Template::new(template.content);
-        let request = bicit_template.desired_map_image_request();
+        let scale = bicit_template.export_scale(self.export_size);
+        let request = bicit_template.desired_map_image_request_with_scale(scale);
 
         // If the template doesn't ask for a map image, export immediately.
         if request.is_none() {
@@ -125,9 +128,10 @@ impl BicitApp {
         }
 
         let req = request.expect("request checked");
+        let options = self.map_options();
 
-        if let Some((cached_req, href)) = &self.preview_map {
-            if *cached_req == req {
+        if let Some((cached_req, cached_options, href)) = &self.preview_map {
+            if *cached_req == req && *cached_options == options {
                 self.export_wasm_with_map_href(
                     &filename,
                     template.content,
@@ -155,6 +159,7 @@ impl BicitApp {
                 template_svg: template.content,
             },
             req,
+            options,
             coords,
             egui_ctx.clone(),
         );
@@ -185,14 +190,16 @@ impl BicitApp {
                 }
             };
 
+            let format = self.export_format;
+            let ext = format.extension();
             let default_name = gpx_path
                 .file_stem()
-                .map(|s| format!("{}.png", s.to_string_lossy()))
-                .unwrap_or_else(|| "output.png".to_string());
+                .map(|s| format!("{}.{ext}", s.to_string_lossy()))
+                .unwrap_or_else(|| format!("output.{ext}"));
 
             let Some(out_path) = rfd::FileDialog::new()
                 .set_file_name(&default_name)
-                .add_filter("PNG", &["png"])
+                .add_filter(format.name(), &[ext])
                 .save_file()
             else {
                 return;
@@ -200,8 +207,10 @@ impl BicitApp {
 
             let template = &self.templates[self.selected_template_idx];
             let bicit_template = Template::new(template.content);
-            let request = bicit_template.desired_map_image_request();
+            let scale = bicit_template.export_scale(self.export_size);
+            let request = bicit_template.desired_map_image_request_with_scale(scale);
 
+            let options = self.map_options();
             let map_href = match request {
                 Some(req) => match bicit::map::render_track_map_href_with_wgpu(
                     self.wgpu_device.clone(),
@@ -209,6 +218,8 @@ impl BicitApp {
                     coords,
                     CartesianSize::<u32>::new(req.w_px, req.h_px),
                     req.track_color,
+                    &options,
+                    &gpx_ctx.map_overlay(&options),
                 ) {
                     Ok(href) => Some(href),
                     Err(e) => {
@@ -229,16 +240,16 @@ impl BicitApp {
                 }
             };
 
-            let png = match render_svg_to_png_bytes(&svg, 1.0) {
-                Ok(png) => png,
+            let data = match render_svg(&svg, self.export_size, format) {
+                Ok(data) => data,
                 Err(e) => {
                     self.status_message = Some(format!("SVG render error: {e}"));
                     return;
                 }
             };
 
-            if let Err(e) = std::fs::write(&out_path, png) {
-                self.status_message = Some(format!("Failed to write PNG: {e}"));
+            if let Err(e) = std::fs::write(&out_path, data) {
+                self.status_message = Some(format!("Failed to write {}: {e}", format.name()));
                 return;
             }
 
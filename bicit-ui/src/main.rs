@@ -2,9 +2,11 @@ pub(crate) mod export;
 pub(crate) mod ui;
 
 use anyhow::{Result, anyhow};
-use bicit::render::parse_svg_tree;
+use bicit::map::MapOptions;
+use bicit::render::{ExportSize, OutputFormat, parse_svg_tree};
 use bicit::template::{AssetProvider, MapImageRequest, TRANSPARENT_PNG_DATA_URL};
-use bicit::{Context, EmbeddedTemplate, Template, get_templates, map};
+use bicit::tiles::TileSource;
+use bicit::{Context, EmbeddedTemplate, Locale, Template, Units, get_templates, map};
 use eframe::wgpu::{Device as WgpuDevice, Queue as WgpuQueue};
 use egui::{ColorImage, TextureHandle, TextureOptions, Vec2};
 use galileo::layer::raster_tile_layer::RasterTileLayerBuilder;
@@ -72,6 +74,7 @@ enum MapJobKind {
 struct MapJobInFlight {
     kind: MapJobKind,
     request: MapImageRequest,
+    options: MapOptions,
 
     #[cfg(target_arch = "wasm32")]
     result: Rc<RefCell<Option<anyhow::Result<String>>>>,
@@ -120,6 +123,12 @@ struct BicitApp {
     // Template state
     templates: &'static [EmbeddedTemplate],
     selected_template_idx: usize,
+    units: Units,
+    locale: Locale,
+    export_format: OutputFormat,
+    export_size: ExportSize,
+    /// `None` uses the style of the template.
+    map_style: Option<TileSource>,
 
     // GPX state
     gpx_path: Option<PathBuf>,
@@ -130,7 +139,7 @@ struct BicitApp {
     map_job: Option<MapJobInFlight>,
 
     // Cached rendered map href for preview
-    preview_map: Option<(MapImageRequest, String)>,
+    preview_map: Option<(MapImageRequest, MapOptions, String)>,
 
     // Preview state
     preview_texture: Option<TextureHandle>,
@@ -164,6 +173,11 @@ impl BicitApp {
             resolution: initial_resolution,
             templates: get_templates(),
             selected_template_idx: 0,
+            units: Units::default(),
+            locale: Locale::default(),
+            export_format: OutputFormat::default(),
+            export_size: ExportSize::default(),
+            map_style: None,
             gpx_path: None,
             gpx_context: None,
             #[cfg(target_arch = "wasm32")]
@@ -179,7 +193,9 @@ impl BicitApp {
     #[cfg(not(target_arch = "wasm32"))]
     fn load_gpx(&mut self, path: PathBuf) -> Result<()> {
         let filename = path.to_str().ok_or(anyhow!("Invalid path"))?;
-        let mut ctx = Context::new(filename);
+        let mut ctx = Context::new(filename)
+            .with_units(self.units)
+            .with_locale(self.locale);
         ctx.load()?;
         let res = self.load_from_context(ctx);
         self.gpx_path = Some(path);
@@ -223,7 +239,9 @@ impl BicitApp {
 
     #[cfg(target_arch = "wasm32")]
     fn load_gpx_from_bytes(&mut self, filename: String, bytes: Vec<u8>) -> Result<()> {
-        let mut ctx = Context::new(&filename);
+        let mut ctx = Context::new(&filename)
+            .with_units(self.units)
+            .with_locale(self.locale);
         ctx.load_from_bytes(&bytes)?;
 
         self.load_from_context(ctx)
@@ -240,7 +258,7 @@ impl BicitApp {
         let cell2 = cell.clone();
         spawn_local(async move {
             let picked = rfd::AsyncFileDialog::new()
-                .add_filter("gpx", &["gpx"])
+                .add_filter("gpx / fit / tcx", &["gpx", "fit", "tcx"])
                 .pick_file()
                 .await;
             let result = match picked {
@@ -289,12 +307,18 @@ impl BicitApp {
         &mut self,
         kind: MapJobKind,
         request: MapImageRequest,
+        options: MapOptions,
         coords: Vec<geo_types::Point<f64>>,
         egui_ctx: egui::Context,
     ) {
         if self.map_job.is_some() {
             return;
         }
+        let overlay = self
+            .gpx_context
+            .as_ref()
+            .map(|ctx| ctx.map_overlay(&options))
+            .unwrap_or_default();
 
         #[cfg(target_arch = "wasm32")]
         {
@@ -304,6 +328,7 @@ impl BicitApp {
             let result = Rc::new(RefCell::new(None));
             let result_cell = result.clone();
             let egui_ctx = egui_ctx.clone();
+            let job_options = options.clone();
             spawn_local(async move {
                 let res = bicit::map::render_track_map_href_with_wgpu_async(
                     device,
@@ -311,6 +336,8 @@ impl BicitApp {
                     &coords,
                     CartesianSize::<u32>::new(request.w_px, request.h_px),
                     request.track_color,
+                    &job_options,
+                    &overlay,
                 )
                 .await;
                 *result_cell.borrow_mut() = Some(res);
@@ -320,6 +347,7 @@ impl BicitApp {
             self.map_job = Some(MapJobInFlight {
                 kind,
                 request,
+                options,
                 result,
             });
         }
@@ -332,6 +360,7 @@ impl BicitApp {
             let queue = self.wgpu_queue.clone();
 
             let (tx, rx) = mpsc::channel();
+            let job_options = options.clone();
             thread::spawn(move || {
                 let res = bicit::map::render_track_map_href_with_wgpu(
                     device,
@@ -339,11 +368,18 @@ impl BicitApp {
                     &coords,
                     CartesianSize::<u32>::new(request.w_px, request.h_px),
                     request.track_color,
+                    &job_options,
+                    &overlay,
                 );
                 let _ = tx.send(res);
             });
 
-            self.map_job = Some(MapJobInFlight { kind, request, rx });
+            self.map_job = Some(MapJobInFlight {
+                kind,
+                request,
+                options,
+                rx,
+            });
         }
     }
 
@@ -372,7 +408,7 @@ impl BicitApp {
         match result {
             Ok(href) => match in_flight.kind {
                 MapJobKind::Preview => {
-                    self.preview_map = Some((in_flight.request, href));
+                    self.preview_map = Some((in_flight.request, in_flight.options, href));
                     self.preview_dirty = true;
                     egui_ctx.request_repaint();
                 }
@@ -392,7 +428,43 @@ impl BicitApp {
         }
     }
 
+    fn set_units(&mut self, units: Units) {
+        self.units = units;
+        if let Some(ctx) = self.gpx_context.as_mut() {
+            ctx.set_units(units);
+        }
+        self.preview_dirty = true;
+    }
+
+    fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+        if let Some(ctx) = self.gpx_context.as_mut() {
+            ctx.set_locale(locale);
+        }
+        self.preview_dirty = true;
+    }
+
+    /// Map tiles for the selected template: the chosen style, else the template one.
+    fn map_options(&self) -> MapOptions {
+        let template = Template::new(self.templates[self.selected_template_idx].content);
+        match &self.map_style {
+            Some(tiles) => MapOptions::default()
+                .with_tiles(tiles.clone())
+                .with_template_settings(&template),
+            None => MapOptions::default().with_template_style(&template),
+        }
+    }
+
+    fn set_map_style(&mut self, style: Option<TileSource>) {
+        self.map_style = style;
+        self.preview_dirty = true;
+    }
+
     fn regenerate_preview(&mut self, ctx: &egui::Context) {
+        let options = self.map_options();
+        if let Some(gpx_ctx) = self.gpx_context.as_mut() {
+            gpx_ctx.set_map_options(options.clone());
+        }
         let Some(gpx_ctx) = self.gpx_context.as_ref() else {
             self.preview_texture = None;
             self.preview_dirty = false;
@@ -406,8 +478,8 @@ impl BicitApp {
         let map_href = match request {
             None => None,
             Some(req) => {
-                if let Some((cached_req, href)) = &self.preview_map {
-                    if *cached_req == req {
+                if let Some((cached_req, cached_options, href)) = &self.preview_map {
+                    if *cached_req == req && *cached_options == options {
                         Some(href.clone())
                     } else {
                         if self.map_job.is_none() {
@@ -426,7 +498,13 @@ impl BicitApp {
                                 return;
                             }
 
-                            self.start_map_job(MapJobKind::Preview, req, coords, ctx.clone());
+                            self.start_map_job(
+                                MapJobKind::Preview,
+                                req,
+                                options,
+                                coords,
+                                ctx.clone(),
+                            );
                         }
 
                         // Template wants a map but we don't have it yet.
@@ -451,7 +529,7 @@ impl BicitApp {
                             return;
                         }
 
-                        self.start_map_job(MapJobKind::Preview, req, coords, ctx.clone());
+                        self.start_map_job(MapJobKind::Preview, req, options, coords, ctx.clone());
                     }
 
                     self.preview_texture = None;
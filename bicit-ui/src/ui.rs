@@ -1,8 +1,22 @@
 use crate::BicitApp;
+use bicit::render::{ExportSize, OutputFormat};
+use bicit::tiles::TileSource;
+use bicit::{Locale, Units};
 use egui::{Align, Layout, ScrollArea, Vec2};
 
 const NARROW_BREAKPOINT: f32 = 700.0;
 
+const EXPORT_SIZES: [ExportSize; 5] = [
+    ExportSize::Scale(1.0),
+    ExportSize::Scale(2.0),
+    ExportSize::Scale(3.0),
+    ExportSize::Width(1080),
+    ExportSize::Exact {
+        width: 1080,
+        height: 1920,
+    },
+];
+
 impl eframe::App for BicitApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let screen_width = ctx.input(|i| i.viewport_rect().width());
@@ -23,7 +37,7 @@ impl eframe::App for BicitApp {
                     #[cfg(not(target_arch = "wasm32"))]
                     {
                         if let Some(path) = rfd::FileDialog::new()
-                            .add_filter("GPX", &["gpx"])
+                            .add_filter("GPX / FIT / TCX", &["gpx", "fit", "tcx"])
                             .pick_file()
                             && let Err(e) = self.load_gpx(path)
                         {
@@ -64,15 +78,87 @@ impl eframe::App for BicitApp {
 
             ui.add_space(4.0);
 
+            // Units and language selector row
+            ui.horizontal(|ui| {
+                ui.label("Units:");
+                for units in Units::ALL {
+                    if ui
+                        .selectable_label(units == self.units, units.name())
+                        .clicked()
+                        && units != self.units
+                    {
+                        self.set_units(units);
+                    }
+                }
+
+                ui.separator();
+                ui.label("Language:");
+                for locale in Locale::ALL {
+                    if ui
+                        .selectable_label(locale == self.locale, locale.code())
+                        .clicked()
+                        && locale != self.locale
+                    {
+                        self.set_locale(locale);
+                    }
+                }
+
+                ui.separator();
+                ui.label("Map:");
+                let selected = self
+                    .map_style
+                    .as_ref()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| "template".to_string());
+                egui::ComboBox::from_id_salt("map_style")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        if ui
+                            .selectable_label(self.map_style.is_none(), "template")
+                            .clicked()
+                        {
+                            self.set_map_style(None);
+                        }
+                        for name in TileSource::STYLES {
+                            let style: TileSource = name.parse().expect("built-in style");
+                            if ui
+                                .selectable_label(self.map_style.as_ref() == Some(&style), name)
+                                .clicked()
+                            {
+                                self.set_map_style(Some(style));
+                            }
+                        }
+                    });
+            });
+
+            ui.add_space(4.0);
+
             // Export row
             ui.horizontal(|ui| {
                 let export_enabled = self.gpx_context.is_some();
                 if ui
-                    .add_enabled(export_enabled, egui::Button::new("Export PNG"))
+                    .add_enabled(
+                        export_enabled,
+                        egui::Button::new(format!("Export {}", self.export_format.name())),
+                    )
                     .clicked()
                 {
                     self.export(ctx);
                 }
+                egui::ComboBox::from_id_salt("export_format")
+                    .selected_text(self.export_format.name())
+                    .show_ui(ui, |ui| {
+                        for format in OutputFormat::ALL {
+                            ui.selectable_value(&mut self.export_format, format, format.name());
+                        }
+                    });
+                egui::ComboBox::from_id_salt("export_size")
+                    .selected_text(self.export_size.to_string())
+                    .show_ui(ui, |ui| {
+                        for size in EXPORT_SIZES {
+                            ui.selectable_value(&mut self.export_size, size, size.to_string());
+                        }
+                    });
 
                 if let Some(ref msg) = self.status_message {
                     ui.label(msg);
@@ -0,0 +1,260 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use geo_types::Point;
+
+use crate::fit::read_fit;
+use crate::sensors::{Sensors, parse_gpx_extensions};
+use crate::tcx::read_tcx;
+
+/// A single recorded point, independent of the file format it was read from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// WGS84 lon/lat.
+    pub point: Point<f64>,
+    pub time: Option<DateTime<Utc>>,
+    pub elevation: Option<f64>,
+    pub sensors: Sensors,
+}
+
+/// A named place, e.g. a GPX `<wpt>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waypoint {
+    /// WGS84 lon/lat.
+    pub point: Point<f64>,
+    pub name: Option<String>,
+}
+
+/// Sensor values recorded without a position, e.g. a FIT record or a TCX trackpoint
+/// without a GPS fix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensorRecord {
+    pub time: DateTime<Utc>,
+    pub sensors: Sensors,
+}
+
+/// Totals and averages of a lap as recorded by the device, each `None` when missing.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Lap {
+    pub start_time: Option<DateTime<Utc>>,
+    /// Seconds.
+    pub total_time: Option<f64>,
+    /// Metres.
+    pub distance: Option<f64>,
+    pub calories: Option<f64>,
+    pub hr_avg: Option<f64>,
+    pub hr_max: Option<f64>,
+    pub cadence_avg: Option<f64>,
+    pub power_avg: Option<f64>,
+}
+
+/// A recorded activity: one or more segments of samples plus some metadata.
+#[derive(Debug, Default, Clone)]
+pub struct Activity {
+    pub name: Option<String>,
+    pub sport: Option<String>,
+    pub segments: Vec<Vec<Sample>>,
+    /// Sensor values between or around the samples, in time order.
+    pub sensor_records: Vec<SensorRecord>,
+    pub laps: Vec<Lap>,
+    pub waypoints: Vec<Waypoint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gpx,
+    Fit,
+    Tcx,
+}
+
+impl Format {
+    /// Detect the file format from the magic bytes, falling back to the file extension.
+    pub fn detect(filename: &str, bytes: &[u8]) -> Format {
+        if bytes.len() >= 12 && &bytes[8..12] == b".FIT" {
+            return Format::Fit;
+        }
+
+        // Both GPX and TCX are XML: look for the TCX root element near the start.
+        let head = &bytes[..bytes.len().min(1024)];
+        if head
+            .windows(b"<TrainingCenterDatabase".len())
+            .any(|w| w == b"<TrainingCenterDatabase")
+        {
+            return Format::Tcx;
+        }
+
+        let ext = Path::new(filename)
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("fit") => Format::Fit,
+            Some("tcx") => Format::Tcx,
+            _ => Format::Gpx,
+        }
+    }
+}
+
+/// A parser turning raw file content into a normalized [`Activity`].
+///
+/// Implement this to plug a custom format into `Context` with `Context::with_reader`.
+pub trait ActivityReader: Send + Sync {
+    /// Whether this reader understands the given file.
+    fn accepts(&self, filename: &str, bytes: &[u8]) -> bool;
+
+    fn read(&self, bytes: &[u8]) -> Result<Activity>;
+}
+
+pub struct GpxReader;
+pub struct FitReader;
+pub struct TcxReader;
+
+impl ActivityReader for GpxReader {
+    fn accepts(&self, filename: &str, bytes: &[u8]) -> bool {
+        Format::detect(filename, bytes) == Format::Gpx
+    }
+
+    fn read(&self, bytes: &[u8]) -> Result<Activity> {
+        read_gpx(bytes)
+    }
+}
+
+impl ActivityReader for FitReader {
+    fn accepts(&self, filename: &str, bytes: &[u8]) -> bool {
+        Format::detect(filename, bytes) == Format::Fit
+    }
+
+    fn read(&self, bytes: &[u8]) -> Result<Activity> {
+        read_fit(bytes)
+    }
+}
+
+impl ActivityReader for TcxReader {
+    fn accepts(&self, filename: &str, bytes: &[u8]) -> bool {
+        Format::detect(filename, bytes) == Format::Tcx
+    }
+
+    fn read(&self, bytes: &[u8]) -> Result<Activity> {
+        read_tcx(bytes)
+    }
+}
+
+/// Read an activity from `bytes`, picking the parser with [`Format::detect`].
+pub fn read_activity(filename: &str, bytes: &[u8]) -> Result<Activity> {
+    match Format::detect(filename, bytes) {
+        Format::Gpx => GpxReader.read(bytes),
+        Format::Fit => FitReader.read(bytes),
+        Format::Tcx => TcxReader.read(bytes),
+    }
+}
+
+pub fn read_gpx(bytes: &[u8]) -> Result<Activity> {
+    let gpx = gpx::read(Cursor::new(bytes))?;
+    // The gpx crate skips <extensions>, so sensor channels are read in a second pass.
+    let sensors = parse_gpx_extensions(bytes)?;
+    let mut sensors_it = sensors.into_iter();
+
+    let name = gpx
+        .tracks
+        .iter()
+        .find_map(|t| t.name.as_ref())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    let sport = gpx.tracks.iter().find_map(|t| t.type_.clone());
+    let waypoints = gpx
+        .waypoints
+        .iter()
+        .map(|w| Waypoint {
+            point: w.point(),
+            name: w
+                .name
+                .as_ref()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+        })
+        .collect();
+
+    let mut segments = vec![];
+    for t in gpx.tracks {
+        for s in t.segments {
+            let samples = s
+                .points
+                .iter()
+                .map(|p| Sample {
+                    point: p.point(),
+                    time: p.time.and_then(|t| {
+                        let t: time::OffsetDateTime = t.into();
+                        DateTime::from_timestamp(t.unix_timestamp(), t.nanosecond())
+                    }),
+                    elevation: p.elevation,
+                    sensors: sensors_it.next().unwrap_or_default(),
+                })
+                .collect();
+            segments.push(samples);
+        }
+    }
+
+    Ok(Activity {
+        name,
+        sport,
+        segments,
+        waypoints,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(Format::detect("ride.gpx", b"<?xml"), Format::Gpx);
+        assert_eq!(Format::detect("ride.FIT", b""), Format::Fit);
+        let header = [14, 0x10, 0, 0, 0, 0, 0, 0, b'.', b'F', b'I', b'T', 0, 0];
+        assert_eq!(Format::detect("upload.bin", &header), Format::Fit);
+        assert_eq!(Format::detect("ride.TCX", b""), Format::Tcx);
+        assert_eq!(
+            Format::detect(
+                "upload.xml",
+                b"<?xml version=\"1.0\"?>\n<TrainingCenterDatabase>"
+            ),
+            Format::Tcx
+        );
+    }
+
+    #[test]
+    fn gpx_samples_carry_sensors() {
+        let a = read_gpx(&std::fs::read("test/t1.gpx").unwrap()).unwrap();
+        assert_eq!(a.name.as_deref(), Some("Casalmaggiore Mountain bike"));
+        assert_eq!(a.sport.as_deref(), Some("mountain_biking"));
+        let first = a.segments[0][0];
+        assert_eq!(first.sensors.hr, Some(101.0));
+        assert!(first.time.is_some());
+    }
+
+    #[test]
+    fn gpx_waypoints() {
+        let gpx = br#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="46.5" lon="11.2"><name> Rifugio </name></wpt>
+  <wpt lat="46.6" lon="11.3"></wpt>
+  <trk><trkseg><trkpt lat="46.5" lon="11.2"></trkpt></trkseg></trk>
+</gpx>"#;
+        let a = read_gpx(gpx).unwrap();
+        assert_eq!(
+            a.waypoints,
+            [
+                Waypoint {
+                    point: Point::new(11.2, 46.5),
+                    name: Some("Rifugio".to_string()),
+                },
+                Waypoint {
+                    point: Point::new(11.3, 46.6),
+                    name: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Animated route replay: the template rendered frame by frame while the track is
+//! drawn progressively on `image_map` and a marker moves along `path_elevation`.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use geo_types::Point;
+use image::RgbaImage;
+use tiny_skia::Pixmap;
+
+use crate::InputPath;
+use crate::context::Context;
+use crate::map::render_base_map;
+use crate::render::{ExportSize, render_svg_to_rgba};
+use crate::template::{AssetProvider, Color, GroupBox, Template, ValueProvider};
+use crate::tiles::{MapFrame, draw_dot, draw_tracks, pixmap_href};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationOptions {
+    pub frames: usize,
+    pub fps: u16,
+    /// Seconds the last frame stays on screen.
+    pub hold: f64,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        AnimationOptions {
+            frames: 60,
+            fps: 20,
+            hold: 2.0,
+        }
+    }
+}
+
+impl AnimationOptions {
+    pub fn with_frames(mut self, frames: usize) -> Self {
+        self.frames = frames.max(1);
+        self
+    }
+
+    pub fn with_fps(mut self, fps: u16) -> Self {
+        self.fps = fps.max(1);
+        self
+    }
+
+    pub fn with_hold(mut self, hold: f64) -> Self {
+        self.hold = hold.max(0.0);
+        self
+    }
+
+    /// How long frame `i` is shown, in milliseconds.
+    fn delay_ms(&self, i: usize) -> u16 {
+        let frame = 1000 / self.fps.max(1);
+        if i + 1 == self.frames {
+            ((self.hold * 1000.0) as u16).max(frame)
+        } else {
+            frame
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+    /// Numbered PNG files in a directory, e.g. to make a video with ffmpeg.
+    Frames,
+}
+
+impl AnimationFormat {
+    /// `.gif` and `.png`/`.apng` files, anything else is a frames directory.
+    pub fn from_path(path: impl AsRef<Path>) -> AnimationFormat {
+        let ext = path
+            .as_ref()
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("gif") => AnimationFormat::Gif,
+            Some("png" | "apng") => AnimationFormat::Apng,
+            _ => AnimationFormat::Frames,
+        }
+    }
+}
+
+/// Map tiles rendered once, the track is drawn on a copy for each frame.
+struct BaseMap {
+    frame: MapFrame,
+    pixmap: Pixmap,
+}
+
+pub struct Animation<'a> {
+    template: &'a Template,
+    context: &'a Context,
+    options: AnimationOptions,
+    size: ExportSize,
+    base_map: RefCell<Option<BaseMap>>,
+}
+
+/// Values of one frame: running stats of the partial track, full elevation profile.
+struct Frame<'a> {
+    animation: &'a Animation<'a>,
+    partial: Context,
+    distance: f64,
+}
+
+impl ValueProvider for Frame<'_> {
+    fn get_string(&self, k: &str) -> Option<String> {
+        self.partial.get_string(k)
+    }
+
+    fn get_path(&self, k: &str, inp: &InputPath) -> Option<String> {
+        self.animation.context.get_path(k, inp)
+    }
+
+    fn get_path_marker(&self, k: &str, inp: &InputPath) -> Option<(f64, f64)> {
+        match k {
+            "path_elevation" => self.animation.context.elevation_marker(inp, self.distance),
+            _ => None,
+        }
+    }
+
+    fn get_group(&self, k: &str, area: &GroupBox) -> Option<String> {
+        self.animation.context.get_group(k, area)
+    }
+}
+
+impl AssetProvider for Frame<'_> {
+    fn get_image(
+        &self,
+        id: &str,
+        w_px: u32,
+        h_px: u32,
+        track_color: Option<Color>,
+    ) -> Option<String> {
+        match id {
+            "image_map" => self
+                .animation
+                .map_href(self.partial.coords()?, w_px, h_px, track_color)
+                .ok(),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Animation<'a> {
+    /// `context` must be loaded.
+    pub fn new(template: &'a Template, context: &'a Context) -> Self {
+        Animation {
+            template,
+            context,
+            options: AnimationOptions::default(),
+            size: ExportSize::default(),
+            base_map: RefCell::new(None),
+        }
+    }
+
+    pub fn with_options(mut self, options: AnimationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_size(mut self, size: ExportSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.options.frames.max(1)
+    }
+
+    /// Filled-in template of frame `i`, the last frame shows the whole track.
+    pub fn frame_svg(&self, i: usize) -> Result<String> {
+        let total = self
+            .context
+            .data()
+            .ok_or(anyhow!("missing track data"))?
+            .distance;
+        let distance = total * (i + 1) as f64 / self.frame_count() as f64;
+        let frame = Frame {
+            animation: self,
+            partial: self
+                .context
+                .truncated(distance)
+                .ok_or(anyhow!("missing track data"))?,
+            distance,
+        };
+        let scale = self.template.export_scale(self.size);
+        self.template.apply_with_scale(&frame, &frame, scale)
+    }
+
+    pub fn frame_rgba(&self, i: usize) -> Result<RgbaImage> {
+        render_svg_to_rgba(&self.frame_svg(i)?, self.size)
+    }
+
+    /// Looping animated GIF.
+    pub fn write_gif(&self, w: impl Write) -> Result<()> {
+        let mut encoder: Option<gif::Encoder<_>> = None;
+        let mut w = Some(w);
+        for i in 0..self.frame_count() {
+            let mut img = self.frame_rgba(i)?;
+            let (width, height) = (u16::try_from(img.width())?, u16::try_from(img.height())?);
+            if encoder.is_none() {
+                let w = w.take().expect("writer used once");
+                let mut e = gif::Encoder::new(w, width, height, &[])?;
+                e.set_repeat(gif::Repeat::Infinite)?;
+                encoder = Some(e);
+            }
+            let mut frame = gif::Frame::from_rgba_speed(width, height, &mut img, 10);
+            // GIF delays are in hundredths of a second.
+            frame.delay = self.options.delay_ms(i) / 10;
+            encoder
+                .as_mut()
+                .expect("encoder created")
+                .write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Looping animated PNG.
+    pub fn write_apng(&self, w: impl Write) -> Result<()> {
+        let n = self.frame_count();
+        let first = self.frame_rgba(0)?;
+        let mut encoder = png::Encoder::new(w, first.width(), first.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(n as u32, 0)?;
+        let mut writer = encoder.write_header()?;
+        for i in 0..n {
+            let img = match i {
+                0 => first.clone(),
+                _ => self.frame_rgba(i)?,
+            };
+            writer.set_frame_delay(self.options.delay_ms(i), 1000)?;
+            writer.write_image_data(&img)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// `frame_0001.png`, `frame_0002.png`, ... in `dir`, created if missing.
+    pub fn write_frames(&self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut paths = vec![];
+        for i in 0..self.frame_count() {
+            let path = dir.join(format!("frame_{:04}.png", i + 1));
+            self.frame_rgba(i)?.save(&path)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Write a GIF, an APNG or a frames directory depending on the extension of `path`.
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        match AnimationFormat::from_path(path) {
+            AnimationFormat::Gif => self.write_gif(fs::File::create(path)?),
+            AnimationFormat::Apng => self.write_apng(fs::File::create(path)?),
+            AnimationFormat::Frames => self.write_frames(path).map(|_| ()),
+        }
+    }
+
+    fn map_href(
+        &self,
+        coords: &[Point<f64>],
+        w_px: u32,
+        h_px: u32,
+        track_color: Option<Color>,
+    ) -> Result<String> {
+        let mut base = self.base_map.borrow_mut();
+        let stale = base
+            .as_ref()
+            .is_none_or(|b| (b.frame.width, b.frame.height) != (w_px, h_px));
+        if stale {
+            let full = self.context.coords().ok_or(anyhow!("missing track data"))?;
+            let frame =
+                MapFrame::for_tracks(&[full], w_px, h_px).ok_or(anyhow!("no coordinates"))?;
+            let rgba = render_base_map(&frame, self.context.map_options())?;
+            let size = tiny_skia::IntSize::from_wh(w_px, h_px).ok_or(anyhow!("invalid size"))?;
+            let pixmap = Pixmap::from_vec(rgba, size).ok_or(anyhow!("invalid map bitmap"))?;
+            *base = Some(BaseMap { frame, pixmap });
+        }
+        let base = base.as_ref().expect("base map rendered");
+
+        let mut pixmap = base.pixmap.clone();
+        draw_tracks(&mut pixmap, &base.frame, &[coords], track_color);
+        if let Some(p) = coords.last() {
+            draw_dot(&mut pixmap, &base.frame, *p, track_color);
+        }
+        pixmap_href(&pixmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20">
+  <text><tspan id="value_distance">0</tspan></text>
+  <path id="path_elevation" d="M 0,20 40,10" stroke="#22C55E"/>
+</svg>"##;
+
+    fn context() -> Context {
+        let mut ctx = Context::new("test/t1.gpx");
+        ctx.load().unwrap();
+        ctx
+    }
+
+    #[test]
+    fn frames_show_running_values_and_marker() {
+        let ctx = context();
+        let template = Template::new(TEMPLATE);
+        let animation = Animation::new(&template, &ctx)
+            .with_options(AnimationOptions::default().with_frames(4));
+
+        let first = animation.frame_svg(0).unwrap();
+        let last = animation.frame_svg(3).unwrap();
+        let distance = ctx.get_string("value_distance").unwrap();
+        assert!(!first.contains(&format!(">{distance}<")));
+        assert!(last.contains(&format!(">{distance}<")));
+        assert!(first.contains(r##"id="path_elevation_marker""##));
+        assert!(first.contains(r##"fill="#22C55E""##));
+    }
+
+    #[test]
+    fn encodes_gif_and_apng() {
+        let ctx = context();
+        let template = Template::new(TEMPLATE);
+        let options = AnimationOptions::default().with_frames(3).with_hold(1.0);
+        let animation = Animation::new(&template, &ctx)
+            .with_options(options)
+            .with_size(ExportSize::Scale(2.0));
+
+        let mut gif = vec![];
+        animation.write_gif(&mut gif).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+
+        let mut apng = vec![];
+        animation.write_apng(&mut apng).unwrap();
+        assert!(apng.windows(4).any(|w| w == b"acTL"));
+        assert_eq!(options.delay_ms(2), 1000);
+    }
+}
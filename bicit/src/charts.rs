@@ -0,0 +1,249 @@
+//! Graphs of speed, heart rate, power, cadence and elevation along the distance or the
+//! time, drawn in the box of a template path like `path_elevation`.
+
+use std::fmt::Write as _;
+
+use geo::{Distance, Geodesic};
+
+use crate::InputPath;
+use crate::activity::Sample;
+use crate::gradient::{ColorBy, point_values};
+
+// Number of points of a graph: samples are averaged in as many bins.
+const BINS: usize = 200;
+// Values further than this many median absolute deviations from the median are dropped.
+const MAX_DEVIATIONS: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Speed,
+    HeartRate,
+    Power,
+    Cadence,
+    Elevation,
+}
+
+/// What the x axis of a graph measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Distance,
+    Time,
+}
+
+impl Metric {
+    /// Metric and axis of a path id: `path_speed`, `path_hr`, `path_power`,
+    /// `path_cadence`, or one of these and `path_elevation` with a `_time` suffix.
+    pub fn from_path_id(k: &str) -> Option<(Metric, Axis)> {
+        let name = k.strip_prefix("path_")?;
+        let (name, axis) = match name.strip_suffix("_time") {
+            Some(name) => (name, Axis::Time),
+            None => (name, Axis::Distance),
+        };
+        let metric = match name {
+            "speed" => Metric::Speed,
+            "hr" => Metric::HeartRate,
+            "power" => Metric::Power,
+            "cadence" => Metric::Cadence,
+            // The distance one is the plain elevation profile.
+            "elevation" if axis == Axis::Time => Metric::Elevation,
+            _ => return None,
+        };
+        Some((metric, axis))
+    }
+
+    /// Whether the graph starts from zero rather than from the lowest value.
+    fn starts_at_zero(&self) -> bool {
+        matches!(self, Metric::Speed | Metric::Power | Metric::Cadence)
+    }
+}
+
+/// Position on `axis` (metres or seconds from the start) and value of `metric` for each
+/// sample that has one. No distance is counted between segments.
+pub fn series(segments: &[Vec<Sample>], metric: Metric, axis: Axis) -> Vec<(f64, f64)> {
+    let speeds = match metric {
+        Metric::Speed => point_values(segments, ColorBy::Speed),
+        _ => vec![],
+    };
+    let start = segments.iter().flatten().find_map(|s| s.time);
+    let mut distance = 0.0;
+    let mut series = vec![];
+    for (i, (sample, prev)) in segments
+        .iter()
+        .flat_map(|samples| {
+            samples
+                .iter()
+                .enumerate()
+                .map(|(i, s)| (s, i.checked_sub(1).map(|p| &samples[p])))
+        })
+        .enumerate()
+    {
+        if let Some(prev) = prev {
+            distance += Geodesic.distance(prev.point, sample.point);
+        }
+        let value = match metric {
+            Metric::Speed => speeds[i],
+            Metric::HeartRate => sample.sensors.hr,
+            Metric::Power => sample.sensors.power,
+            Metric::Cadence => sample.sensors.cadence,
+            Metric::Elevation => sample.elevation,
+        };
+        let x = match axis {
+            Axis::Distance => Some(distance),
+            Axis::Time => sample
+                .time
+                .zip(start)
+                .map(|(t, t0)| (t - t0).num_milliseconds() as f64 / 1000.0),
+        };
+        if let (Some(x), Some(value)) = (x, value) {
+            series.push((x, value));
+        }
+    }
+    series
+}
+
+/// `points` without outliers, averaged in [`BINS`] bins along x and smoothed.
+pub fn smooth(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let median = |values: &mut Vec<f64>| {
+        values.sort_by(f64::total_cmp);
+        values.get(values.len() / 2).copied()
+    };
+    let mut values: Vec<f64> = points.iter().map(|p| p.1).collect();
+    let Some(m) = median(&mut values) else {
+        return vec![];
+    };
+    let mut deviations: Vec<f64> = values.iter().map(|v| (v - m).abs()).collect();
+    let mad = median(&mut deviations).unwrap_or_default();
+    let kept = points
+        .iter()
+        .filter(|p| mad == 0.0 || (p.1 - m).abs() <= MAX_DEVIATIONS * mad);
+
+    let (x0, x1) = (points[0].0, points[points.len() - 1].0);
+    let width = ((x1 - x0) / BINS as f64).max(f64::EPSILON);
+    let mut bins = vec![(0.0, 0.0, 0); BINS];
+    for (x, v) in kept {
+        let bin = &mut bins[(((x - x0) / width) as usize).min(BINS - 1)];
+        *bin = (bin.0 + x, bin.1 + v, bin.2 + 1);
+    }
+    let averages: Vec<(f64, f64)> = bins
+        .into_iter()
+        .filter(|b| b.2 > 0)
+        .map(|(x, v, n)| (x / n as f64, v / n as f64))
+        .collect();
+
+    // Moving average over 3 bins.
+    (0..averages.len())
+        .map(|i| {
+            let window = &averages[i.saturating_sub(1)..(i + 2).min(averages.len())];
+            let v = window.iter().map(|p| p.1).sum::<f64>() / window.len() as f64;
+            (averages[i].0, v)
+        })
+        .collect()
+}
+
+/// Closed path of the graph of `points` in the box of `inp`, from the start of `inp`
+/// along its length and back to it, `None` without points.
+pub fn graph_path(points: &[(f64, f64)], metric: Metric, inp: &InputPath) -> Option<String> {
+    let (first, last) = (points.first()?, points.last()?);
+    let (lo, hi) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p.1), hi.max(p.1))
+    });
+    let lo = if metric.starts_at_zero() {
+        lo.min(0.0)
+    } else {
+        lo
+    };
+    let x_span = (last.0 - first.0).max(f64::EPSILON);
+    let v_span = (hi - lo).max(f64::EPSILON);
+
+    let (mut old_x, mut old_y) = (0.0, 0.0);
+    let mut d = format!("{} {}", inp.prefix, inp.ss);
+    for (x, v) in points {
+        let x = (x - first.0) / x_span * inp.length;
+        let y = (v - lo) / v_span * inp.height;
+        let _ = write!(d, " l {:.3} {:.3}", x - old_x, y - old_y);
+        (old_x, old_y) = (x, y);
+    }
+    let _ = write!(d, " l 0 {:.3}", -old_y);
+    Some(d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use geo_types::Point;
+
+    use crate::sensors::Sensors;
+
+    fn segment(start: usize, power: impl Fn(usize) -> Option<f64>) -> Vec<Sample> {
+        let t0 = DateTime::<Utc>::from_timestamp(1_792_108_800, 0).unwrap();
+        (start..start + 100)
+            .map(|i| Sample {
+                // 10m and 2s apart.
+                point: Point::new(10.0, 45.0 + i as f64 * 10.0 / 111_132.0),
+                time: Some(t0 + chrono::Duration::seconds(2 * i as i64)),
+                elevation: Some(100.0),
+                sensors: Sensors {
+                    power: power(i),
+                    ..Default::default()
+                },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn path_ids() {
+        assert_eq!(
+            Metric::from_path_id("path_hr_time"),
+            Some((Metric::HeartRate, Axis::Time))
+        );
+        assert_eq!(
+            Metric::from_path_id("path_cadence"),
+            Some((Metric::Cadence, Axis::Distance))
+        );
+        assert_eq!(Metric::from_path_id("path_elevation"), None);
+        assert_eq!(Metric::from_path_id("path_temperature"), None);
+    }
+
+    #[test]
+    fn series_along_distance_and_time() {
+        // A pause of 200 samples (400s) and 2km between the segments.
+        let segments = vec![
+            segment(0, |i| Some(200.0 + i as f64)),
+            segment(300, |i| (i % 2 == 0).then_some(100.0)),
+        ];
+        let by_distance = series(&segments, Metric::Power, Axis::Distance);
+        assert_eq!(by_distance.len(), 150);
+        let (x, v) = by_distance[100];
+        assert!((x - 990.0).abs() < 0.5 && v == 100.0, "{x} {v}");
+
+        let by_time = series(&segments, Metric::Power, Axis::Time);
+        assert_eq!(by_time[100], (600.0, 100.0));
+
+        let speed = series(&segments, Metric::Speed, Axis::Distance);
+        assert!(speed.iter().all(|(_, v)| (v - 18.0).abs() < 0.1));
+    }
+
+    #[test]
+    fn smoothed_graph_in_the_path_box() {
+        let mut points: Vec<(f64, f64)> = (0..1000)
+            .map(|i| (i as f64, if i < 500 { 100.0 } else { 200.0 }))
+            .collect();
+        // A spike that would flatten the rest of the graph.
+        points[700].1 = 2000.0;
+        let smoothed = smooth(&points);
+        assert_eq!(smoothed.len(), BINS);
+        assert!(smoothed.iter().all(|(_, v)| (100.0..=200.0).contains(v)));
+
+        let inp = InputPath::new("m 10,100 200,-50").unwrap();
+        let d = graph_path(&smoothed, Metric::Power, &inp).unwrap();
+        assert!(d.starts_with("m 10,100 l 0.000 -25.000 l "));
+        assert!(d.ends_with(" l 0 50.000"));
+        let width: f64 = d
+            .split(" l ")
+            .skip(1)
+            .filter_map(|l| l.split(' ').next()?.parse::<f64>().ok())
+            .sum();
+        assert!((width - 200.0).abs() < 0.01);
+    }
+}
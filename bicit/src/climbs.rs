@@ -0,0 +1,208 @@
+//! Climb detection and gradient statistics on a processed elevation profile.
+
+use crate::elevation::ElevationSample;
+
+// A climb ends once the road drops this many metres below its top.
+const MAX_DROP: f64 = 10.0;
+const MIN_LENGTH: f64 = 500.0;
+const MIN_GAIN: f64 = 30.0;
+const MIN_GRADE: f64 = 3.0;
+// Max grade is measured over this distance, so single noisy points don't count.
+const GRADE_WINDOW: f64 = 100.0;
+
+/// UCI-like category, from the climb score `length (m) * grade (%)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ClimbCategory {
+    Hc,
+    Cat1,
+    Cat2,
+    Cat3,
+    Cat4,
+}
+
+impl ClimbCategory {
+    pub fn from_score(score: f64) -> Option<ClimbCategory> {
+        match score {
+            s if s >= 80000.0 => Some(ClimbCategory::Hc),
+            s if s >= 64000.0 => Some(ClimbCategory::Cat1),
+            s if s >= 32000.0 => Some(ClimbCategory::Cat2),
+            s if s >= 16000.0 => Some(ClimbCategory::Cat3),
+            s if s >= 8000.0 => Some(ClimbCategory::Cat4),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClimbCategory::Hc => "HC",
+            ClimbCategory::Cat1 => "Cat 1",
+            ClimbCategory::Cat2 => "Cat 2",
+            ClimbCategory::Cat3 => "Cat 3",
+            ClimbCategory::Cat4 => "Cat 4",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Climb {
+    /// Distance from the start of the activity, in metres.
+    pub start: f64,
+    pub length: f64,
+    pub gain: f64,
+    /// Average grade in percent.
+    pub grade: f64,
+    /// Seconds spent on the climb, if the track has timestamps.
+    pub duration: Option<f64>,
+    pub category: Option<ClimbCategory>,
+}
+
+impl Climb {
+    pub fn score(&self) -> f64 {
+        self.length * self.grade
+    }
+
+    /// Category name, `Climb` when uncategorized.
+    pub fn name(&self) -> &'static str {
+        self.category.map(|c| c.name()).unwrap_or("Climb")
+    }
+
+    /// Vertical metres per hour.
+    pub fn vam(&self) -> Option<f64> {
+        self.duration
+            .filter(|t| *t > 0.0)
+            .map(|t| self.gain / t * 3600.0)
+    }
+}
+
+/// `points` goes from the lowest to the highest point of a candidate climb.
+fn make_climb(points: &[ElevationSample]) -> Option<Climb> {
+    let high = points.last()?;
+    // Flat ground before the foot of the climb is not part of it.
+    let foot = points
+        .windows(2)
+        .position(|w| w[1].e - w[0].e >= (w[1].d - w[0].d) * MIN_GRADE / 100.0)
+        .unwrap_or(0);
+    let low = &points[foot];
+    let length = high.d - low.d;
+    let gain = high.e - low.e;
+    if length < MIN_LENGTH || gain < MIN_GAIN {
+        return None;
+    }
+    let grade = gain / length * 100.0;
+    if grade < MIN_GRADE {
+        return None;
+    }
+    let category = ClimbCategory::from_score(length * grade);
+    Some(Climb {
+        start: low.d,
+        length,
+        gain,
+        grade,
+        duration: low.t.zip(high.t).map(|(t1, t2)| t2 - t1),
+        category,
+    })
+}
+
+/// Detect climbs in one segment, in track order.
+pub fn detect(points: &[ElevationSample]) -> Vec<Climb> {
+    let mut climbs = vec![];
+    let (mut low, mut high) = (0, 0);
+    for (i, p) in points.iter().enumerate() {
+        if points[high].e - p.e > MAX_DROP {
+            climbs.extend(make_climb(&points[low..=high]));
+            (low, high) = (i, i);
+        } else if p.e <= points[low].e {
+            (low, high) = (i, i);
+        } else if p.e > points[high].e {
+            high = i;
+        }
+    }
+    if high > low {
+        climbs.extend(make_climb(&points[low..=high]));
+    }
+    climbs
+}
+
+/// `(climbed metres, distance spent climbing, max grade in percent)` for one segment.
+pub fn grade_stats(points: &[ElevationSample]) -> (f64, f64, f64) {
+    let mut up = 0.0;
+    let mut up_distance = 0.0;
+    for w in points.windows(2) {
+        let de = w[1].e - w[0].e;
+        if de > 0.0 {
+            up += de;
+            up_distance += w[1].d - w[0].d;
+        }
+    }
+
+    let mut max: f64 = 0.0;
+    let mut j = 0;
+    for (i, p) in points.iter().enumerate() {
+        j = j.max(i);
+        while j < points.len() && points[j].d - p.d < GRADE_WINDOW {
+            j += 1;
+        }
+        let Some(q) = points.get(j) else {
+            break;
+        };
+        max = max.max((q.e - p.e) / (q.d - p.d) * 100.0);
+    }
+    (up, up_distance, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn profile(e: impl Fn(f64) -> f64, length: f64) -> Vec<ElevationSample> {
+        (0..=(length / 10.0) as usize)
+            .map(|i| {
+                let d = i as f64 * 10.0;
+                ElevationSample {
+                    d,
+                    t: Some(d / 3.0),
+                    e: e(d),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_two_climbs() {
+        // 2 km at 8%, 1 km down at 6%, 1 km at 4%.
+        let p = profile(
+            |d| match d {
+                d if d <= 2000.0 => d * 0.08,
+                d if d <= 3000.0 => 160.0 - (d - 2000.0) * 0.06,
+                d => 100.0 + (d - 3000.0) * 0.04,
+            },
+            4000.0,
+        );
+        let climbs = detect(&p);
+        assert_eq!(climbs.len(), 2);
+        assert_approx_eq!(climbs[0].gain, 160.0);
+        assert_approx_eq!(climbs[0].grade, 8.0);
+        assert_eq!(climbs[0].category, Some(ClimbCategory::Cat3));
+        assert_approx_eq!(climbs[1].start, 3000.0);
+        assert_eq!(climbs[1].category, None);
+        // 160m in 2000m at 3 m/s.
+        assert_approx_eq!(climbs[0].vam().unwrap(), 864.0);
+    }
+
+    #[test]
+    fn rollers_are_not_climbs() {
+        let p = profile(|d| (d / 200.0).sin() * 4.0, 5000.0);
+        assert!(detect(&p).is_empty());
+    }
+
+    #[test]
+    fn max_grade_uses_a_window() {
+        let mut p = profile(|d| d * 0.05, 1000.0);
+        // Single 10m spike.
+        p[50].e += 5.0;
+        let (up, _, max) = grade_stats(&p);
+        assert!(up > 50.0);
+        assert!(max < 11.0, "max {max}");
+    }
+}
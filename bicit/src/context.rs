@@ -1,65 +1,177 @@
 use std::cell::RefCell;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::Cursor;
+use std::fs;
 use std::path::Path;
 
-use crate::time::get_hhmmss;
+use crate::time::{get_hhmmss, local_time};
 use anyhow::Result;
+use chrono::{DateTime, Datelike, FixedOffset, Utc};
+use chrono_tz::Tz;
 
 #[cfg(not(target_arch = "wasm32"))]
 use anyhow::anyhow;
-use chrono::Duration;
-use geo::algorithm::line_measures::Length;
-use geo::{Distance, Geodesic, MultiLineString};
+use geo::{Distance, Geodesic};
 use geo_types::Point;
-use gpx::read;
 
 use crate::InputPath;
+use crate::activity::{Activity, ActivityReader, read_activity};
+use crate::charts::{Metric, graph_path, series, smooth};
+use crate::elevation::ElevationOptions;
+use crate::gradient::{ColorBy, TrackColoring, TrackGradient, point_values};
+use crate::locale::Locale;
+use crate::pause::PauseOptions;
+use crate::profile::{ProfileColors, ProfileOptions, profile_svg};
+use crate::stats::{ElevPoint, StatsAccumulator};
+use crate::units::Units;
+use crate::zones::{ZoneOptions, zones_svg};
 
+pub use crate::stats::ContextData;
+
+use crate::map::MapOptions;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::map::render_track_map_href;
-
-#[derive(Debug)]
-struct ElevPoint {
-    e: f64,
-    d: f64,
-}
-
-#[derive(Debug)]
-pub struct ContextData {
-    track_name: String,
-    distance: f64,
-    speed: f64,
-    speed_max: f64,
-    speed_moving: f64,
-    time: Duration,
-    time_moving: Duration,
-    uphill: f64,
-    downhill: f64,
-    elevation: Vec<ElevPoint>,
-    elevation_max: f64,
-    elevation_min: f64,
-    pub coords: Vec<Point<f64>>,
-}
+use crate::markers::{MapMarker, MapOverlay, MarkerKind, MarkerOptions, distance_markers};
+use crate::template::GroupBox;
 
 pub struct Context {
     filename: String,
+    readers: Vec<Box<dyn ActivityReader>>,
+    elevation_options: ElevationOptions,
+    pause_options: Option<PauseOptions>,
+    zone_options: ZoneOptions,
+    units: Units,
+    locale: Locale,
+    date_format: Option<String>,
+    time_format: String,
+    timezone: Option<Tz>,
+    map_options: MapOptions,
 
+    activity: Option<Activity>,
     data: Option<ContextData>,
     map_href: RefCell<Option<String>>,
     map_size: RefCell<Option<(u32, u32)>>,
     map_track_color: RefCell<Option<galileo::Color>>,
+    map_error: RefCell<Option<String>>,
 }
 
 impl Context {
     pub fn new(filename: impl Into<String>) -> Context {
         Context {
             filename: filename.into(),
+            readers: vec![],
+            elevation_options: ElevationOptions::default(),
+            pause_options: None,
+            zone_options: ZoneOptions::default(),
+            units: Units::default(),
+            locale: Locale::default(),
+            date_format: None,
+            time_format: "%H:%M".to_string(),
+            timezone: None,
+            map_options: MapOptions::default(),
+            activity: None,
             data: None,
             map_href: RefCell::new(None),
             map_size: RefCell::new(None),
             map_track_color: RefCell::new(None),
+            map_error: RefCell::new(None),
+        }
+    }
+
+    /// Register a custom reader. Custom readers are tried, in order, before the built-in
+    /// GPX/FIT/TCX detection.
+    pub fn with_reader(mut self, reader: impl ActivityReader + 'static) -> Context {
+        self.readers.push(Box::new(reader));
+        self
+    }
+
+    /// Configure how uphill, downhill and the elevation profile are computed. Applies to
+    /// the next load.
+    pub fn with_elevation_options(mut self, options: ElevationOptions) -> Context {
+        self.elevation_options = options;
+        self
+    }
+
+    /// Override the auto-pause thresholds, which otherwise depend on the activity sport.
+    /// Applies to the next load.
+    pub fn with_pause_options(mut self, options: PauseOptions) -> Context {
+        self.pause_options = Some(options);
+        self
+    }
+
+    /// Heart rate and power zone references for the time in zones. Applies to the next
+    /// load.
+    pub fn with_zone_options(mut self, options: ZoneOptions) -> Context {
+        self.zone_options = options;
+        self
+    }
+
+    /// Unit system used by [`Context::get_string`].
+    pub fn with_units(mut self, units: Units) -> Context {
+        self.units = units;
+        self
+    }
+
+    /// Change the unit system of an already loaded context.
+    pub fn set_units(&mut self, units: Units) {
+        self.units = units;
+    }
+
+    pub fn units(&self) -> Units {
+        self.units
+    }
+
+    /// Locale used for numbers, dates and `label_*` keys.
+    pub fn with_locale(mut self, locale: Locale) -> Context {
+        self.locale = locale;
+        self
+    }
+
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// strftime-like format for `value_date`, e.g. `%d/%m/%Y`. `%A` and `%B` are
+    /// localized. Defaults to the locale long date.
+    pub fn with_date_format(mut self, format: impl Into<String>) -> Context {
+        self.date_format = Some(format.into());
+        self
+    }
+
+    /// strftime-like format for `value_start_time` and `value_end_time`, `%H:%M` by default.
+    pub fn with_time_format(mut self, format: impl Into<String>) -> Context {
+        self.time_format = format.into();
+        self
+    }
+
+    /// Time zone for dates and times. Defaults to the zone at the start of the track.
+    pub fn with_timezone(mut self, timezone: Tz) -> Context {
+        self.timezone = Some(timezone);
+        self
+    }
+
+    /// Tile source of `image_map`.
+    pub fn with_map_options(mut self, options: MapOptions) -> Context {
+        self.map_options = options;
+        self
+    }
+
+    pub fn map_options(&self) -> &MapOptions {
+        &self.map_options
+    }
+
+    /// Why the last `image_map` render failed, `get_image` only returns `None`.
+    pub fn map_error(&self) -> Option<String> {
+        self.map_error.borrow().clone()
+    }
+
+    /// Change the tile source of a loaded context, e.g. for another template.
+    pub fn set_map_options(&mut self, options: MapOptions) {
+        if options != self.map_options {
+            self.cleanup_temp_files();
+            self.map_options = options;
         }
     }
 
@@ -73,24 +185,288 @@ impl Context {
         self.data.as_ref().map(|d| d.coords.as_slice())
     }
 
+    pub(crate) fn data(&self) -> Option<&ContextData> {
+        self.data.as_ref()
+    }
+
     pub fn get_string(&self, k: &str) -> Option<String> {
-        if let Some(d) = &self.data {
-            return match k {
-                "value_track_name" => Some(d.track_name.clone()),
-                "value_distance" => Some(format!("{:.0}km", d.distance / 1000.0)),
-                "value_speed" => Some(format!("{:.1}km/h", d.speed)),
-                "value_speed_max" => Some(format!("{:.1}km/h", d.speed_max)),
-                "value_speed_moving" => Some(format!("{:.1}km/h", d.speed_moving)),
-                "value_uphill" => Some(format!("{:.0}m", d.uphill)),
-                "value_downhill" => Some(format!("{:.0}m", d.downhill)),
-                "value_elevation_max" => Some(format!("{:.0}m", d.elevation_max)),
-                "value_elevation_min" => Some(format!("{:.0}m", d.elevation_min)),
-                "value_time" => Some(get_hhmmss(d.time)),
-                "value_moving_time" => Some(get_hhmmss(d.time_moving)),
-                _ => None,
-            };
+        // Labels are static text, available even before loading.
+        if let Some(label) = k.strip_prefix("label_") {
+            return self.locale.label(label).map(str::to_string);
+        }
+        if k == "value_map_attribution" {
+            return Some(
+                self.map_options
+                    .tiles
+                    .attribution()
+                    .unwrap_or_default()
+                    .to_string(),
+            );
+        }
+
+        let d = self.data.as_ref()?;
+        let (u, l) = (self.units, self.locale);
+        let v = match k {
+            "value_track_name" => return Some(d.track_name.clone()),
+            "value_date" => {
+                return Some(
+                    self.format_time(d, d.start_time, |t| match &self.date_format {
+                        Some(f) => self.locale.strftime(t, f),
+                        None => Some(self.locale.date(t)),
+                    }),
+                );
+            }
+            "value_weekday" => {
+                return Some(self.format_time(d, d.start_time, |t| {
+                    Some(self.locale.weekday_name(t.weekday()).to_string())
+                }));
+            }
+            "value_start_time" => {
+                return Some(self.format_time(d, d.start_time, |t| {
+                    self.locale.strftime(t, &self.time_format)
+                }));
+            }
+            "value_end_time" => {
+                return Some(self.format_time(d, d.end_time, |t| {
+                    self.locale.strftime(t, &self.time_format)
+                }));
+            }
+            "value_elapsed_time" => match (d.start_time, d.end_time) {
+                (Some(t1), Some(t2)) => get_hhmmss(t2 - t1),
+                _ => "-".to_string(),
+            },
+            "value_distance" => u.distance(d.distance),
+            "value_speed" => u.speed(d.speed, l),
+            "value_speed_max" => u.speed(d.speed_max, l),
+            "value_speed_moving" => u.speed(d.speed_moving, l),
+            "value_uphill" => u.elevation(d.uphill),
+            "value_downhill" => u.elevation(d.downhill),
+            "value_elevation_max" => u.elevation(d.elevation_max),
+            "value_elevation_min" => u.elevation(d.elevation_min),
+            "value_time" => get_hhmmss(d.time),
+            "value_moving_time" => get_hhmmss(d.time_moving),
+            "value_stopped_time" => get_hhmmss(d.time_stopped),
+            "value_pause_count" => d.pause_count.to_string(),
+            "value_hr_avg" => format_sensor(d.sensors.hr_avg, "bpm"),
+            "value_hr_max" => format_sensor(d.sensors.hr_max, "bpm"),
+            "value_cadence_avg" => format_sensor(d.sensors.cadence_avg, "rpm"),
+            "value_cadence_max" => format_sensor(d.sensors.cadence_max, "rpm"),
+            "value_power_avg" => format_sensor(d.sensors.power_avg, "W"),
+            "value_power_max" => format_sensor(d.sensors.power_max, "W"),
+            "value_power_np" => format_sensor(d.sensors.power_np, "W"),
+            "value_temp_avg" => u.temperature(d.sensors.temp_avg),
+            "value_temp_min" => u.temperature(d.sensors.temp_min),
+            "value_temp_max" => u.temperature(d.sensors.temp_max),
+            "value_vam" => d.vam.map(|v| u.vam(v)).unwrap_or_else(|| "-".to_string()),
+            "value_grade_avg" => format!("{}%", l.decimal(d.grade_avg, 1)),
+            "value_grade_max" => format!("{}%", l.decimal(d.grade_max, 1)),
+            "value_climb_count" => d.climbs.len().to_string(),
+            "value_lap_count" => d.laps.len().to_string(),
+            _ => {
+                if let Some(rest) = k.strip_prefix("value_climb_") {
+                    self.get_climb_string(d, rest)?
+                } else if let Some(rest) = k.strip_prefix("value_lap_") {
+                    self.get_lap_string(d, rest)?
+                } else if let Some(rest) = k.strip_prefix("value_hr_z") {
+                    get_zone_string(&d.hr_zones, rest)?
+                } else if let Some(rest) = k.strip_prefix("value_power_z") {
+                    get_zone_string(&d.power_zones, rest)?
+                } else {
+                    return None;
+                }
+            }
+        };
+        Some(v)
+    }
+
+    /// Indexed climb keys, `rest` is e.g. `1_gain` for `value_climb_1_gain`.
+    fn get_climb_string(&self, d: &ContextData, rest: &str) -> Option<String> {
+        let (index, field) = rest.split_once('_')?;
+        let index: usize = index.parse().ok()?;
+        let Some(c) = index.checked_sub(1).and_then(|i| d.climbs.get(i)) else {
+            // Templates may show more climbs than the track has.
+            return Some("-".to_string());
+        };
+        let u = self.units;
+        Some(match field {
+            "name" => c.name().to_string(),
+            "gain" => u.elevation(c.gain),
+            "length" => u.length(c.length, self.locale),
+            "grade" => format!("{}%", self.locale.decimal(c.grade, 1)),
+            "start" => u.length(c.start, self.locale),
+            "vam" => c.vam().map(|v| u.vam(v)).unwrap_or_else(|| "-".to_string()),
+            "time" => c
+                .duration
+                .map(|t| get_hhmmss(chrono::Duration::seconds(t.round() as i64)))
+                .unwrap_or_else(|| "-".to_string()),
+            _ => return None,
+        })
+    }
+
+    /// Indexed lap keys, `rest` is e.g. `2_time` for `value_lap_2_time`.
+    fn get_lap_string(&self, d: &ContextData, rest: &str) -> Option<String> {
+        let (index, field) = rest.split_once('_')?;
+        let index: usize = index.parse().ok()?;
+        // Templates may show more laps than the activity has.
+        let lap = index
+            .checked_sub(1)
+            .and_then(|i| d.laps.get(i))
+            .copied()
+            .unwrap_or_default();
+        let u = self.units;
+        Some(match field {
+            "time" => lap
+                .total_time
+                .map(|t| get_hhmmss(chrono::Duration::seconds(t.round() as i64)))
+                .unwrap_or_else(|| "-".to_string()),
+            "distance" => lap
+                .distance
+                .map(|m| u.length(m, self.locale))
+                .unwrap_or_else(|| "-".to_string()),
+            "speed" => lap
+                .distance
+                .zip(lap.total_time)
+                .filter(|(_, t)| *t > 0.0)
+                .map(|(m, t)| u.speed(m / t * 3.6, self.locale))
+                .unwrap_or_else(|| "-".to_string()),
+            "hr_avg" => format_sensor(lap.hr_avg, "bpm"),
+            "hr_max" => format_sensor(lap.hr_max, "bpm"),
+            "cadence_avg" => format_sensor(lap.cadence_avg, "rpm"),
+            "power_avg" => format_sensor(lap.power_avg, "W"),
+            "calories" => format_sensor(lap.calories, "kcal"),
+            _ => return None,
+        })
+    }
+
+    /// Format `t` in the track time zone, `-` when missing or on a bad format.
+    fn format_time<F>(&self, d: &ContextData, t: Option<DateTime<Utc>>, f: F) -> String
+    where
+        F: FnOnce(&DateTime<FixedOffset>) -> Option<String>,
+    {
+        t.map(|t| local_time(t, self.timezone.or(d.timezone)))
+            .and_then(|t| f(&t))
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    /// Markers to draw on the map, labelled in the context units. Empty before loading.
+    pub fn map_markers(&self, options: &MarkerOptions) -> Vec<MapMarker> {
+        let Some(d) = self.data.as_ref() else {
+            return vec![];
+        };
+        let marker = |kind, point, label| MapMarker { kind, point, label };
+        let mut markers = vec![];
+        if let Some(step) = options.distance {
+            let interval = step * self.units.distance_unit();
+            markers.extend(distance_markers(&d.coords, interval, step));
+        }
+        if options.waypoints
+            && let Some(activity) = &self.activity
+        {
+            markers.extend(
+                activity
+                    .waypoints
+                    .iter()
+                    .map(|w| marker(MarkerKind::Waypoint, w.point, w.name.clone())),
+            );
+        }
+        if options.highest
+            && let Some((e, p)) = d.highest_point
+        {
+            // The recorded elevation of that point, not the smoothed maximum.
+            let label = self.units.elevation(e);
+            markers.push(marker(MarkerKind::Highest, p, Some(label)));
+        }
+        if options.max_speed
+            && let Some(p) = d.speed_max_point
+        {
+            let label = self.units.speed(d.speed_max, self.locale);
+            markers.push(marker(MarkerKind::MaxSpeed, p, Some(label)));
+        }
+        // Start and finish on top of the rest.
+        if options.finish
+            && let Some(p) = d.coords.last()
+        {
+            markers.push(marker(MarkerKind::Finish, *p, None));
+        }
+        if options.start
+            && let Some(p) = d.coords.first()
+        {
+            markers.push(marker(MarkerKind::Start, *p, None));
+        }
+        markers
+    }
+
+    /// Per-point track colors for `coloring`, `None` before loading or when the track
+    /// has no values for the metric.
+    pub fn track_gradient(&self, coloring: &TrackColoring) -> Option<TrackGradient> {
+        let activity = self.activity.as_ref()?;
+        TrackGradient::new(coloring, &point_values(&activity.segments, coloring.by))
+    }
+
+    /// Markers and track colors of a map drawn with `options`.
+    pub fn map_overlay(&self, options: &MapOptions) -> MapOverlay {
+        MapOverlay {
+            markers: self.map_markers(&options.markers),
+            gradient: options
+                .coloring
+                .as_ref()
+                .and_then(|coloring| self.track_gradient(coloring)),
+        }
+    }
+
+    /// Generated content of `group_*` elements: `group_elevation_profile` is the
+    /// elevation profile, see [`profile_svg`], and `group_map_legend` the legend of the
+    /// track colors, `group_hr_zones` and `group_power_zones` bars of the time in zones.
+    pub fn get_group(&self, k: &str, area: &GroupBox) -> Option<String> {
+        match k {
+            "group_elevation_profile" => {
+                let d = self.data.as_ref()?;
+                let points: Vec<(f64, f64)> = d.elevation.iter().map(|p| (p.d, p.e)).collect();
+                let options = match &area.options {
+                    Some(options) => options.parse().ok()?,
+                    None => ProfileOptions::default(),
+                };
+                let colors = ProfileColors {
+                    line: area.stroke.clone(),
+                    fill: area.fill.clone(),
+                };
+                Some(profile_svg(
+                    &points,
+                    area.width,
+                    area.height,
+                    &options,
+                    &colors,
+                    self.units,
+                    self.locale,
+                ))
+            }
+            "group_map_legend" => {
+                let gradient = self.track_gradient(self.map_options.coloring.as_ref()?)?;
+                let u = self.units;
+                let (title, format): (_, &dyn Fn(f64) -> String) = match gradient.by {
+                    ColorBy::Speed => ("velocity", &|v| u.speed(v, self.locale)),
+                    ColorBy::Grade => ("grade", &|v| format!("{v:.0}%")),
+                    ColorBy::Elevation => ("elevation", &|v| u.elevation(v)),
+                    ColorBy::HeartRate => ("hr", &|v| format!("{v:.0}bpm")),
+                };
+                Some(gradient.legend_svg(
+                    area.width,
+                    area.height,
+                    self.locale.label(title).unwrap_or_default(),
+                    &format(gradient.min),
+                    &format(gradient.max),
+                ))
+            }
+            "group_hr_zones" => {
+                let d = self.data.as_ref()?;
+                Some(zones_svg(&d.hr_zones, area.width, area.height))
+            }
+            "group_power_zones" => {
+                let d = self.data.as_ref()?;
+                Some(zones_svg(&d.power_zones, area.width, area.height))
+            }
+            _ => None,
         }
-        None
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -104,6 +480,8 @@ impl Context {
             &d.coords,
             galileo_types::cartesian::Size::<u32>::new(w_px, h_px),
             track_color,
+            &self.map_options,
+            &self.map_overlay(&self.map_options),
         )?;
         *self.map_href.borrow_mut() = Some(href);
         *self.map_size.borrow_mut() = Some((w_px, h_px));
@@ -129,7 +507,9 @@ impl Context {
                 || *self.map_track_color.borrow() != track_color;
 
             if needs_render {
-                self.build_map(w_px, h_px, track_color).ok()?;
+                let result = self.build_map(w_px, h_px, track_color);
+                *self.map_error.borrow_mut() = result.as_ref().err().map(|e| format!("{e:#}"));
+                result.ok()?;
             }
 
             return match k {
@@ -157,16 +537,10 @@ impl Context {
             "path_elevation" => self.data.as_ref().map(|d| {
                 let mut old_d: f64 = 0.0;
                 let mut old_e: f64 = 0.0;
-                let el_width = (d.elevation_max - d.elevation_min).max(100.0);
-                let el_offset = d.elevation_min;
-                let el_factor = inp.height / el_width;
                 let s2 = d
                     .elevation
                     .iter()
-                    .map(|val| ElevPoint {
-                        d: val.d * (inp.length / d.distance),
-                        e: (val.e - el_offset) * (el_factor),
-                    })
+                    .map(|val| elevation_point(d, inp, val.d, val.e))
                     .map(|val| {
                         let f = format!("l {} {}", val.d - old_d, val.e - old_e);
                         old_d = val.d;
@@ -177,10 +551,97 @@ impl Context {
                     .join(" ");
                 format!("{} {} {} l 0 {}", inp.prefix, inp.ss, s2, -old_e)
             }),
-            _ => None,
+            _ => {
+                let (metric, axis) = Metric::from_path_id(k)?;
+                let activity = self.activity.as_ref()?;
+                graph_path(
+                    &smooth(&series(&activity.segments, metric, axis)),
+                    metric,
+                    inp,
+                )
+            }
         }
     }
 
+    /// Point of `path_elevation` at `distance` metres from the start, relative to the
+    /// start of the path.
+    pub fn elevation_marker(&self, inp: &InputPath, distance: f64) -> Option<(f64, f64)> {
+        let d = self.data.as_ref()?;
+        let i = d.elevation.iter().position(|p| p.d >= distance);
+        let e = match i {
+            Some(0) => d.elevation[0].e,
+            Some(i) => {
+                let (p1, p2) = (&d.elevation[i - 1], &d.elevation[i]);
+                let t = (distance - p1.d) / (p2.d - p1.d).max(f64::EPSILON);
+                p1.e + (p2.e - p1.e) * t
+            }
+            None => d.elevation.last()?.e,
+        };
+        let p = elevation_point(d, inp, distance.min(d.distance), e);
+        Some((p.d, p.e))
+    }
+
+    /// Stats of the activity up to `distance` metres from the start, with the same
+    /// settings, e.g. for the frames of an animation. `None` before loading.
+    pub fn truncated(&self, distance: f64) -> Option<Context> {
+        let activity = self.activity.as_ref()?;
+        let mut partial = Activity {
+            name: activity.name.clone(),
+            sport: activity.sport.clone(),
+            segments: vec![],
+            sensor_records: vec![],
+            laps: vec![],
+            waypoints: activity.waypoints.clone(),
+        };
+        let mut total = 0.0;
+        for segment in &activity.segments {
+            let mut samples = vec![];
+            let mut done = false;
+            for (i, sample) in segment.iter().enumerate() {
+                if i > 0 {
+                    total += Geodesic.distance(segment[i - 1].point, sample.point);
+                }
+                // Keep at least the first sample.
+                if total > distance && !(samples.is_empty() && partial.segments.is_empty()) {
+                    done = true;
+                    break;
+                }
+                samples.push(*sample);
+            }
+            if !samples.is_empty() {
+                partial.segments.push(samples);
+            }
+            if done {
+                break;
+            }
+        }
+        let end = partial.segments.last().and_then(|s| s.last()?.time);
+        let before_end = |t: Option<DateTime<Utc>>| t.zip(end).is_some_and(|(t, end)| t <= end);
+        partial.sensor_records = (activity.sensor_records.iter())
+            .filter(|r| before_end(Some(r.time)))
+            .copied()
+            .collect();
+        partial.laps = (activity.laps.iter())
+            .filter(|l| before_end(l.start_time))
+            .copied()
+            .collect();
+
+        let mut ctx = Context::new(self.filename.clone())
+            .with_elevation_options(self.elevation_options)
+            .with_units(self.units)
+            .with_locale(self.locale)
+            .with_time_format(self.time_format.clone());
+        ctx.pause_options = self.pause_options;
+        ctx.zone_options = self.zone_options;
+        ctx.map_options = self.map_options.clone();
+        ctx.date_format = self.date_format.clone();
+        ctx.timezone = self
+            .timezone
+            .or(self.data.as_ref().and_then(|d| d.timezone));
+        ctx.data = Some(ctx.stats(&partial));
+        Some(ctx)
+    }
+
     fn truncate_ellipsis(s: &str, max_chars: usize) -> String {
         if max_chars == 0 {
             return String::new();
@@ -196,11 +657,10 @@ impl Context {
         format!("{}…", prefix)
     }
 
-    fn compute_track_name(gpx: &gpx::Gpx, filename: &str) -> String {
-        let from_track = gpx
-            .tracks
-            .iter()
-            .find_map(|t| t.name.as_ref())
+    fn compute_track_name(activity: &Activity, filename: &str) -> String {
+        let from_track = activity
+            .name
+            .as_deref()
             .map(|s| s.trim())
             .filter(|s| !s.is_empty())
             .map(|s| Context::truncate_ellipsis(s, 32));
@@ -221,123 +681,86 @@ impl Context {
     }
 
     pub fn load(&mut self) -> Result<()> {
-        let file = File::open(&self.filename)?;
-        let reader = BufReader::new(file);
-        self.load_from_bufread(reader)
+        let bytes = fs::read(&self.filename)?;
+        self.load_from_bytes(&bytes)
     }
 
+    /// Load GPX, FIT or TCX data (or any format handled by a registered reader),
+    /// detecting the format from the content and the filename.
     pub fn load_from_bytes(&mut self, bytes: &[u8]) -> Result<()> {
-        let reader = BufReader::new(Cursor::new(bytes));
-        self.load_from_bufread(reader)
-    }
-
-    fn load_from_bufread<R: std::io::BufRead>(&mut self, reader: R) -> Result<()> {
-        let mut tot_distance: f64 = 0.0;
-        let mut cur_distance: f64 = 0.0;
-        let mut tot_time: Duration = Duration::seconds(0);
-        let mut tot_moving_time: Duration = Duration::seconds(0);
-        let mut uphill: f64 = 0.0;
-        let mut downhill: f64 = 0.0;
-        let mut speed_max: f64 = 0.0;
-        let mut elevation_max: f64 = 0.0;
-        let mut elevation_min: f64 = 99999.0;
-        let mut elev: Vec<ElevPoint> = vec![];
-        let mut coords: Vec<Point<f64>> = vec![];
-
-        let gpx = read(reader)?;
-        let track_name = Context::compute_track_name(&gpx, &self.filename);
-        for t in gpx.tracks {
-            let mls: MultiLineString<f64> = t.multilinestring();
-            tot_distance += Geodesic.length(&mls);
-            for s in t.segments {
-                // step by is required to filter a bit elevation variation
-                s.points.iter().for_each(|f| {
-                    coords.push(f.point());
-                });
-                let i1 = s.points.iter().step_by(10);
-                let i2 = s.points.iter().step_by(10).skip(1);
-                for (w1, w2) in i1.zip(i2) {
-                    let p1 = w1.point();
-                    let p2 = w2.point();
-                    let d = Geodesic.distance(p1, p2);
-                    cur_distance += d;
-
-                    if let (Some(t1), Some(t2)) = (w1.time, w2.time) {
-                        let t1: time::OffsetDateTime = t1.into();
-                        let t2: time::OffsetDateTime = t2.into();
-                        let ptime_time = t2 - t1;
-                        let ptime = Duration::seconds(ptime_time.whole_seconds());
-
-                        if ptime.num_seconds() > 0 {
-                            tot_time += ptime;
-                            let speed = (d.round() / ptime.num_seconds() as f64) * 3.6;
-                            if speed > 0.5 {
-                                tot_moving_time += ptime;
-                            }
-                            if speed > speed_max {
-                                speed_max = speed;
-                            }
-                        }
-                    }
+        let activity = match self
+            .readers
+            .iter()
+            .find(|r| r.accepts(&self.filename, bytes))
+        {
+            Some(reader) => reader.read(bytes)?,
+            None => read_activity(&self.filename, bytes)?,
+        };
+        self.load_activity(activity);
+        Ok(())
+    }
 
-                    if let Some(e1) = w1.elevation
-                        && let Some(e2) = w2.elevation
-                    {
-                        let d = e2 - e1;
-                        if d > 0.0 {
-                            uphill += d;
-                        } else {
-                            downhill -= d;
-                        }
-                        if e1 > elevation_max {
-                            elevation_max = e1;
-                        }
-                        if e1 < elevation_min {
-                            elevation_min = e1;
-                        }
-                        elev.push(ElevPoint {
-                            d: cur_distance,
-                            e: e1,
-                        });
-                    }
-                }
-            }
-        }
+    /// Computed stats, moved out of the context (e.g. into a `Summary`).
+    pub(crate) fn into_data(self) -> Option<ContextData> {
+        self.data
+    }
 
-        let speed = if tot_time.num_seconds() > 0 {
-            (tot_distance.round() / tot_time.num_seconds() as f64) * 3.6
-        } else {
-            0.0
-        };
-        let speed_moving = if tot_moving_time.num_seconds() > 0 {
-            (tot_distance.round() / tot_moving_time.num_seconds() as f64) * 3.6
-        } else {
-            0.0
-        };
+    pub fn load_activity(&mut self, activity: Activity) {
+        self.data = Some(self.stats(&activity));
+        self.activity = Some(activity);
+    }
 
-        self.data = Some(ContextData {
-            track_name,
-            distance: tot_distance,
-            speed,
-            speed_max,
-            speed_moving,
-            time: tot_time,
-            time_moving: tot_moving_time,
-            uphill,
-            downhill,
-            elevation: elev,
-            elevation_max,
-            elevation_min,
-            coords,
-        });
+    fn stats(&self, activity: &Activity) -> ContextData {
+        let pause_options = self
+            .pause_options
+            .unwrap_or_else(|| PauseOptions::for_sport(activity.sport.as_deref()));
+        let mut acc = StatsAccumulator::new(self.elevation_options, pause_options)
+            .with_zone_options(&self.zone_options);
+        acc.push_activity(activity);
+        let track_name = Context::compute_track_name(activity, &self.filename);
+        acc.finish(track_name)
+    }
+}
 
-        Ok(())
+/// Profile point at `distance` and `elevation`, scaled to the size of `inp`.
+fn elevation_point(d: &ContextData, inp: &InputPath, distance: f64, elevation: f64) -> ElevPoint {
+    let el_width = (d.elevation_max - d.elevation_min).max(100.0);
+    ElevPoint {
+        d: distance * (inp.length / d.distance),
+        e: (elevation - d.elevation_min) * (inp.height / el_width),
+    }
+}
+
+/// Zone keys, `rest` is e.g. `2_time` for `value_hr_z2_time`: the time or the percentage
+/// of the time in the zone.
+fn get_zone_string(zones: &[f64], rest: &str) -> Option<String> {
+    let (index, field) = rest.split_once('_')?;
+    let index: usize = index.parse().ok()?;
+    if !matches!(field, "time" | "percent") {
+        return None;
+    }
+    // No zones without a reference or data, or fewer than the template shows.
+    let Some(seconds) = index.checked_sub(1).and_then(|i| zones.get(i)) else {
+        return Some("-".to_string());
+    };
+    Some(match field {
+        "time" => get_hhmmss(chrono::Duration::seconds(seconds.round() as i64)),
+        _ => format!("{:.0}%", seconds / zones.iter().sum::<f64>() * 100.0),
+    })
+}
+
+fn format_sensor(v: Option<f64>, unit: &str) -> String {
+    match v {
+        Some(v) => format!("{:.0}{}", v, unit),
+        None => "-".to_string(),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gradient::ColorRamp;
+    use crate::tiles::TileSource;
 
     #[test]
     fn track_name_from_first_track() {
@@ -349,23 +772,402 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sensor_values_from_track_point_extension() {
+        let mut ctx = Context::new("test/t1.gpx");
+        ctx.load().unwrap();
+        let hr_avg: f64 = ctx
+            .get_string("value_hr_avg")
+            .unwrap()
+            .trim_end_matches("bpm")
+            .parse()
+            .unwrap();
+        assert!(hr_avg > 80.0 && hr_avg < 180.0);
+        assert_eq!(ctx.get_string("value_temp_max").unwrap(), "21°C");
+        // t1.gpx has no power meter.
+        assert_eq!(ctx.get_string("value_power_np").unwrap(), "-");
+    }
+
+    #[test]
+    fn values_follow_units() {
+        let mut ctx = Context::new("test/t1.gpx").with_units(Units::Imperial);
+        ctx.load().unwrap();
+        assert!(ctx.get_string("value_distance").unwrap().ends_with("mi"));
+        assert!(ctx.get_string("value_speed").unwrap().ends_with("mph"));
+        assert!(ctx.get_string("value_uphill").unwrap().ends_with("ft"));
+        assert_eq!(ctx.get_string("value_temp_max").unwrap(), "70°F");
+
+        ctx.set_units(Units::Metric);
+        assert_eq!(ctx.get_string("value_temp_max").unwrap(), "21°C");
+    }
+
+    #[test]
+    fn dates_in_track_time_zone() {
+        let mut ctx = Context::new("test/t1.gpx").with_date_format("%d/%m/%Y");
+        ctx.load().unwrap();
+        assert_eq!(ctx.get_string("value_date").unwrap(), "23/03/2021");
+        // First point at 12:05:53 UTC, Italy is UTC+1 in March.
+        assert_eq!(ctx.get_string("value_start_time").unwrap(), "13:05");
+        assert_eq!(
+            ctx.get_string("value_elapsed_time").unwrap(),
+            ctx.get_string("value_time").unwrap()
+        );
+    }
+
+    #[test]
+    fn lap_values() {
+        let sample = |i: i64, lat: f64| crate::activity::Sample {
+            point: Point::new(10.0, lat),
+            time: DateTime::from_timestamp(1_600_000_000 + i, 0),
+            elevation: None,
+            sensors: Default::default(),
+        };
+        let mut ctx = Context::new("laps.tcx");
+        ctx.load_activity(Activity {
+            segments: vec![vec![sample(0, 45.0), sample(600, 45.03)]],
+            laps: vec![
+                crate::activity::Lap {
+                    total_time: Some(300.0),
+                    distance: Some(2500.0),
+                    hr_avg: Some(141.4),
+                    ..Default::default()
+                },
+                crate::activity::Lap::default(),
+            ],
+            ..Default::default()
+        });
+        assert_eq!(ctx.get_string("value_lap_count").unwrap(), "2");
+        assert_eq!(ctx.get_string("value_lap_1_time").unwrap(), "00:05:00");
+        assert_eq!(ctx.get_string("value_lap_1_distance").unwrap(), "2.5km");
+        assert_eq!(ctx.get_string("value_lap_1_speed").unwrap(), "30.0km/h");
+        assert_eq!(ctx.get_string("value_lap_1_hr_avg").unwrap(), "141bpm");
+        assert_eq!(ctx.get_string("value_lap_1_power_avg").unwrap(), "-");
+        assert_eq!(ctx.get_string("value_lap_2_time").unwrap(), "-");
+        assert_eq!(ctx.get_string("value_lap_3_distance").unwrap(), "-");
+        assert_eq!(ctx.get_string("value_lap_1_foo"), None);
+    }
+
+    #[test]
+    fn climb_values() {
+        // 1 km flat, then 2.5 km at 8%.
+        let records: Vec<_> = (0..=350)
+            .map(|i| {
+                let d = i as f64 * 10.0;
+                let e = (d - 1000.0).max(0.0) * 0.08;
+                (i * 3, 45.0 + d / 111_195.0, 10.0, 100.0 + e, 130, 180)
+            })
+            .collect();
+        let mut ctx = Context::new("climb.fit");
+        ctx.load_from_bytes(&crate::test_util::build_fit(&records))
+            .unwrap();
+        assert_eq!(ctx.get_string("value_climb_count").unwrap(), "1");
+        assert_eq!(ctx.get_string("value_climb_1_name").unwrap(), "Cat 3");
+        assert_eq!(ctx.get_string("value_climb_1_gain").unwrap(), "200m");
+        assert_eq!(ctx.get_string("value_climb_1_length").unwrap(), "2.5km");
+        assert_eq!(ctx.get_string("value_climb_2_gain").unwrap(), "-");
+        assert_eq!(ctx.get_string("value_climb_1_foo"), None);
+        // 200m in 750s, minus the foot of the climb rounded by smoothing.
+        let vam: f64 = ctx
+            .get_string("value_vam")
+            .unwrap()
+            .trim_end_matches("m/h")
+            .parse()
+            .unwrap();
+        assert!((vam - 960.0).abs() < 10.0, "vam {vam}");
+        assert_eq!(ctx.get_string("value_grade_max").unwrap(), "8.0%");
+    }
+
+    #[test]
+    fn values_follow_locale() {
+        let mut ctx = Context::new("test/t1.gpx").with_locale(Locale::It);
+        assert_eq!(ctx.get_string("label_uphill").unwrap(), "Ascesa");
+        assert_eq!(ctx.get_string("label_unknown"), None);
+        ctx.load().unwrap();
+        assert!(ctx.get_string("value_speed").unwrap().contains(','));
+        assert!(ctx.get_string("value_grade_max").unwrap().contains(','));
+        assert!(
+            ctx.get_string("value_date")
+                .unwrap()
+                .ends_with("marzo 2021")
+        );
+
+        assert_eq!(ctx.get_string("value_weekday").unwrap(), "martedì");
+
+        ctx.set_locale(Locale::De);
+        assert_eq!(ctx.get_string("label_distance").unwrap(), "Distanz");
+        // Only decimal points become commas.
+        assert_eq!(ctx.get_string("value_date").unwrap(), "23. März 2021");
+    }
+
     #[test]
     fn track_name_falls_back_to_filename_stem() {
-        let gpx = gpx::Gpx::default();
-        assert_eq!(Context::compute_track_name(&gpx, "foo_bar.gpx"), "foo_bar");
+        let activity = Activity::default();
+        assert_eq!(
+            Context::compute_track_name(&activity, "foo_bar.gpx"),
+            "foo_bar"
+        );
     }
 
     #[test]
     fn track_name_is_truncated_to_32() {
-        let mut gpx = gpx::Gpx::default();
-        let trk = gpx::Track {
+        let activity = Activity {
             name: Some("abcdefghijklmnopqrstuvwxyz0123456789".to_string()),
             ..Default::default()
         };
-        gpx.tracks.push(trk);
 
-        let name = Context::compute_track_name(&gpx, "x.gpx");
+        let name = Context::compute_track_name(&activity, "x.gpx");
         assert_eq!(name.chars().count(), 32);
         assert!(name.ends_with('…'));
     }
+
+    struct CsvReader;
+
+    impl ActivityReader for CsvReader {
+        fn accepts(&self, filename: &str, _bytes: &[u8]) -> bool {
+            filename.ends_with(".csv")
+        }
+
+        fn read(&self, bytes: &[u8]) -> Result<Activity> {
+            let samples = std::str::from_utf8(bytes)?
+                .lines()
+                .map(|l| {
+                    let v: Vec<f64> = l.split(',').map(|x| x.parse().unwrap()).collect();
+                    crate::activity::Sample {
+                        point: Point::new(v[1], v[0]),
+                        time: None,
+                        elevation: Some(v[2]),
+                        sensors: Default::default(),
+                    }
+                })
+                .collect();
+            Ok(Activity {
+                segments: vec![samples],
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn load_with_custom_reader() {
+        let csv: String = (0..11)
+            .map(|i| format!("{},10.0,{}\n", 45.0 + i as f64 * 0.001, 100 + i * 10))
+            .collect();
+        let mut ctx = Context::new("logger.csv").with_reader(CsvReader);
+        ctx.load_from_bytes(csv.as_bytes()).unwrap();
+        assert_eq!(ctx.get_string("value_distance").unwrap(), "1km");
+        assert_eq!(ctx.get_string("value_uphill").unwrap(), "100m");
+        assert_eq!(ctx.get_string("value_track_name").unwrap(), "logger");
+    }
+
+    #[test]
+    fn load_fit_from_bytes() {
+        let records: Vec<_> = (0..60)
+            .map(|i| {
+                (
+                    i * 2,
+                    45.0 + i as f64 * 0.0001,
+                    10.0,
+                    100.0 + i as f64,
+                    130,
+                    180,
+                )
+            })
+            .collect();
+        let mut ctx = Context::new("ride.fit");
+        ctx.load_from_bytes(&crate::test_util::build_fit(&records))
+            .unwrap();
+        assert_eq!(ctx.coords().unwrap().len(), 60);
+        assert_eq!(ctx.get_string("value_track_name").unwrap(), "ride");
+        assert_eq!(ctx.get_string("value_hr_avg").unwrap(), "130bpm");
+        assert_eq!(ctx.get_string("value_time").unwrap(), "00:01:58");
+    }
+
+    #[test]
+    fn truncated_stats_and_marker() {
+        let mut ctx = Context::new("test/t1.gpx").with_units(Units::Imperial);
+        ctx.load().unwrap();
+        let total = ctx.data.as_ref().unwrap().distance;
+
+        let half = ctx.truncated(total / 2.0).unwrap();
+        let d = half.data.as_ref().unwrap();
+        assert!((d.distance - total / 2.0).abs() < 100.0, "{}", d.distance);
+        assert!(half.coords().unwrap().len() < ctx.coords().unwrap().len());
+        assert!(half.get_string("value_distance").unwrap().ends_with("mi"));
+        assert_eq!(half.get_string("value_date"), ctx.get_string("value_date"));
+
+        let inp = InputPath::new("M 0,100 200,0").unwrap();
+        let (x, y) = ctx.elevation_marker(&inp, total / 2.0).unwrap();
+        assert!((x - 100.0).abs() < 0.01);
+        assert!((-100.0..=0.0).contains(&y));
+    }
+
+    #[test]
+    fn pause_options_override_sport_thresholds() {
+        // 60s riding, 20s at 1 km/h, 60s riding.
+        let records: Vec<_> = (0..141)
+            .map(|i| {
+                let lat = match i {
+                    0..=60 => i as f64 * 0.00005,
+                    61..=80 => 60.0 * 0.00005 + (i - 60) as f64 * 0.0000025,
+                    _ => 60.0 * 0.00005 + 20.0 * 0.0000025 + (i - 80) as f64 * 0.00005,
+                };
+                (i, 45.0 + lat, 10.0, 100.0, 130, 180)
+            })
+            .collect();
+        let bytes = crate::test_util::build_fit(&records);
+
+        let mut ctx = Context::new("ride.fit");
+        ctx.load_from_bytes(&bytes).unwrap();
+        assert_eq!(ctx.get_string("value_stopped_time").unwrap(), "00:00:20");
+        assert_eq!(ctx.get_string("value_pause_count").unwrap(), "1");
+        assert_eq!(ctx.get_string("value_moving_time").unwrap(), "00:02:00");
+
+        let mut ctx = Context::new("ride.fit").with_pause_options(PauseOptions {
+            min_speed: 0.5,
+            ..PauseOptions::cycling()
+        });
+        ctx.load_from_bytes(&bytes).unwrap();
+        assert_eq!(ctx.get_string("value_stopped_time").unwrap(), "00:00:00");
+        assert_eq!(ctx.get_string("value_pause_count").unwrap(), "0");
+    }
+
+    #[test]
+    fn offline_map_and_attribution() {
+        let tiles: TileSource = "test/tiles".parse().unwrap();
+        let mut ctx =
+            Context::new("test/t1.gpx").with_map_options(MapOptions::default().with_tiles(tiles));
+        ctx.load().unwrap();
+        let href = ctx.get_image("image_map", 120, 80, None).unwrap();
+        assert!(href.starts_with("data:image/png;base64,"));
+        assert_eq!(ctx.get_string("value_map_attribution").unwrap(), "");
+
+        ctx.set_map_options(MapOptions::default().with_tiles("dark".parse().unwrap()));
+        assert!(
+            ctx.get_string("value_map_attribution")
+                .unwrap()
+                .contains("CARTO")
+        );
+    }
+
+    #[test]
+    fn map_markers_for_t1() {
+        let mut ctx = Context::new("test/t1.gpx");
+        assert!(ctx.map_markers(&"all".parse().unwrap()).is_empty());
+        ctx.load().unwrap();
+        let markers = ctx.map_markers(&"all".parse().unwrap());
+        let count = |kind| markers.iter().filter(|m| m.kind == kind).count();
+        let ticks = (ctx.data.as_ref().unwrap().distance / 5000.0).floor() as usize;
+        assert!(ticks > 0);
+        assert_eq!(count(MarkerKind::Distance), ticks);
+        assert_eq!(count(MarkerKind::Waypoint), 0);
+        assert_eq!(markers.last().unwrap().kind, MarkerKind::Start);
+        let highest = markers.iter().find(|m| m.kind == MarkerKind::Highest);
+        let raw_max = (ctx.activity.as_ref().unwrap().segments.iter().flatten())
+            .filter_map(|s| s.elevation)
+            .fold(f64::MIN, f64::max);
+        assert_eq!(highest.unwrap().label, Some(ctx.units().elevation(raw_max)));
+        let max_speed = markers.iter().find(|m| m.kind == MarkerKind::MaxSpeed);
+        assert_eq!(max_speed.unwrap().label, ctx.get_string("value_speed_max"));
+
+        let markers = ctx.map_markers(&"finish".parse().unwrap());
+        assert_eq!(markers.len(), 1);
+        assert_eq!(Some(&markers[0].point), ctx.coords().unwrap().last());
+    }
+
+    #[test]
+    fn track_gradient_for_t1() {
+        let mut ctx = Context::new("test/t1.gpx");
+        ctx.load().unwrap();
+        let options = MapOptions::default().with_coloring(TrackColoring {
+            by: ColorBy::HeartRate,
+            ramp: ColorRamp::default(),
+        });
+        assert!(ctx.map_overlay(&MapOptions::default()).gradient.is_none());
+        let gradient = ctx.map_overlay(&options).gradient.unwrap();
+        assert_eq!(gradient.colors.len(), ctx.coords().unwrap().len());
+        assert!(gradient.min < gradient.max);
+
+        ctx.set_map_options(options);
+        let area = GroupBox {
+            x: 0.0,
+            y: 0.0,
+            width: 200.0,
+            height: 35.0,
+            ..Default::default()
+        };
+        let legend = ctx.get_group("group_map_legend", &area).unwrap();
+        assert!(legend.contains(">Heart rate</text>"));
+        assert!(legend.contains(&format!(">{:.0}bpm</text>", gradient.max)));
+    }
+
+    #[test]
+    fn elevation_profile_group() {
+        let mut ctx = Context::new("test/t1.gpx");
+        ctx.load().unwrap();
+        let area = GroupBox {
+            width: 600.0,
+            height: 200.0,
+            options: Some("fill axes".to_string()),
+            fill: Some("#334155".to_string()),
+            ..Default::default()
+        };
+        let svg = ctx.get_group("group_elevation_profile", &area).unwrap();
+        assert!(svg.contains(r##"fill="#334155""##));
+        assert!(svg.contains(">20km<") && !svg.contains("<circle"));
+
+        let area = GroupBox {
+            options: Some("grid".to_string()),
+            ..area
+        };
+        assert_eq!(ctx.get_group("group_elevation_profile", &area), None);
+    }
+
+    #[test]
+    fn hr_zones_for_t1() {
+        let mut ctx = Context::new("test/t1.gpx");
+        ctx.load().unwrap();
+        assert_eq!(ctx.get_string("value_hr_z1_time").unwrap(), "-");
+
+        let mut ctx = Context::new("test/t1.gpx")
+            .with_zone_options(ZoneOptions::default().with_hr_max(190.0));
+        ctx.load().unwrap();
+        let percent: f64 = (1..=5)
+            .map(|i| {
+                let v = ctx.get_string(&format!("value_hr_z{i}_percent")).unwrap();
+                v.trim_end_matches('%').parse::<f64>().unwrap()
+            })
+            .sum();
+        assert!((percent - 100.0).abs() <= 2.0, "{percent}");
+        assert_ne!(ctx.get_string("value_hr_z3_time").unwrap(), "-");
+        assert_eq!(ctx.get_string("value_hr_z6_time").unwrap(), "-");
+        assert_eq!(ctx.get_string("value_hr_z1_speed"), None);
+        // No FTP.
+        assert_eq!(ctx.get_string("value_power_z1_time").unwrap(), "-");
+
+        let area = GroupBox {
+            width: 400.0,
+            height: 150.0,
+            ..Default::default()
+        };
+        let svg = ctx.get_group("group_hr_zones", &area).unwrap();
+        assert_eq!(svg.matches("<rect").count(), 5);
+    }
+
+    #[test]
+    fn sensor_graphs_for_t1() {
+        let mut ctx = Context::new("test/t1.gpx");
+        ctx.load().unwrap();
+        let inp = InputPath::new("M 10,200 210,100").unwrap();
+        for k in [
+            "path_speed",
+            "path_hr",
+            "path_hr_time",
+            "path_elevation_time",
+        ] {
+            let d = ctx.get_path(k, &inp).unwrap();
+            assert!(d.starts_with("M 10,200 l 0.000 "), "{k}: {d}");
+        }
+        // No power meter.
+        assert_eq!(ctx.get_path("path_power", &inp), None);
+    }
 }
@@ -0,0 +1,257 @@
+//! Elevation processing: resampling, smoothing and total gain.
+//!
+//! Raw elevation is noisy (GPS or barometric), and summing every tiny up and down
+//! overestimates climbing in a way that depends on the device sample rate. The pipeline
+//! first resamples the profile on a regular distance or time grid, then smooths it and
+//! finally sums the climbs with a configurable algorithm.
+
+/// How the raw samples are resampled before smoothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resample {
+    None,
+    /// One point every given number of metres.
+    Distance(f64),
+    /// One point every given number of seconds. Falls back to `None` without timestamps.
+    Time(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    None,
+    /// Centered moving average over `window` points (rounded down to an odd number).
+    MovingAverage {
+        window: usize,
+    },
+    /// One-dimensional Kalman filter with constant elevation model.
+    Kalman {
+        process_noise: f64,
+        measurement_noise: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gain {
+    /// Sum of every positive and negative difference.
+    Raw,
+    /// Only count a climb or a descent once it exceeds `threshold` metres.
+    Hysteresis { threshold: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElevationOptions {
+    pub resample: Resample,
+    pub smoothing: Smoothing,
+    pub gain: Gain,
+}
+
+impl Default for ElevationOptions {
+    fn default() -> Self {
+        ElevationOptions {
+            resample: Resample::Distance(10.0),
+            smoothing: Smoothing::MovingAverage { window: 5 },
+            gain: Gain::Hysteresis { threshold: 2.0 },
+        }
+    }
+}
+
+/// A point of the elevation profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElevationSample {
+    /// Cumulative distance in metres.
+    pub d: f64,
+    /// Elapsed time in seconds, if known.
+    pub t: Option<f64>,
+    /// Elevation in metres.
+    pub e: f64,
+}
+
+fn lerp(a: f64, b: f64, f: f64) -> f64 {
+    a + (b - a) * f
+}
+
+fn resample_by<F>(points: &[ElevationSample], step: f64, key: F) -> Vec<ElevationSample>
+where
+    F: Fn(&ElevationSample) -> f64,
+{
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return vec![];
+    };
+    if step <= 0.0 {
+        return points.to_vec();
+    }
+
+    let mut out = vec![*first];
+    let mut next = key(first) + step;
+    for w in points.windows(2) {
+        let (k1, k2) = (key(&w[0]), key(&w[1]));
+        while k2 > k1 && next <= k2 {
+            let f = (next - k1) / (k2 - k1);
+            out.push(ElevationSample {
+                d: lerp(w[0].d, w[1].d, f),
+                t: w[0].t.zip(w[1].t).map(|(t1, t2)| lerp(t1, t2, f)),
+                e: lerp(w[0].e, w[1].e, f),
+            });
+            next += step;
+        }
+    }
+    if out.last() != Some(last) {
+        out.push(*last);
+    }
+    out
+}
+
+pub fn resample(points: &[ElevationSample], resample: Resample) -> Vec<ElevationSample> {
+    match resample {
+        Resample::None => points.to_vec(),
+        Resample::Distance(step) => resample_by(points, step, |p| p.d),
+        Resample::Time(step) => {
+            if points.iter().all(|p| p.t.is_some()) {
+                resample_by(points, step, |p| p.t.unwrap_or_default())
+            } else {
+                points.to_vec()
+            }
+        }
+    }
+}
+
+pub fn smooth(points: &mut [ElevationSample], smoothing: Smoothing) {
+    match smoothing {
+        Smoothing::None => {}
+        Smoothing::MovingAverage { window } => {
+            let half = window / 2;
+            if half == 0 || points.is_empty() {
+                return;
+            }
+            let mut prefix = Vec::with_capacity(points.len() + 1);
+            prefix.push(0.0);
+            for p in points.iter() {
+                prefix.push(prefix.last().copied().unwrap_or_default() + p.e);
+            }
+            let n = points.len();
+            for (i, p) in points.iter_mut().enumerate() {
+                // Shrink the window symmetrically at the edges so that linear climbs
+                // keep their exact gain.
+                let h = half.min(i).min(n - 1 - i);
+                p.e = (prefix[i + h + 1] - prefix[i - h]) / (2 * h + 1) as f64;
+            }
+        }
+        Smoothing::Kalman {
+            process_noise,
+            measurement_noise,
+        } => {
+            let Some(first) = points.first() else {
+                return;
+            };
+            let mut x = first.e;
+            let mut p = 1.0;
+            for s in points.iter_mut() {
+                p += process_noise;
+                let k = p / (p + measurement_noise);
+                x += k * (s.e - x);
+                p *= 1.0 - k;
+                s.e = x;
+            }
+        }
+    }
+}
+
+/// Total `(uphill, downhill)` in metres.
+pub fn gain(points: &[ElevationSample], gain: Gain) -> (f64, f64) {
+    let mut uphill = 0.0;
+    let mut downhill = 0.0;
+    match gain {
+        Gain::Raw => {
+            for w in points.windows(2) {
+                let d = w[1].e - w[0].e;
+                if d > 0.0 {
+                    uphill += d;
+                } else {
+                    downhill -= d;
+                }
+            }
+        }
+        Gain::Hysteresis { threshold } => {
+            let Some(first) = points.first() else {
+                return (0.0, 0.0);
+            };
+            let mut reference = first.e;
+            for p in points {
+                let d = p.e - reference;
+                if d >= threshold {
+                    uphill += d;
+                    reference = p.e;
+                } else if -d >= threshold {
+                    downhill -= d;
+                    reference = p.e;
+                }
+            }
+        }
+    }
+    (uphill, downhill)
+}
+
+/// Run the whole pipeline on one segment, returning the processed profile and the
+/// `(uphill, downhill)` totals.
+pub fn process(
+    points: &[ElevationSample],
+    opts: &ElevationOptions,
+) -> (Vec<ElevationSample>, f64, f64) {
+    let mut profile = resample(points, opts.resample);
+    smooth(&mut profile, opts.smoothing);
+    let (uphill, downhill) = gain(&profile, opts.gain);
+    (profile, uphill, downhill)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn profile(e: &[f64], step: f64) -> Vec<ElevationSample> {
+        e.iter()
+            .enumerate()
+            .map(|(i, e)| ElevationSample {
+                d: i as f64 * step,
+                t: Some(i as f64),
+                e: *e,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resample_by_distance_interpolates() {
+        let p = profile(&[0.0, 10.0, 20.0], 25.0);
+        let r = resample(&p, Resample::Distance(10.0));
+        let d: Vec<f64> = r.iter().map(|s| s.d).collect();
+        assert_eq!(d, vec![0.0, 10.0, 20.0, 30.0, 40.0, 50.0]);
+        assert_approx_eq!(r[1].e, 4.0);
+        assert_approx_eq!(r[3].t.unwrap(), 1.2);
+    }
+
+    #[test]
+    fn hysteresis_ignores_noise() {
+        let p = profile(
+            &[100.0, 101.0, 100.0, 101.0, 100.0, 105.0, 104.0, 110.0],
+            10.0,
+        );
+        assert_eq!(gain(&p, Gain::Raw), (13.0, 3.0));
+        assert_eq!(gain(&p, Gain::Hysteresis { threshold: 2.0 }), (10.0, 0.0));
+    }
+
+    #[test]
+    fn smoothing_flattens_spikes() {
+        let mut p = profile(&[100.0, 100.0, 110.0, 100.0, 100.0], 10.0);
+        smooth(&mut p, Smoothing::MovingAverage { window: 5 });
+        assert_approx_eq!(p[2].e, 102.0);
+
+        let mut k = profile(&[100.0, 100.0, 110.0, 100.0, 100.0], 10.0);
+        smooth(
+            &mut k,
+            Smoothing::Kalman {
+                process_noise: 0.01,
+                measurement_noise: 4.0,
+            },
+        );
+        assert!(k[2].e < 103.0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Minimal decoder for Garmin FIT activity files.
+//!
+//! Only the messages needed to build an [`Activity`] are interpreted (`record`, `event`,
+//! `session` and `sport`); everything else is skipped using the definition messages.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use chrono::DateTime;
+use geo_types::Point;
+
+use crate::activity::{Activity, Sample, SensorRecord};
+use crate::sensors::Sensors;
+
+// Seconds between the unix epoch and the FIT epoch (1989-12-31T00:00:00Z).
+const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+pub(crate) const SEMICIRCLES_TO_DEGREES: f64 = 180.0 / 2_147_483_648.0;
+
+const MESG_SPORT: u16 = 12;
+const MESG_SESSION: u16 = 18;
+pub(crate) const MESG_RECORD: u16 = 20;
+const MESG_EVENT: u16 = 21;
+
+const FIELD_TIMESTAMP: u8 = 253;
+
+#[derive(Debug, Clone)]
+struct FieldDef {
+    num: u8,
+    size: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    global: u16,
+    big_endian: bool,
+    fields: Vec<FieldDef>,
+    dev_size: usize,
+}
+
+/// Raw field values of one data message, keyed by field number.
+struct Message<'a> {
+    big_endian: bool,
+    fields: Vec<(u8, &'a [u8])>,
+}
+
+impl Message<'_> {
+    fn raw(&self, num: u8) -> Option<&[u8]> {
+        self.fields.iter().find(|(n, _)| *n == num).map(|(_, b)| *b)
+    }
+
+    fn uint(&self, num: u8) -> Option<u64> {
+        let b = self.raw(num)?;
+        let v = match b.len() {
+            1 => b[0] as u64,
+            2 => {
+                let a = [b[0], b[1]];
+                if self.big_endian {
+                    u16::from_be_bytes(a) as u64
+                } else {
+                    u16::from_le_bytes(a) as u64
+                }
+            }
+            4 => {
+                let a = [b[0], b[1], b[2], b[3]];
+                if self.big_endian {
+                    u32::from_be_bytes(a) as u64
+                } else {
+                    u32::from_le_bytes(a) as u64
+                }
+            }
+            _ => return None,
+        };
+        // All bits set marks an invalid value.
+        let invalid = (1u64 << (b.len() * 8)) - 1;
+        (v != invalid).then_some(v)
+    }
+
+    fn sint(&self, num: u8) -> Option<i64> {
+        let b = self.raw(num)?;
+        let v = match b.len() {
+            1 => b[0] as i8 as i64,
+            2 => {
+                let a = [b[0], b[1]];
+                if self.big_endian {
+                    i16::from_be_bytes(a) as i64
+                } else {
+                    i16::from_le_bytes(a) as i64
+                }
+            }
+            4 => {
+                let a = [b[0], b[1], b[2], b[3]];
+                if self.big_endian {
+                    i32::from_be_bytes(a) as i64
+                } else {
+                    i32::from_le_bytes(a) as i64
+                }
+            }
+            _ => return None,
+        };
+        // The maximum positive value marks an invalid value.
+        let invalid = (1i64 << (b.len() * 8 - 1)) - 1;
+        (v != invalid).then_some(v)
+    }
+
+    fn string(&self, num: u8) -> Option<String> {
+        let b = self.raw(num)?;
+        let end = b.iter().position(|c| *c == 0).unwrap_or(b.len());
+        let s = String::from_utf8_lossy(&b[..end]).trim().to_string();
+        (!s.is_empty()).then_some(s)
+    }
+}
+
+fn sport_name(sport: u64) -> Option<&'static str> {
+    match sport {
+        0 => Some("generic"),
+        1 => Some("running"),
+        2 => Some("cycling"),
+        5 => Some("swimming"),
+        11 => Some("walking"),
+        17 => Some("hiking"),
+        _ => None,
+    }
+}
+
+fn read_u16(bytes: &[u8], big_endian: bool) -> u16 {
+    let a = [bytes[0], bytes[1]];
+    if big_endian {
+        u16::from_be_bytes(a)
+    } else {
+        u16::from_le_bytes(a)
+    }
+}
+
+/// Decode a FIT activity file.
+pub fn read_fit(bytes: &[u8]) -> Result<Activity> {
+    let header_size = *bytes.first().ok_or(anyhow!("empty FIT file"))? as usize;
+    if header_size < 12 || bytes.len() < header_size || &bytes[8..12] != b".FIT" {
+        return Err(anyhow!("invalid FIT header"));
+    }
+    let data_size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+    let end = (header_size + data_size).min(bytes.len());
+
+    let mut definitions: HashMap<u8, Definition> = HashMap::new();
+    let mut activity = Activity::default();
+    let mut segment: Vec<Sample> = vec![];
+    let mut last_timestamp: Option<u32> = None;
+    let mut pos = header_size;
+
+    while pos < end {
+        let header = bytes[pos];
+        pos += 1;
+
+        if header & 0x80 == 0 && header & 0x40 != 0 {
+            // Definition message.
+            let local = header & 0x0F;
+            let has_dev = header & 0x20 != 0;
+            let fixed = bytes
+                .get(pos..pos + 5)
+                .ok_or(anyhow!("truncated FIT definition"))?;
+            let big_endian = fixed[1] == 1;
+            let global = read_u16(&fixed[2..4], big_endian);
+            let n_fields = fixed[4] as usize;
+            pos += 5;
+
+            let raw = bytes
+                .get(pos..pos + n_fields * 3)
+                .ok_or(anyhow!("truncated FIT definition"))?;
+            let fields = raw
+                .chunks(3)
+                .map(|f| FieldDef {
+                    num: f[0],
+                    size: f[1] as usize,
+                })
+                .collect();
+            pos += n_fields * 3;
+
+            let mut dev_size = 0;
+            if has_dev {
+                let n_dev = *bytes.get(pos).ok_or(anyhow!("truncated FIT definition"))? as usize;
+                pos += 1;
+                let raw = bytes
+                    .get(pos..pos + n_dev * 3)
+                    .ok_or(anyhow!("truncated FIT definition"))?;
+                dev_size = raw.chunks(3).map(|f| f[1] as usize).sum();
+                pos += n_dev * 3;
+            }
+
+            definitions.insert(
+                local,
+                Definition {
+                    global,
+                    big_endian,
+                    fields,
+                    dev_size,
+                },
+            );
+            continue;
+        }
+
+        // Data message, either with a normal or a compressed timestamp header.
+        let (local, time_offset) = if header & 0x80 != 0 {
+            ((header >> 5) & 0x03, Some((header & 0x1F) as u32))
+        } else {
+            (header & 0x0F, None)
+        };
+        let def = definitions
+            .get(&local)
+            .ok_or(anyhow!("FIT data message without definition"))?;
+
+        let mut fields = Vec::with_capacity(def.fields.len());
+        for f in &def.fields {
+            let raw = bytes
+                .get(pos..pos + f.size)
+                .ok_or(anyhow!("truncated FIT data message"))?;
+            fields.push((f.num, raw));
+            pos += f.size;
+        }
+        pos += def.dev_size;
+
+        let msg = Message {
+            big_endian: def.big_endian,
+            fields,
+        };
+
+        let timestamp = match (msg.uint(FIELD_TIMESTAMP), time_offset, last_timestamp) {
+            (Some(t), _, _) => Some(t as u32),
+            (None, Some(offset), Some(last)) => {
+                let mut t = (last & !0x1F) | offset;
+                if offset < (last & 0x1F) {
+                    t += 0x20;
+                }
+                Some(t)
+            }
+            _ => None,
+        };
+        if timestamp.is_some() {
+            last_timestamp = timestamp;
+        }
+
+        match def.global {
+            MESG_RECORD => {
+                let time = timestamp
+                    .and_then(|t| DateTime::from_timestamp(t as i64 + FIT_EPOCH_OFFSET, 0));
+                let sensors = Sensors {
+                    hr: msg.uint(3).map(|v| v as f64),
+                    cadence: msg.uint(4).map(|v| v as f64),
+                    power: msg.uint(7).map(|v| v as f64),
+                    temperature: msg.sint(13).map(|v| v as f64),
+                };
+                let (Some(lat), Some(lon)) = (msg.sint(0), msg.sint(1)) else {
+                    // No GPS fix: keep the sensor values.
+                    if let Some(time) = time
+                        && sensors != Sensors::default()
+                    {
+                        activity.sensor_records.push(SensorRecord { time, sensors });
+                    }
+                    continue;
+                };
+                let elevation = msg
+                    .uint(78)
+                    .or_else(|| msg.uint(2))
+                    .map(|v| v as f64 / 5.0 - 500.0);
+                segment.push(Sample {
+                    point: Point::new(
+                        lon as f64 * SEMICIRCLES_TO_DEGREES,
+                        lat as f64 * SEMICIRCLES_TO_DEGREES,
+                    ),
+                    time,
+                    elevation,
+                    sensors,
+                });
+            }
+            MESG_EVENT => {
+                // Timer stop: start a new segment with the next record.
+                let is_timer = msg.uint(0) == Some(0);
+                let is_stop = matches!(msg.uint(1), Some(1) | Some(4));
+                if is_timer && is_stop && !segment.is_empty() {
+                    activity.segments.push(std::mem::take(&mut segment));
+                }
+            }
+            MESG_SPORT => {
+                if let Some(name) = msg.string(3) {
+                    activity.name.get_or_insert(name);
+                }
+            }
+            MESG_SESSION => {
+                if let Some(sport) = msg.uint(5).and_then(sport_name) {
+                    activity.sport.get_or_insert(sport.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !segment.is_empty() {
+        activity.segments.push(segment);
+    }
+
+    Ok(activity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::build_fit;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn decode_records() {
+        let fit = build_fit(&[
+            (1_000_000_000, 45.0, 10.0, 100.0, 120, 200),
+            (1_000_000_001, 45.0001, 10.0001, 101.0, 0xFF, 0xFFFF),
+        ]);
+        let a = read_fit(&fit).unwrap();
+        assert_eq!(a.segments.len(), 1);
+        let s = &a.segments[0];
+        assert_eq!(s.len(), 2);
+        assert_approx_eq!(s[0].point.y(), 45.0, 1e-6);
+        assert_approx_eq!(s[0].point.x(), 10.0, 1e-6);
+        assert_approx_eq!(s[1].elevation.unwrap(), 101.0);
+        assert_eq!(s[0].sensors.hr, Some(120.0));
+        assert_eq!(s[0].sensors.power, Some(200.0));
+        // 0xFF / 0xFFFF are invalid values.
+        assert_eq!(s[1].sensors.hr, None);
+        assert_eq!(s[1].sensors.power, None);
+        assert_eq!(
+            s[0].time.unwrap().timestamp(),
+            1_000_000_000 + FIT_EPOCH_OFFSET
+        );
+    }
+
+    #[test]
+    fn records_without_position_keep_sensors() {
+        // 180° saturates to the invalid latitude.
+        let fit = build_fit(&[
+            (1_000_000_000, 45.0, 10.0, 100.0, 120, 200),
+            (1_000_000_005, 180.0, 10.0, 100.0, 150, 250),
+            (1_000_000_006, 180.0, 10.0, 100.0, 0xFF, 0xFFFF),
+        ]);
+        let a = read_fit(&fit).unwrap();
+        assert_eq!(a.segments.len(), 1);
+        assert_eq!(a.segments[0].len(), 1);
+        // Without any sensor value the record is dropped.
+        assert_eq!(a.sensor_records.len(), 1);
+        let r = a.sensor_records[0];
+        assert_eq!(r.time.timestamp(), 1_000_000_005 + FIT_EPOCH_OFFSET);
+        assert_eq!(r.sensors.hr, Some(150.0));
+        assert_eq!(r.sensors.power, Some(250.0));
+    }
+
+    #[test]
+    fn reject_non_fit_data() {
+        assert!(read_fit(b"<?xml version=\"1.0\"?><gpx/>").is_err());
+    }
+}
@@ -0,0 +1,403 @@
+//! Track colored by a per-point metric (speed, grade, elevation or heart rate) along a
+//! color ramp, and the matching legend.
+
+use std::fmt;
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use anyhow::{Error, Result, anyhow, bail};
+use galileo::Color;
+use geo::{Distance, Geodesic};
+use geo_types::Point;
+
+use crate::activity::Sample;
+
+// Speed and grade are measured over about this distance around each point.
+const WINDOW: f64 = 100.0;
+// Shorter windows (long gaps, standing still) give no value.
+const MIN_WINDOW: f64 = 20.0;
+// Number of distinct colors along the track, so that runs of points share a stroke.
+const LEVELS: f64 = 16.0;
+
+/// Metric a gradient-colored track follows, e.g. from the `data-color-by` attribute of
+/// `image_map`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorBy {
+    Speed,
+    Grade,
+    Elevation,
+    HeartRate,
+}
+
+impl ColorBy {
+    pub const ALL: [ColorBy; 4] = [
+        ColorBy::Speed,
+        ColorBy::Grade,
+        ColorBy::Elevation,
+        ColorBy::HeartRate,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorBy::Speed => "speed",
+            ColorBy::Grade => "grade",
+            ColorBy::Elevation => "elevation",
+            ColorBy::HeartRate => "hr",
+        }
+    }
+}
+
+impl fmt::Display for ColorBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ColorBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "heart-rate" => Ok(ColorBy::HeartRate),
+            s => ColorBy::ALL
+                .into_iter()
+                .find(|c| c.name() == s)
+                .ok_or_else(|| {
+                    anyhow!("unknown color metric '{s}', expected speed, grade, elevation or hr")
+                }),
+        }
+    }
+}
+
+/// Colors from the lowest to the highest value, evenly spaced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRamp(Vec<Color>);
+
+impl Default for ColorRamp {
+    /// Blue, green, yellow and red.
+    fn default() -> Self {
+        ColorRamp(vec![
+            Color::rgba(37, 99, 235, 255),
+            Color::rgba(34, 197, 94, 255),
+            Color::rgba(250, 204, 21, 255),
+            Color::rgba(239, 68, 68, 255),
+        ])
+    }
+}
+
+impl ColorRamp {
+    pub fn new(colors: Vec<Color>) -> Result<ColorRamp> {
+        if colors.len() < 2 {
+            bail!("a color ramp needs at least 2 colors");
+        }
+        Ok(ColorRamp(colors))
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.0
+    }
+
+    /// Color at `t`, from 0 (first color) to 1 (last color).
+    pub fn color_at(&self, t: f64) -> Color {
+        let t = t.clamp(0.0, 1.0) * (self.0.len() - 1) as f64;
+        let i = (t.floor() as usize).min(self.0.len() - 2);
+        let (c1, c2, f) = (self.0[i], self.0[i + 1], t - i as f64);
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * f).round() as u8;
+        Color::rgba(
+            mix(c1.r(), c2.r()),
+            mix(c1.g(), c2.g()),
+            mix(c1.b(), c2.b()),
+            mix(c1.a(), c2.a()),
+        )
+    }
+}
+
+impl FromStr for ColorRamp {
+    type Err = Error;
+
+    /// `#rrggbb` colors separated by commas or spaces, e.g. `#2563EB,#EF4444`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors = s
+            .split([' ', ','])
+            .filter(|c| !c.is_empty())
+            .map(|c| Color::try_from_hex(c).ok_or(anyhow!("invalid ramp color '{c}'")))
+            .collect::<Result<Vec<_>>>()?;
+        ColorRamp::new(colors)
+    }
+}
+
+/// Gradient coloring asked for a track map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackColoring {
+    pub by: ColorBy,
+    pub ramp: ColorRamp,
+}
+
+/// One value per sample of `segments`, in the order of the track coordinates, `None`
+/// where unknown. Speeds are in km/h and grades in %.
+pub fn point_values(segments: &[Vec<Sample>], by: ColorBy) -> Vec<Option<f64>> {
+    segments
+        .iter()
+        .flat_map(|samples| segment_values(samples, by))
+        .collect()
+}
+
+fn segment_values(samples: &[Sample], by: ColorBy) -> Vec<Option<f64>> {
+    match by {
+        ColorBy::Elevation => return samples.iter().map(|s| s.elevation).collect(),
+        ColorBy::HeartRate => return samples.iter().map(|s| s.sensors.hr).collect(),
+        ColorBy::Speed | ColorBy::Grade => {}
+    }
+
+    let mut total = 0.0;
+    let distances: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            if i > 0 {
+                total += Geodesic.distance(samples[i - 1].point, s.point);
+            }
+            total
+        })
+        .collect();
+
+    // Averaging over a window smooths out GPS noise.
+    (0..samples.len())
+        .map(|i| {
+            let lo = distances.partition_point(|d| *d < distances[i] - WINDOW / 2.0);
+            let hi = distances.partition_point(|d| *d <= distances[i] + WINDOW / 2.0) - 1;
+            let length = distances[hi] - distances[lo];
+            if length < MIN_WINDOW {
+                return None;
+            }
+            let (a, b) = (&samples[lo], &samples[hi]);
+            match by {
+                ColorBy::Speed => {
+                    let seconds = (b.time? - a.time?).num_milliseconds() as f64 / 1000.0;
+                    (seconds > 0.0).then(|| length / seconds * 3.6)
+                }
+                _ => Some((b.elevation? - a.elevation?) / length * 100.0),
+            }
+        })
+        .collect()
+}
+
+/// Per-point colors of a track and the values at the ends of the ramp.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackGradient {
+    pub by: ColorBy,
+    pub ramp: ColorRamp,
+    pub min: f64,
+    pub max: f64,
+    /// One color per point.
+    pub colors: Vec<Color>,
+}
+
+impl TrackGradient {
+    /// Colors for `values`, `None` when there are none. The ramp spans the 5th to 95th
+    /// percentiles so a few outliers don't flatten it, and points without a value take
+    /// the color of the previous one.
+    pub fn new(coloring: &TrackColoring, values: &[Option<f64>]) -> Option<TrackGradient> {
+        let mut sorted: Vec<f64> = values.iter().flatten().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        let min = *sorted.get(n * 5 / 100)?;
+        let max = sorted[(n - 1) * 95 / 100].max(min + f64::EPSILON);
+
+        let first = values.iter().flatten().next().copied()?;
+        let mut last = first;
+        let colors = values
+            .iter()
+            .map(|v| {
+                last = v.unwrap_or(last);
+                let t = ((last - min) / (max - min)).clamp(0.0, 1.0);
+                coloring
+                    .ramp
+                    .color_at((t * (LEVELS - 1.0)).round() / (LEVELS - 1.0))
+            })
+            .collect();
+        Some(TrackGradient {
+            by: coloring.by,
+            ramp: coloring.ramp.clone(),
+            min,
+            max,
+            colors,
+        })
+    }
+
+    /// Legend of `width` x `height`: the title, the ramp as a bar and the labels of the
+    /// lowest and highest values under its ends. The text uses the fill of the parent.
+    pub fn legend_svg(
+        &self,
+        width: f64,
+        height: f64,
+        title: &str,
+        min_label: &str,
+        max_label: &str,
+    ) -> String {
+        let font_size = height / 3.5;
+        let colors = self.ramp.colors();
+        let mut svg = String::from(r#"<defs><linearGradient id="map_legend_ramp">"#);
+        for (i, color) in colors.iter().enumerate() {
+            let _ = write!(
+                svg,
+                r#"<stop offset="{:.3}" {}/>"#,
+                i as f64 / (colors.len() - 1) as f64,
+                paint("stop-color", *color)
+            );
+        }
+        let _ = write!(
+            svg,
+            r#"</linearGradient></defs><text x="0" y="{:.2}" font-size="{font_size:.2}" font-weight="bold">{}</text><rect x="0" y="{:.2}" width="{width:.2}" height="{:.2}" rx="{:.2}" fill="url(#map_legend_ramp)"/><text x="0" y="{height:.2}" font-size="{font_size:.2}">{}</text><text x="{width:.2}" y="{height:.2}" font-size="{font_size:.2}" text-anchor="end">{}</text>"#,
+            font_size * 0.9,
+            quick_xml::escape::escape(title),
+            font_size * 1.3,
+            font_size * 0.9,
+            font_size * 0.45,
+            quick_xml::escape::escape(min_label),
+            quick_xml::escape::escape(max_label),
+        );
+        svg
+    }
+}
+
+/// `tracks` split into runs of points with the same color, each run starting at the
+/// last point of the previous one. Without a gradient (or when it doesn't match the
+/// tracks) each track is one run in `color`.
+pub fn color_runs(
+    tracks: &[&[Point<f64>]],
+    gradient: Option<&TrackGradient>,
+    color: Color,
+) -> Vec<(Color, Vec<Point<f64>>)> {
+    let points: usize = tracks.iter().map(|t| t.len()).sum();
+    let Some(gradient) = gradient.filter(|g| g.colors.len() == points) else {
+        return tracks.iter().map(|t| (color, t.to_vec())).collect();
+    };
+
+    let mut runs: Vec<(Color, Vec<Point<f64>>)> = vec![];
+    let mut colors = gradient.colors.iter();
+    for track in tracks {
+        let mut track_colors = colors.by_ref().take(track.len());
+        track_colors.next();
+        let mut current: Option<(Color, Vec<Point<f64>>)> = None;
+        for (w, color) in track.windows(2).zip(track_colors) {
+            match &mut current {
+                Some((c, points)) if c == color => points.push(w[1]),
+                _ => {
+                    runs.extend(current.take());
+                    current = Some((*color, vec![w[0], w[1]]));
+                }
+            }
+        }
+        runs.extend(current);
+    }
+    runs
+}
+
+/// `name="#rrggbb"`, with the opacity when not opaque.
+fn paint(name: &str, color: Color) -> String {
+    let mut paint = format!(
+        r##"{name}="#{:02x}{:02x}{:02x}""##,
+        color.r(),
+        color.g(),
+        color.b()
+    );
+    if color.a() < 255 {
+        let _ = write!(
+            paint,
+            r#" {name}-opacity="{:.3}""#,
+            color.a() as f32 / 255.0
+        );
+    }
+    paint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    use crate::sensors::Sensors;
+
+    fn samples() -> Vec<Sample> {
+        // 20m apart northwards, every 4s (18km/h) for the first half then every 2s,
+        // climbing 1m per point.
+        let t0 = DateTime::<Utc>::from_timestamp(1_792_108_800, 0).unwrap();
+        let mut t = t0;
+        (0..60)
+            .map(|i| {
+                if i > 0 {
+                    t += chrono::Duration::seconds(if i <= 30 { 4 } else { 2 });
+                }
+                Sample {
+                    point: Point::new(10.0, 45.0 + i as f64 * 20.0 / 111_132.0),
+                    time: Some(t),
+                    elevation: Some(100.0 + i as f64),
+                    sensors: Sensors {
+                        hr: (i % 10 != 0).then_some(120.0 + i as f64),
+                        ..Default::default()
+                    },
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn metrics_per_point() {
+        let segments = vec![samples()];
+        let speed = point_values(&segments, ColorBy::Speed);
+        assert_eq!(speed.len(), 60);
+        assert!((speed[10].unwrap() - 18.0).abs() < 0.2, "{:?}", speed[10]);
+        assert!((speed[50].unwrap() - 36.0).abs() < 0.4, "{:?}", speed[50]);
+
+        let grade = point_values(&segments, ColorBy::Grade);
+        assert!(grade.iter().all(|g| (g.unwrap() - 5.0).abs() < 0.1));
+        let hr = point_values(&segments, ColorBy::HeartRate);
+        assert_eq!((hr[0], hr[1]), (None, Some(121.0)));
+
+        assert_eq!("heart-rate".parse::<ColorBy>().unwrap(), ColorBy::HeartRate);
+        assert!("cadence".parse::<ColorBy>().is_err());
+    }
+
+    #[test]
+    fn ramp_colors() {
+        let ramp: ColorRamp = "#000000, #FF0000,#FFFFFF".parse().unwrap();
+        assert_eq!(ramp.color_at(0.0), Color::rgba(0, 0, 0, 255));
+        assert_eq!(ramp.color_at(0.25), Color::rgba(128, 0, 0, 255));
+        assert_eq!(ramp.color_at(0.75), Color::rgba(255, 128, 128, 255));
+        assert_eq!(ramp.color_at(2.0), Color::rgba(255, 255, 255, 255));
+        assert!("#000000".parse::<ColorRamp>().is_err());
+        assert!("#000000,blue".parse::<ColorRamp>().is_err());
+    }
+
+    #[test]
+    fn gradient_runs_and_legend() {
+        let coloring = TrackColoring {
+            by: ColorBy::HeartRate,
+            ramp: ColorRamp::default(),
+        };
+        let segments = vec![samples()];
+        let gradient =
+            TrackGradient::new(&coloring, &point_values(&segments, ColorBy::HeartRate)).unwrap();
+        assert_eq!(gradient.colors[0], gradient.colors[1]);
+        assert_eq!(gradient.colors[59], Color::rgba(239, 68, 68, 255));
+        assert!(gradient.min > 121.0 && gradient.max < 179.0);
+
+        let coords: Vec<_> = segments[0].iter().map(|s| s.point).collect();
+        let runs = color_runs(&[&coords], Some(&gradient), Color::rgba(0, 0, 0, 255));
+        assert!(runs.len() > 2 && runs.len() <= 16);
+        assert_eq!(runs[0].1[0], coords[0]);
+        assert_eq!(runs.last().unwrap().1.last(), coords.last());
+        for w in runs.windows(2) {
+            assert_eq!(w[0].1.last(), w[1].1.first());
+            assert_ne!(w[0].0, w[1].0);
+        }
+        // Mismatched tracks are drawn in the plain color.
+        let runs = color_runs(&[&coords[1..]], Some(&gradient), Color::rgba(0, 0, 0, 255));
+        assert_eq!(runs.len(), 1);
+
+        let legend = gradient.legend_svg(200.0, 35.0, "Heart rate", "125bpm", "<175bpm");
+        assert!(legend.contains(r##"<stop offset="0.000" stop-color="#2563eb"/>"##));
+        assert!(legend.contains("&lt;175bpm"));
+    }
+}
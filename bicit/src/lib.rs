@@ -1,65 +1,79 @@
+pub mod activity;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod animation;
+pub mod charts;
+pub mod climbs;
 pub mod context;
+pub mod elevation;
 pub mod embedded_templates;
+pub mod fit;
+pub mod gradient;
+pub mod locale;
 pub mod map;
+pub mod markers;
+pub mod pause;
+pub mod profile;
 pub mod render;
+pub mod sensors;
+pub mod stats;
+pub mod summary;
+pub mod svg_path;
+pub mod tcx;
 pub mod template;
+#[cfg(test)]
+mod test_util;
+pub mod tiles;
 pub mod time;
+pub mod units;
+pub mod vector;
+pub mod zones;
 
 pub use context::Context;
 pub use embedded_templates::{EmbeddedTemplate, get_template_by_name, get_templates};
+pub use locale::Locale;
+pub use summary::Summary;
 pub use template::Template;
+pub use units::Units;
 
 use anyhow::Result;
 
-pub struct InputPath<'a> {
+/// Box of a `path_*` template element, where generated paths are drawn: from the bottom
+/// left corner `ss`, `length` to the right and `height` (negative) up.
+pub struct InputPath {
     pub height: f64,
     pub length: f64,
-    pub ss: &'a str,
-    pub prefix: &'a str,
+    pub ss: String,
+    pub prefix: &'static str,
 }
 
-impl InputPath<'_> {
-    pub fn new(path: &str) -> Result<InputPath<'_>> {
-        let mut ss: &str = "";
-        let mut prefix: &str = "";
-        let mut height: f64 = 0.0;
-        let mut length: f64 = 0.0;
-        let mut sx: f64 = 0.0;
-        let mut sy: f64 = 0.0;
-
-        for (i, s) in path.split(' ').enumerate() {
-            if i == 0 {
-                assert!(s == "m" || s == "M");
-                prefix = s;
-            } else if i == 1 {
-                ss = s;
-                let it: Vec<&str> = s.split(',').collect();
-                sy = it[1].parse().unwrap();
-                sx = it[0].parse().unwrap();
-            } else if i == 2 {
-                let it: Vec<&str> = s.split(',').collect();
-                height = match prefix {
-                    "m" => it[1].parse::<f64>().unwrap(),
-                    "M" => it[1].parse::<f64>().unwrap() - sy,
-                    _ => 0.0,
-                };
-                length = match prefix {
-                    "m" => it[0].parse::<f64>().unwrap(),
-                    "M" => it[0].parse::<f64>().unwrap() - sx,
-                    _ => 0.0,
-                };
-            } else {
-                break;
-            }
-        }
-
+impl InputPath {
+    /// Box of the shape drawn by the path data `path`, e.g. a line from the bottom left
+    /// to the top right corner.
+    pub fn new(path: &str) -> Result<InputPath> {
+        let bbox = svg_path::bounding_box(path)?;
+        // The first moveto is absolute either way, keep the one of the template.
+        let prefix = if path.trim_start().starts_with('m') {
+            "m"
+        } else {
+            "M"
+        };
         Ok(InputPath {
-            height,
-            ss,
-            length,
+            height: -bbox.height(),
+            length: bbox.width(),
+            ss: format!("{},{}", bbox.min_x, bbox.max_y),
             prefix,
         })
     }
+
+    /// Box with its top left corner at `x`, `y`, e.g. from a `data-bbox` attribute.
+    pub fn from_box(x: f64, y: f64, width: f64, height: f64) -> InputPath {
+        InputPath {
+            height: -height,
+            length: width,
+            ss: format!("{},{}", x, y + height),
+            prefix: "M",
+        }
+    }
 }
 
 #[cfg(test)]
@@ -84,4 +98,23 @@ mod tests {
         assert_eq!(i.prefix, "M");
         assert_eq!(i.ss, "5.2,174.6");
     }
+
+    #[test]
+    fn parse_path_any_shape() {
+        // Drawn the other way, or as a rectangle: same box.
+        for d in [
+            "m 137.5,140.2 -132.3,34.4",
+            "M 5.2,140.2 H 137.5 V 174.6 H 5.2 Z",
+        ] {
+            let i = InputPath::new(d).unwrap();
+            assert_approx_eq!(i.height, -34.4);
+            assert_approx_eq!(i.length, 132.3);
+            let (x, y) = i.ss.split_once(',').unwrap();
+            assert_approx_eq!(x.parse::<f64>().unwrap(), 5.2);
+            assert_approx_eq!(y.parse::<f64>().unwrap(), 174.6);
+        }
+        let i = InputPath::from_box(5.0, 140.0, 130.0, 30.0);
+        assert_eq!((i.ss.as_str(), i.length, i.height), ("5,170", 130.0, -30.0));
+        assert!(InputPath::new("5.2,174.6 137.5,140.2").is_err());
+    }
 }
@@ -0,0 +1,284 @@
+//! Locale-dependent formatting: decimal separator, month and weekday
+//! names and template labels.
+
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+use anyhow::{Error, anyhow};
+use chrono::format::{Fixed, Item, StrftimeItems};
+use chrono::{DateTime, Datelike, TimeZone, Weekday};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    It,
+    De,
+}
+
+const MONTHS_EN: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const MONTHS_IT: [&str; 12] = [
+    "gennaio",
+    "febbraio",
+    "marzo",
+    "aprile",
+    "maggio",
+    "giugno",
+    "luglio",
+    "agosto",
+    "settembre",
+    "ottobre",
+    "novembre",
+    "dicembre",
+];
+const MONTHS_DE: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+
+const WEEKDAYS_EN: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const WEEKDAYS_IT: [&str; 7] = [
+    "lunedì",
+    "martedì",
+    "mercoledì",
+    "giovedì",
+    "venerdì",
+    "sabato",
+    "domenica",
+];
+const WEEKDAYS_DE: [&str; 7] = [
+    "Montag",
+    "Dienstag",
+    "Mittwoch",
+    "Donnerstag",
+    "Freitag",
+    "Samstag",
+    "Sonntag",
+];
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::En, Locale::It, Locale::De];
+
+    /// ISO 639-1 code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::It => "it",
+            Locale::De => "de",
+        }
+    }
+
+    pub fn decimal_separator(&self) -> char {
+        match self {
+            Locale::En => '.',
+            Locale::It | Locale::De => ',',
+        }
+    }
+
+    /// Format `v` with `decimals` digits after the decimal separator, e.g. `12,5`.
+    pub fn decimal(&self, v: f64, decimals: usize) -> String {
+        let s = format!("{v:.decimals$}");
+        match self.decimal_separator() {
+            '.' => s,
+            sep => s.replacen('.', &sep.to_string(), 1),
+        }
+    }
+
+    /// Full month name, `month` is 1-based.
+    pub fn month_name(&self, month: u32) -> &'static str {
+        let months = match self {
+            Locale::En => &MONTHS_EN,
+            Locale::It => &MONTHS_IT,
+            Locale::De => &MONTHS_DE,
+        };
+        months[(month.clamp(1, 12) - 1) as usize]
+    }
+
+    /// Abbreviated month name, e.g. `Oct`, `ott` or `Okt`.
+    pub fn short_month_name(&self, month: u32) -> &'static str {
+        abbreviate(self.month_name(month), 3)
+    }
+
+    pub fn weekday_name(&self, weekday: Weekday) -> &'static str {
+        let weekdays = match self {
+            Locale::En => &WEEKDAYS_EN,
+            Locale::It => &WEEKDAYS_IT,
+            Locale::De => &WEEKDAYS_DE,
+        };
+        weekdays[weekday.num_days_from_monday() as usize]
+    }
+
+    /// Abbreviated weekday name, e.g. `Fri`, `ven` or `Fr`.
+    pub fn short_weekday_name(&self, weekday: Weekday) -> &'static str {
+        let len = match self {
+            Locale::De => 2,
+            Locale::En | Locale::It => 3,
+        };
+        abbreviate(self.weekday_name(weekday), len)
+    }
+
+    /// Long date, e.g. `16 October 2026`, `16 ottobre 2026` or `16. Oktober 2026`.
+    pub fn date(&self, t: &impl Datelike) -> String {
+        let month = self.month_name(t.month());
+        match self {
+            Locale::De => format!("{}. {} {}", t.day(), month, t.year()),
+            Locale::En | Locale::It => format!("{} {} {}", t.day(), month, t.year()),
+        }
+    }
+
+    /// strftime-like formatting where the weekday and month names (`%A`, `%a`, `%B`,
+    /// `%b`) are localized. Returns `None` for an invalid format.
+    pub fn strftime<Tz: TimeZone>(&self, t: &DateTime<Tz>, fmt: &str) -> Option<String>
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let mut out = String::new();
+        for item in StrftimeItems::new(fmt).parse().ok()? {
+            match item {
+                Item::Fixed(Fixed::LongWeekdayName) => out.push_str(self.weekday_name(t.weekday())),
+                Item::Fixed(Fixed::ShortWeekdayName) => {
+                    out.push_str(self.short_weekday_name(t.weekday()))
+                }
+                Item::Fixed(Fixed::LongMonthName) => out.push_str(self.month_name(t.month())),
+                Item::Fixed(Fixed::ShortMonthName) => {
+                    out.push_str(self.short_month_name(t.month()))
+                }
+                item => write!(out, "{}", t.format_with_items([item].iter())).ok()?,
+            }
+        }
+        Some(out)
+    }
+
+    /// Translation for a template label, looked up by the part of the `label_*` id after
+    /// the prefix.
+    pub fn label(&self, key: &str) -> Option<&'static str> {
+        let (en, it, de) = match key {
+            "distance" => ("Distance", "Distanza", "Distanz"),
+            "time" => ("Time", "Durata", "Dauer"),
+            "moving_time" => ("Moving time", "Durata mov", "Fahrzeit"),
+            "stopped_time" => ("Stopped time", "Tempo fermo", "Pausenzeit"),
+            "speed" => ("Avg speed", "Velocità media", "Ø Tempo"),
+            "speed_moving" => ("Moving speed", "Velocità mov", "Ø Fahrtempo"),
+            "speed_max" => ("Max speed", "Velocità max", "Max. Tempo"),
+            "velocity" => ("Speed", "Velocità", "Tempo"),
+            "uphill" => ("Ascent", "Ascesa", "Aufstieg"),
+            "downhill" => ("Descent", "Discesa", "Abstieg"),
+            "elevation_max" => ("Max elevation", "Altezza max", "Max. Höhe"),
+            "elevation_min" => ("Min elevation", "Altezza min", "Min. Höhe"),
+            "elevation" => ("Elevation", "Altezza", "Höhe"),
+            "grade" => ("Grade", "Pendenza", "Steigung"),
+            "hr" => ("Heart rate", "Frequenza cardiaca", "Herzfrequenz"),
+            "cadence" => ("Cadence", "Cadenza", "Trittfrequenz"),
+            "power" => ("Power", "Potenza", "Leistung"),
+            "temperature" => ("Temperature", "Temperatura", "Temperatur"),
+            "date" => ("Date", "Data", "Datum"),
+            "ride_count" => ("rides", "uscite", "Fahrten"),
+            "longest_ride" => ("Longest ride", "Uscita più lunga", "Längste Fahrt"),
+            _ => return None,
+        };
+        Some(match self {
+            Locale::En => en,
+            Locale::It => it,
+            Locale::De => de,
+        })
+    }
+}
+
+/// The first `len` characters of `name`.
+fn abbreviate(name: &'static str, len: usize) -> &'static str {
+    let end = name.char_indices().nth(len).map_or(name.len(), |(i, _)| i);
+    &name[..end]
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = Error;
+
+    /// Accepts `it`, `it_IT`, `it-IT.UTF-8` and the like.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lang = s.split(['_', '-', '.']).next().unwrap_or_default();
+        Locale::ALL
+            .into_iter()
+            .find(|l| l.code().eq_ignore_ascii_case(lang))
+            .ok_or_else(|| anyhow!("unsupported locale '{s}', expected en, it or de"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_comma() {
+        assert_eq!(Locale::It.decimal(12.46, 1), "12,5");
+        assert_eq!(Locale::De.decimal(-0.5, 2), "-0,50");
+        assert_eq!(Locale::En.decimal(12.46, 1), "12.5");
+        assert_eq!(Locale::It.decimal(12.46, 0), "12");
+    }
+
+    #[test]
+    fn localized_dates() {
+        let t = DateTime::from_timestamp(1_792_108_800, 0).unwrap();
+        assert_eq!(Locale::En.date(&t), "16 October 2026");
+        assert_eq!(Locale::It.date(&t), "16 ottobre 2026");
+        assert_eq!(Locale::De.date(&t), "16. Oktober 2026");
+        assert_eq!(
+            Locale::It.strftime(&t, "%A %d/%m/%Y").unwrap(),
+            "venerdì 16/10/2026"
+        );
+        assert_eq!(Locale::It.strftime(&t, "%a %d %b").unwrap(), "ven 16 ott");
+        assert_eq!(
+            Locale::De.strftime(&t, "%a, %d. %b").unwrap(),
+            "Fr, 16. Okt"
+        );
+        assert_eq!(Locale::En.strftime(&t, "%a %b").unwrap(), "Fri Oct");
+        assert_eq!(
+            Locale::De.strftime(&t, "%B %%A %%b").unwrap(),
+            "Oktober %A %b"
+        );
+        assert_eq!(Locale::De.strftime(&t, "%B %Q"), None);
+    }
+
+    #[test]
+    fn parse_locale() {
+        assert_eq!("de_DE.UTF-8".parse::<Locale>().unwrap(), Locale::De);
+        assert_eq!("IT".parse::<Locale>().unwrap(), Locale::It);
+        assert!("fr".parse::<Locale>().is_err());
+    }
+}
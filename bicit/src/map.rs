@@ -1,9 +1,9 @@
-use std::io::Cursor;
+use std::fmt;
+use std::str::FromStr;
 use std::time::Duration;
 
-use anyhow::{Result, anyhow};
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD as BASE64;
+use anyhow::{Error, Result, anyhow};
+use galileo::layer::RasterTileLayer;
 use galileo::layer::feature_layer::symbol::Symbol;
 use galileo::layer::raster_tile_layer::RasterTileLayerBuilder;
 use galileo::layer::{FeatureLayer, Layer, feature_layer::FeatureLayerOptions};
@@ -11,7 +11,7 @@ use galileo::render::render_bundle::RenderBundle;
 use galileo::render::{LineCap, LinePaint, WgpuRenderer};
 use galileo::tile_schema::TileSchemaBuilder;
 use galileo::{Color, Map, MapView, Messenger};
-use galileo_types::cartesian::{CartesianPoint3d, Point3, Size};
+use galileo_types::cartesian::{CartesianPoint3d, Point2, Point3, Size};
 use galileo_types::geo::impls::GeoPoint2d;
 use galileo_types::geo::{Crs, NewGeoPoint};
 use galileo_types::geometry::Geom;
@@ -20,9 +20,184 @@ use galileo_types::impls::Contour;
 use galileo_types::{Disambig, MultiContour};
 use geo::Simplify;
 use geo_types::{LineString, Point};
-use image::{DynamicImage, ImageBuffer, ImageFormat, Rgba};
+use tiny_skia::{IntSize, Pixmap};
 use wgpu::{Device, Queue};
 
+use crate::gradient::{TrackColoring, TrackGradient, color_runs};
+use crate::markers::{self, MapOverlay, MarkerOptions};
+use crate::template::Template;
+use crate::tiles::{self, MapFrame, TileSource};
+use crate::vector::{VectorStyle, vector_layers};
+
+/// How maps are drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MapRenderer {
+    /// GPU, or the CPU when no GPU adapter is available.
+    #[default]
+    Auto,
+    Gpu,
+    /// Tiles stitched and the track drawn with tiny-skia, e.g. on headless servers.
+    Cpu,
+}
+
+impl MapRenderer {
+    pub const ALL: [MapRenderer; 3] = [MapRenderer::Auto, MapRenderer::Gpu, MapRenderer::Cpu];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapRenderer::Auto => "auto",
+            MapRenderer::Gpu => "gpu",
+            MapRenderer::Cpu => "cpu",
+        }
+    }
+}
+
+impl fmt::Display for MapRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for MapRenderer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MapRenderer::ALL
+            .into_iter()
+            .find(|r| r.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow!("unknown map renderer '{s}', expected auto, gpu or cpu"))
+    }
+}
+
+/// Map rendering settings shared by the track and summary maps.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapOptions {
+    pub tiles: TileSource,
+    /// Replaces `{key}` in tile URLs.
+    pub api_key: Option<String>,
+    pub renderer: MapRenderer,
+    /// Colors of vector tile maps.
+    pub vector_style: VectorStyle,
+    /// Markers the map should show, see [`Context::map_markers`](crate::Context::map_markers).
+    pub markers: MarkerOptions,
+    /// Track colored by a metric instead of the track color.
+    pub coloring: Option<TrackColoring>,
+}
+
+impl MapOptions {
+    pub fn with_tiles(mut self, tiles: TileSource) -> Self {
+        self.tiles = tiles;
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_renderer(mut self, renderer: MapRenderer) -> Self {
+        self.renderer = renderer;
+        self
+    }
+
+    pub fn with_vector_style(mut self, vector_style: VectorStyle) -> Self {
+        self.vector_style = vector_style;
+        self
+    }
+
+    pub fn with_markers(mut self, markers: MarkerOptions) -> Self {
+        self.markers = markers;
+        self
+    }
+
+    pub fn with_coloring(mut self, coloring: TrackColoring) -> Self {
+        self.coloring = Some(coloring);
+        self
+    }
+
+    /// Whether maps are drawn on the CPU without trying the GPU. Offline tiles always are.
+    pub fn uses_cpu(&self) -> bool {
+        self.renderer == MapRenderer::Cpu || self.tiles.is_offline()
+    }
+
+    /// Use the map style, colors, markers and track coloring the template asks for, if any.
+    pub fn with_template_style(self, template: &Template) -> Self {
+        let options = self.with_template_settings(template);
+        match template.map_style() {
+            Some(tiles) => options.with_tiles(tiles),
+            None => options,
+        }
+    }
+
+    /// Use the vector map colors, the markers and the track coloring of the template but
+    /// not its tiles. Invalid settings are ignored.
+    pub fn with_template_settings(self, template: &Template) -> Self {
+        let style = template
+            .map_colors()
+            .and_then(|colors| VectorStyle::default().with_colors(&colors).ok());
+        let options = match style {
+            Some(style) => self.with_vector_style(style),
+            None => self,
+        };
+        let mut options = options.with_markers(template.map_markers().unwrap_or_default());
+        options.coloring = template.track_coloring();
+        options
+    }
+}
+
+/// Raster layer for the online tile sources, `osm` builds the [`TileSource::Osm`] one.
+fn tile_layer(
+    options: &MapOptions,
+    osm: impl FnOnce() -> RasterTileLayerBuilder,
+) -> Result<RasterTileLayer> {
+    let builder = match &options.tiles {
+        TileSource::Url(url) => {
+            if url.needs_api_key() && options.api_key.is_none() {
+                return Err(anyhow!("tile URL {} needs an API key", url.url));
+            }
+            let url = url.clone();
+            let api_key = options.api_key.clone();
+            RasterTileLayerBuilder::new_rest(move |index| {
+                url.tile_url(
+                    u32::try_from(index.z).unwrap_or_default(),
+                    u32::try_from(index.x).unwrap_or_default(),
+                    u32::try_from(index.y).unwrap_or_default(),
+                    api_key.as_deref(),
+                )
+            })
+        }
+        _ => osm(),
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = builder.with_file_cache_checked(".tile_cache");
+    let mut layer = builder
+        .build()
+        .map_err(|e| anyhow!("error creating {} layer: {e}", options.tiles))?;
+
+    // Without this, the first render can be partially transparent due to fade-in.
+    layer.set_fade_in_duration(Duration::default());
+    Ok(layer)
+}
+
+/// The basemap layer drawn under the track, and for vector tiles the label layer drawn
+/// over it, with their tiles loaded for `map_view`.
+async fn basemap_layers(
+    options: &MapOptions,
+    osm: impl FnOnce() -> RasterTileLayerBuilder,
+    map_view: &MapView,
+) -> Result<(Box<dyn Layer>, Option<Box<dyn Layer>>)> {
+    if options.tiles.is_vector() {
+        let (basemap, labels) = vector_layers(options)?;
+        basemap.load_tiles(map_view).await;
+        labels.load_tiles(map_view).await;
+        return Ok((Box::new(basemap), Some(Box::new(labels))));
+    }
+    let basemap = tile_layer(options, osm)?;
+    basemap.load_tiles(map_view).await;
+    Ok((Box::new(basemap), None))
+}
+
 pub struct TrackLayers {
     pub inner: FeatureLayer<
         GeoPoint2d,
@@ -124,22 +299,33 @@ fn dedupe_consecutive_coords(coords: &[Point<f64>]) -> Vec<Point<f64>> {
 }
 
 pub fn get_layers(coords: &[Point<f64>], track_color: Option<Color>) -> TrackLayers {
-    let coords = dedupe_consecutive_coords(coords);
-
-    // Simplify a potentially very dense polyline (reduces render time / overdraw).
-    let raw_line: LineString<f64> = coords.iter().map(|p| (p.x(), p.y())).collect();
-    let simplified = simplify_linestring(&raw_line, 2000);
+    get_tracks_layers(&[coords], track_color)
+}
 
-    let points: Vec<GeoPoint2d> = simplified
-        .points()
-        .map(|p| NewGeoPoint::latlon(p.y(), p.x()))
+/// Same as [`get_layers`] with several independent tracks, e.g. for a summary map.
+pub fn get_tracks_layers(tracks: &[&[Point<f64>]], track_color: Option<Color>) -> TrackLayers {
+    // Keep the total number of points bounded whatever the number of tracks.
+    let max_points = (2000 / tracks.len().max(1)).max(200);
+    let contours: Vec<Disambig<Contour<GeoPoint2d>, GeoSpace2d>> = tracks
+        .iter()
+        .map(|coords| {
+            let coords = dedupe_consecutive_coords(coords);
+
+            // Simplify a potentially very dense polyline (reduces render time / overdraw).
+            let raw_line: LineString<f64> = coords.iter().map(|p| (p.x(), p.y())).collect();
+            let simplified = simplify_linestring(&raw_line, max_points);
+
+            let points: Vec<GeoPoint2d> = simplified
+                .points()
+                .map(|p| NewGeoPoint::latlon(p.y(), p.x()))
+                .collect();
+            Disambig::new(Contour::open(points))
+        })
         .collect();
 
-    let contour: Disambig<Contour<GeoPoint2d>, GeoSpace2d> = Disambig::new(Contour::open(points));
-
     // "Cased" line: outline + inner stroke.
     let track_outline_layer = FeatureLayer::new(
-        vec![contour.clone()],
+        contours.clone(),
         RoundSegmentContourSymbol {
             color: Color::rgba(0, 0, 0, 200),
             width: 10.0,
@@ -155,7 +341,7 @@ pub fn get_layers(coords: &[Point<f64>], track_color: Option<Color>) -> TrackLay
     let track_color = track_color.unwrap_or(Color::rgba(255, 45, 85, 255));
 
     let track_inner_layer = FeatureLayer::new(
-        vec![contour],
+        contours,
         RoundSegmentContourSymbol {
             color: track_color,
             width: 6.0,
@@ -173,15 +359,73 @@ pub fn get_layers(coords: &[Point<f64>], track_color: Option<Color>) -> TrackLay
     }
 }
 
-/// Renders an OSM map with the provided track overlay and returns a `data:image/png;base64,...` href.
+/// Inner stroke layers in the colors of `gradient`, one per color, drawn over the
+/// [`TrackLayers`].
+fn gradient_layers(tracks: &[&[Point<f64>]], gradient: &TrackGradient) -> Vec<Box<dyn Layer>> {
+    let runs = color_runs(tracks, Some(gradient), tiles::DEFAULT_TRACK_COLOR);
+    let total: usize = runs.iter().map(|(_, points)| points.len()).sum();
+    let mut contours: Vec<(Color, Vec<Disambig<Contour<GeoPoint2d>, GeoSpace2d>>)> = vec![];
+    for (color, points) in runs {
+        // Same budget as get_tracks_layers, shared by the runs.
+        let max_points = (2000 * points.len() / total.max(1)).max(2);
+        let raw_line: LineString<f64> = dedupe_consecutive_coords(&points)
+            .iter()
+            .map(|p| (p.x(), p.y()))
+            .collect();
+        let points: Vec<GeoPoint2d> = simplify_linestring(&raw_line, max_points)
+            .points()
+            .map(|p| NewGeoPoint::latlon(p.y(), p.x()))
+            .collect();
+        let contour = Disambig::new(Contour::open(points));
+        match contours.iter_mut().find(|(c, _)| *c == color) {
+            Some((_, same_color)) => same_color.push(contour),
+            None => contours.push((color, vec![contour])),
+        }
+    }
+
+    contours
+        .into_iter()
+        .map(|(color, contours)| {
+            let layer = FeatureLayer::new(
+                contours,
+                RoundSegmentContourSymbol { color, width: 6.0 },
+                Crs::WGS84,
+            )
+            .with_options(FeatureLayerOptions {
+                sort_by_depth: true,
+                use_antialiasing: true,
+                ..Default::default()
+            });
+            Box::new(layer) as Box<dyn Layer>
+        })
+        .collect()
+}
+
+/// Renders a map with the provided track overlay and returns a `data:image/png;base64,...` href.
 ///
-/// `coords` are expected to be WGS84 lon/lat points.
+/// `coords` are expected to be WGS84 lon/lat points, `overlay` is drawn over the track.
 pub async fn render_track_map_href_async(
     coords: &[Point<f64>],
     image_size: Size<u32>,
     track_color: Option<Color>,
+    options: &MapOptions,
+    overlay: &MapOverlay,
 ) -> Result<String> {
-    if coords.is_empty() {
+    render_tracks_map_href_async(&[coords], image_size, track_color, options, overlay).await
+}
+
+/// Renders a map with several tracks overlaid, framed on all of them.
+///
+/// Offline tile sources are stitched on the CPU, see [`tiles`]. Vector tiles put their
+/// labels over the tracks, see [`vector`](crate::vector).
+pub async fn render_tracks_map_href_async(
+    tracks: &[&[Point<f64>]],
+    image_size: Size<u32>,
+    track_color: Option<Color>,
+    options: &MapOptions,
+    overlay: &MapOverlay,
+) -> Result<String> {
+    if tracks.iter().all(|t| t.is_empty()) {
         return Err(anyhow!("error building map: no coordinates"));
     }
 
@@ -189,7 +433,27 @@ pub async fn render_track_map_href_async(
         return Err(anyhow!("error building map: invalid image size"));
     }
 
-    let track_layers = get_layers(coords, track_color);
+    let render_cpu = || {
+        tiles::render_tracks_map_href(
+            tracks,
+            image_size.width(),
+            image_size.height(),
+            track_color,
+            options,
+            overlay,
+        )
+    };
+    if options.uses_cpu() {
+        return render_cpu();
+    }
+    let Some(renderer) = WgpuRenderer::new_with_texture_rt(image_size).await else {
+        return match options.renderer {
+            MapRenderer::Auto => render_cpu(),
+            _ => Err(anyhow!("error creating renderer: no GPU adapter")),
+        };
+    };
+
+    let track_layers = get_tracks_layers(tracks, track_color);
 
     let extent = track_layers
         .inner
@@ -207,34 +471,30 @@ pub async fn render_track_map_href_async(
     let resolution = (width_resolution.max(height_resolution) * 1.1).max(min_resolution);
 
     #[cfg(target_arch = "wasm32")]
-    let osm_builder = RasterTileLayerBuilder::new_rest(|index| {
-        format!("/tiles/{}/{}/{}.png", index.z, index.x, index.y)
-    });
+    let osm_builder = || {
+        RasterTileLayerBuilder::new_rest(|index| {
+            format!("/tiles/{}/{}/{}.png", index.z, index.x, index.y)
+        })
+    };
 
     #[cfg(not(target_arch = "wasm32"))]
-    let osm_builder = RasterTileLayerBuilder::new_osm().with_file_cache_checked(".tile_cache");
-    let mut osm = osm_builder
-        .build()
-        .map_err(|e| anyhow!("error creating OSM layer: {e}"))?;
-
-    // Without this, the first render can be partially transparent due to fade-in.
-    osm.set_fade_in_duration(Duration::default());
+    let osm_builder = RasterTileLayerBuilder::new_osm;
 
     let map_view = MapView::new_projected(&center, resolution).with_size(image_size.cast());
-    osm.load_tiles(&map_view).await;
+    let (basemap, labels) = basemap_layers(options, osm_builder, &map_view).await?;
 
-    let layers: Vec<Box<dyn Layer>> = vec![
-        Box::new(osm),
+    let mut layers: Vec<Box<dyn Layer>> = vec![
+        basemap,
         Box::new(track_layers.outline),
         Box::new(track_layers.inner),
     ];
+    if let Some(gradient) = &overlay.gradient {
+        layers.extend(gradient_layers(tracks, gradient));
+    }
+    layers.extend(labels);
 
     let map = Map::new(map_view, layers, None::<Box<dyn Messenger>>);
 
-    let renderer = WgpuRenderer::new_with_texture_rt(image_size)
-        .await
-        .ok_or(anyhow!("error creating renderer"))?;
-
     renderer
         .render(&map)
         .map_err(|e| anyhow!("error rendering map: {e}"))?;
@@ -244,14 +504,25 @@ pub async fn render_track_map_href_async(
         .await
         .map_err(|e| anyhow!("error retrieving rendered bitmap: {e}"))?;
 
-    let buffer =
-        ImageBuffer::<Rgba<u8>, _>::from_raw(image_size.width(), image_size.height(), bitmap)
-            .ok_or(anyhow!("error creating image buffer"))?;
-
-    let mut png = Vec::new();
-    DynamicImage::ImageRgba8(buffer).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    bitmap_href(bitmap, image_size, tracks, overlay)
+}
 
-    Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
+/// PNG href of a rendered map, with the overlay markers drawn on the CPU.
+fn bitmap_href(
+    bitmap: Vec<u8>,
+    image_size: Size<u32>,
+    tracks: &[&[Point<f64>]],
+    overlay: &MapOverlay,
+) -> Result<String> {
+    let size = IntSize::from_wh(image_size.width(), image_size.height())
+        .ok_or(anyhow!("error building map: invalid image size"))?;
+    // The map is opaque, so the bitmap is already premultiplied.
+    let mut pixmap =
+        Pixmap::from_vec(bitmap, size).ok_or(anyhow!("error creating image buffer"))?;
+    if let Some(frame) = MapFrame::for_tracks(tracks, image_size.width(), image_size.height()) {
+        markers::draw_markers(&mut pixmap, &frame, &overlay.markers)?;
+    }
+    tiles::pixmap_href(&pixmap)
 }
 
 /// Same as [`render_track_map_href_async`] but reuses an existing wgpu `Device`/`Queue`.
@@ -263,6 +534,8 @@ pub async fn render_track_map_href_with_wgpu_async(
     coords: &[Point<f64>],
     image_size: Size<u32>,
     track_color: Option<Color>,
+    options: &MapOptions,
+    overlay: &MapOverlay,
 ) -> Result<String> {
     if coords.is_empty() {
         return Err(anyhow!("error building map: no coordinates"));
@@ -272,6 +545,17 @@ pub async fn render_track_map_href_with_wgpu_async(
         return Err(anyhow!("error building map: invalid image size"));
     }
 
+    if options.uses_cpu() {
+        return tiles::render_tracks_map_href(
+            &[coords],
+            image_size.width(),
+            image_size.height(),
+            track_color,
+            options,
+            overlay,
+        );
+    }
+
     let track_layers = get_layers(coords, track_color);
 
     let extent = track_layers
@@ -289,25 +573,19 @@ pub async fn render_track_map_href_with_wgpu_async(
         .expect("tile schema has zoom level 17");
     let resolution = (width_resolution.max(height_resolution) * 1.1).max(min_resolution);
 
-    #[cfg(target_arch = "wasm32")]
-    let osm_builder = RasterTileLayerBuilder::new_osm();
-
-    #[cfg(not(target_arch = "wasm32"))]
-    let osm_builder = RasterTileLayerBuilder::new_osm().with_file_cache_checked(".tile_cache");
-    let mut osm = osm_builder
-        .build()
-        .map_err(|e| anyhow!("error creating OSM layer: {e}"))?;
-
-    osm.set_fade_in_duration(Duration::default());
-
     let map_view = MapView::new_projected(&center, resolution).with_size(image_size.cast());
-    osm.load_tiles(&map_view).await;
+    let (basemap, labels) =
+        basemap_layers(options, RasterTileLayerBuilder::new_osm, &map_view).await?;
 
-    let layers: Vec<Box<dyn Layer>> = vec![
-        Box::new(osm),
+    let mut layers: Vec<Box<dyn Layer>> = vec![
+        basemap,
         Box::new(track_layers.outline),
         Box::new(track_layers.inner),
     ];
+    if let Some(gradient) = &overlay.gradient {
+        layers.extend(gradient_layers(&[coords], gradient));
+    }
+    layers.extend(labels);
 
     let map = Map::new(map_view, layers, None::<Box<dyn Messenger>>);
 
@@ -322,14 +600,7 @@ pub async fn render_track_map_href_with_wgpu_async(
         .await
         .map_err(|e| anyhow!("error retrieving rendered bitmap: {e}"))?;
 
-    let buffer =
-        ImageBuffer::<Rgba<u8>, _>::from_raw(image_size.width(), image_size.height(), bitmap)
-            .ok_or(anyhow!("error creating image buffer"))?;
-
-    let mut png = Vec::new();
-    DynamicImage::ImageRgba8(buffer).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
-
-    Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
+    bitmap_href(bitmap, image_size, &[coords], overlay)
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -337,11 +608,39 @@ pub fn render_track_map_href(
     coords: &[Point<f64>],
     image_size: Size<u32>,
     track_color: Option<Color>,
+    options: &MapOptions,
+    overlay: &MapOverlay,
 ) -> Result<String> {
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()?;
-    runtime.block_on(render_track_map_href_async(coords, image_size, track_color))
+    runtime.block_on(render_track_map_href_async(
+        coords,
+        image_size,
+        track_color,
+        options,
+        overlay,
+    ))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_tracks_map_href(
+    tracks: &[&[Point<f64>]],
+    image_size: Size<u32>,
+    track_color: Option<Color>,
+    options: &MapOptions,
+    overlay: &MapOverlay,
+) -> Result<String> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(render_tracks_map_href_async(
+        tracks,
+        image_size,
+        track_color,
+        options,
+        overlay,
+    ))
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -351,6 +650,8 @@ pub fn render_track_map_href_with_wgpu(
     coords: &[Point<f64>],
     image_size: Size<u32>,
     track_color: Option<Color>,
+    options: &MapOptions,
+    overlay: &MapOverlay,
 ) -> Result<String> {
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -361,9 +662,65 @@ pub fn render_track_map_href_with_wgpu(
         coords,
         image_size,
         track_color,
+        options,
+        overlay,
     ))
 }
 
+/// Renders the tiles of `frame` without any track, as premultiplied RGBA pixels, e.g.
+/// to draw a track on it later.
+pub async fn render_base_map_async(frame: &MapFrame, options: &MapOptions) -> Result<Vec<u8>> {
+    if frame.width == 0 || frame.height == 0 {
+        return Err(anyhow!("error building map: invalid image size"));
+    }
+    let render_cpu = || tiles::stitch_tiles(frame, options).map(|p| p.take());
+    if options.uses_cpu() {
+        return render_cpu();
+    }
+    let image_size = Size::new(frame.width, frame.height);
+    let Some(renderer) = WgpuRenderer::new_with_texture_rt(image_size).await else {
+        return match options.renderer {
+            MapRenderer::Auto => render_cpu(),
+            _ => Err(anyhow!("error creating renderer: no GPU adapter")),
+        };
+    };
+
+    #[cfg(target_arch = "wasm32")]
+    let osm_builder = || {
+        RasterTileLayerBuilder::new_rest(|index| {
+            format!("/tiles/{}/{}/{}.png", index.z, index.x, index.y)
+        })
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let osm_builder = RasterTileLayerBuilder::new_osm;
+
+    let center = Point2::new(frame.center.0, frame.center.1);
+    let map_view = MapView::new_projected(&center, frame.resolution).with_size(image_size.cast());
+    // No labels, the track is drawn over this later.
+    let (basemap, _) = basemap_layers(options, osm_builder, &map_view).await?;
+
+    let layers: Vec<Box<dyn Layer>> = vec![basemap];
+    let map = Map::new(map_view, layers, None::<Box<dyn Messenger>>);
+
+    renderer
+        .render(&map)
+        .map_err(|e| anyhow!("error rendering map: {e}"))?;
+
+    renderer
+        .get_image()
+        .await
+        .map_err(|e| anyhow!("error retrieving rendered bitmap: {e}"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_base_map(frame: &MapFrame, options: &MapOptions) -> Result<Vec<u8>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(render_base_map_async(frame, options))
+}
+
 fn simplify_linestring(raw: &LineString<f64>, max_points: usize) -> LineString<f64> {
     // Units are degrees; values correspond roughly to ~1–20 meters.
     let mut simplified = raw.clone();
@@ -391,5 +748,16 @@ mod tests {
         assert_eq!(simplified.0.last(), raw.0.last());
     }
 
-    // NOTE: no render test here (requires wgpu/GPU).
+    #[test]
+    fn parse_map_renderer() {
+        assert_eq!("CPU".parse::<MapRenderer>().unwrap(), MapRenderer::Cpu);
+        assert!("vulkan".parse::<MapRenderer>().is_err());
+        let options = MapOptions::default().with_renderer(MapRenderer::Cpu);
+        assert!(options.uses_cpu());
+        let vector = MapOptions::default()
+            .with_tiles("https://tiles.example.com/{z}/{x}/{y}.pbf".parse().unwrap());
+        assert!(!vector.uses_cpu());
+    }
+
+    // NOTE: no GPU render test here, see `tiles` for the CPU renderer.
 }
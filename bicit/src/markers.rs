@@ -0,0 +1,256 @@
+//! Markers drawn over the track: start and finish, distance ticks, waypoints, max speed
+//! and highest point.
+
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use anyhow::{Error, Result, anyhow, bail};
+use geo::{Distance, Geodesic};
+use geo_types::Point;
+use quick_xml::escape::escape;
+use tiny_skia::{Pixmap, Transform};
+
+use crate::gradient::TrackGradient;
+use crate::tiles::MapFrame;
+
+/// Which markers a map shows, e.g. from the `data-map-markers` attribute of `image_map`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkerOptions {
+    pub start: bool,
+    pub finish: bool,
+    /// Distance between ticks, in km, mi or nmi depending on the units.
+    pub distance: Option<f64>,
+    pub waypoints: bool,
+    pub max_speed: bool,
+    pub highest: bool,
+}
+
+impl MarkerOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == MarkerOptions::default()
+    }
+}
+
+impl FromStr for MarkerOptions {
+    type Err = Error;
+
+    /// Marker names separated by spaces or commas: `start`, `finish`, `distance` (every
+    /// 5 units, or `distance:N`), `waypoints`, `max-speed`, `highest` or `all`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = MarkerOptions::default();
+        for token in s.split([' ', ',']).filter(|t| !t.is_empty()) {
+            let (name, value) = token.split_once(':').unwrap_or((token, ""));
+            match name {
+                "start" => options.start = true,
+                "finish" => options.finish = true,
+                "distance" => {
+                    options.distance = Some(match value {
+                        "" => 5.0,
+                        v => v
+                            .parse()
+                            .ok()
+                            .filter(|n: &f64| *n > 0.0)
+                            .ok_or(anyhow!("invalid marker distance '{v}'"))?,
+                    })
+                }
+                "waypoints" => options.waypoints = true,
+                "max-speed" => options.max_speed = true,
+                "highest" => options.highest = true,
+                "all" => {
+                    options = MarkerOptions {
+                        start: true,
+                        finish: true,
+                        distance: options.distance.or(Some(5.0)),
+                        waypoints: true,
+                        max_speed: true,
+                        highest: true,
+                    }
+                }
+                _ => bail!(
+                    "unknown map marker '{name}', expected start, finish, distance, waypoints, \
+                     max-speed, highest or all"
+                ),
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Start,
+    Finish,
+    Distance,
+    Waypoint,
+    MaxSpeed,
+    Highest,
+}
+
+/// A marker at a WGS84 point, with an optional label.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapMarker {
+    pub kind: MarkerKind,
+    pub point: Point<f64>,
+    pub label: Option<String>,
+}
+
+/// What is drawn over a track map besides the track: the markers, and per-point colors
+/// replacing the track color.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapOverlay {
+    pub markers: Vec<MapMarker>,
+    pub gradient: Option<TrackGradient>,
+}
+
+/// Ticks every `interval` metres along `coords`, labelled `step`, `2 * step`, ...
+pub fn distance_markers(coords: &[Point<f64>], interval: f64, step: f64) -> Vec<MapMarker> {
+    let mut markers = vec![];
+    let mut total = 0.0;
+    let mut next = interval;
+    for w in coords.windows(2) {
+        let d = Geodesic.distance(w[0], w[1]);
+        // A long segment can hold several ticks.
+        while d > 0.0 && total + d >= next {
+            let t = (next - total) / d;
+            markers.push(MapMarker {
+                kind: MarkerKind::Distance,
+                point: Point::new(
+                    w[0].x() + (w[1].x() - w[0].x()) * t,
+                    w[0].y() + (w[1].y() - w[0].y()) * t,
+                ),
+                label: Some(format!("{}", step * (markers.len() + 1) as f64)),
+            });
+            next += interval;
+        }
+        total += d;
+    }
+    markers
+}
+
+/// SVG group with the markers in the pixels of `frame`.
+pub fn markers_svg(frame: &MapFrame, markers: &[MapMarker]) -> String {
+    let mut svg = String::from(
+        r#"<g id="markers" font-family="sans-serif" font-weight="bold" stroke-linejoin="round">"#,
+    );
+    for marker in markers {
+        let (x, y) = frame.to_pixel(marker.point);
+        let _ = match marker.kind {
+            MarkerKind::Start => write!(
+                svg,
+                r##"<circle cx="{x:.1}" cy="{y:.1}" r="9" fill="#22C55E" stroke="#FFFFFF" stroke-width="3"/>"##
+            ),
+            MarkerKind::Finish => write!(
+                svg,
+                r##"<circle cx="{x:.1}" cy="{y:.1}" r="9" fill="#111827" stroke="#FFFFFF" stroke-width="3"/><circle cx="{x:.1}" cy="{y:.1}" r="3" fill="#FFFFFF"/>"##
+            ),
+            MarkerKind::Distance => write!(
+                svg,
+                r##"<circle cx="{x:.1}" cy="{y:.1}" r="10" fill="#FFFFFF" stroke="#111827" stroke-width="2"/><text x="{x:.1}" y="{:.1}" font-size="10" text-anchor="middle" fill="#111827">{}</text>"##,
+                y + 3.5,
+                escape(marker.label.as_deref().unwrap_or_default())
+            ),
+            MarkerKind::Waypoint => write!(
+                svg,
+                r##"<path d="M{x:.1} {y:.1}l-7 -12a8 8 0 1 1 14 0z" fill="#2563EB" stroke="#FFFFFF" stroke-width="2"/><circle cx="{x:.1}" cy="{:.1}" r="3" fill="#FFFFFF"/>"##,
+                y - 16.0
+            ),
+            MarkerKind::MaxSpeed => write!(
+                svg,
+                r##"<circle cx="{x:.1}" cy="{y:.1}" r="7" fill="#F97316" stroke="#FFFFFF" stroke-width="2"/>"##
+            ),
+            MarkerKind::Highest => write!(
+                svg,
+                r##"<path d="M{x:.1} {:.1}l8 14h-16z" fill="#8B5CF6" stroke="#FFFFFF" stroke-width="2"/>"##,
+                y - 9.0
+            ),
+        };
+        if let Some(label) = &marker.label
+            && marker.kind != MarkerKind::Distance
+        {
+            let _ = write!(
+                svg,
+                r##"<text x="{:.1}" y="{:.1}" font-size="12" fill="#111827" stroke="#FFFFFF" stroke-width="3" paint-order="stroke">{}</text>"##,
+                x + 12.0,
+                y + 4.0,
+                escape(label.as_str())
+            );
+        }
+    }
+    svg.push_str("</g>");
+    svg
+}
+
+/// Draws the markers over a rendered map.
+pub fn draw_markers(pixmap: &mut Pixmap, frame: &MapFrame, markers: &[MapMarker]) -> Result<()> {
+    if markers.is_empty() {
+        return Ok(());
+    }
+    let svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">{}</svg>"#,
+        markers_svg(frame, markers),
+        w = frame.width,
+        h = frame.height,
+    );
+    let tree = crate::render::parse_svg_tree(&svg)?;
+    resvg::render(&tree, Transform::identity(), &mut pixmap.as_mut());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_marker_options() {
+        let options: MarkerOptions = "start, finish distance:2.5 highest".parse().unwrap();
+        assert!(options.start && options.finish && options.highest);
+        assert!(!options.waypoints && !options.max_speed);
+        assert_eq!(options.distance, Some(2.5));
+        assert_eq!("all".parse::<MarkerOptions>().unwrap().distance, Some(5.0));
+        assert!("".parse::<MarkerOptions>().unwrap().is_empty());
+        assert!("distance:0".parse::<MarkerOptions>().is_err());
+        assert!("flags".parse::<MarkerOptions>().is_err());
+    }
+
+    #[test]
+    fn distance_ticks_along_the_track() {
+        // About 12.2km northwards, with one long segment in the middle.
+        let mut coords: Vec<Point<f64>> = (0..=40)
+            .map(|i| Point::new(10.0, 45.0 + i as f64 * 0.001))
+            .collect();
+        coords.extend((0..=60).map(|i| Point::new(10.0, 45.05 + i as f64 * 0.001)));
+        let markers = distance_markers(&coords, 2000.0, 2.0);
+        let labels: Vec<_> = markers.iter().filter_map(|m| m.label.as_deref()).collect();
+        assert_eq!(labels, ["2", "4", "6", "8", "10", "12"]);
+        for (i, m) in markers.iter().enumerate() {
+            let d = Geodesic.distance(coords[0], m.point);
+            assert!((d - 2000.0 * (i + 1) as f64).abs() < 1.0, "tick {i} at {d}");
+        }
+    }
+
+    #[test]
+    fn draws_markers() {
+        let track = [Point::new(11.0, 46.0), Point::new(11.1, 46.05)];
+        let frame = MapFrame::for_tracks(&[&track], 200, 100).unwrap();
+        let markers = [
+            MapMarker {
+                kind: MarkerKind::Start,
+                point: track[0],
+                label: None,
+            },
+            MapMarker {
+                kind: MarkerKind::Waypoint,
+                point: track[1],
+                label: Some("Bar & Café".to_string()),
+            },
+        ];
+        let svg = markers_svg(&frame, &markers);
+        assert!(svg.contains("Bar &amp; Café"));
+
+        let mut pixmap = Pixmap::new(200, 100).unwrap();
+        draw_markers(&mut pixmap, &frame, &markers).unwrap();
+        let (x, y) = frame.to_pixel(track[0]);
+        let start = pixmap.pixel(x as u32, y as u32).unwrap();
+        assert_eq!((start.red(), start.green(), start.blue()), (34, 197, 94));
+    }
+}
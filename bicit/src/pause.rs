@@ -0,0 +1,187 @@
+//! Auto-pause model used to split elapsed time into moving and stopped time.
+
+/// Thresholds for detecting stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PauseOptions {
+    /// Below this speed (km/h) the athlete is considered stopped.
+    pub min_speed: f64,
+    /// Slow stretches shorter than this (seconds) still count as moving, e.g. a tight
+    /// corner or a slow switchback.
+    pub min_stop_duration: f64,
+    /// Intervals between two samples longer than this (seconds) are a recording gap
+    /// (device paused or smart recording), counted as a stop unless the distance covered
+    /// during the gap shows the athlete kept moving.
+    pub max_gap: f64,
+}
+
+impl Default for PauseOptions {
+    fn default() -> Self {
+        PauseOptions::cycling()
+    }
+}
+
+impl PauseOptions {
+    pub fn cycling() -> Self {
+        PauseOptions {
+            min_speed: 3.0,
+            min_stop_duration: 5.0,
+            max_gap: 30.0,
+        }
+    }
+
+    pub fn mountain_biking() -> Self {
+        PauseOptions {
+            min_speed: 1.5,
+            min_stop_duration: 10.0,
+            max_gap: 30.0,
+        }
+    }
+
+    pub fn running() -> Self {
+        PauseOptions {
+            min_speed: 2.0,
+            min_stop_duration: 5.0,
+            max_gap: 30.0,
+        }
+    }
+
+    pub fn hiking() -> Self {
+        PauseOptions {
+            min_speed: 0.8,
+            min_stop_duration: 30.0,
+            max_gap: 60.0,
+        }
+    }
+
+    /// Pick thresholds from a free-form sport name (GPX `<type>`, FIT/TCX sport).
+    pub fn for_sport(sport: Option<&str>) -> Self {
+        let sport = sport.unwrap_or_default().to_ascii_lowercase();
+        if sport.contains("mountain") || sport.contains("mtb") {
+            PauseOptions::mountain_biking()
+        } else if sport.contains("run") {
+            PauseOptions::running()
+        } else if sport.contains("walk") || sport.contains("hik") {
+            PauseOptions::hiking()
+        } else {
+            PauseOptions::cycling()
+        }
+    }
+}
+
+/// Classifies consecutive intervals as moving or stopped.
+#[derive(Debug, Default)]
+pub struct PauseDetector {
+    options: PauseOptions,
+    moving: f64,
+    stopped: f64,
+    pauses: usize,
+    // Current run of slow intervals, not yet classified.
+    run: f64,
+    run_has_gap: bool,
+}
+
+impl PauseDetector {
+    pub fn new(options: PauseOptions) -> Self {
+        PauseDetector {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Add an interval of `dt` seconds during which `distance` metres were covered.
+    pub fn push(&mut self, dt: f64, distance: f64) {
+        if dt <= 0.0 {
+            return;
+        }
+
+        let speed = distance / dt * 3.6;
+        if speed >= self.options.min_speed {
+            self.close_run();
+            self.moving += dt;
+            return;
+        }
+
+        self.run += dt;
+        if dt > self.options.max_gap {
+            self.run_has_gap = true;
+        }
+    }
+
+    /// Add the time between two recorded segments: always a stop.
+    pub fn push_gap(&mut self, dt: f64) {
+        if dt > 0.0 {
+            self.run += dt;
+            self.run_has_gap = true;
+        }
+    }
+
+    fn close_run(&mut self) {
+        if self.run > 0.0 {
+            if self.run_has_gap || self.run >= self.options.min_stop_duration {
+                self.stopped += self.run;
+                self.pauses += 1;
+            } else {
+                self.moving += self.run;
+            }
+        }
+        self.run = 0.0;
+        self.run_has_gap = false;
+    }
+
+    /// `(moving seconds, stopped seconds, number of pauses)`.
+    pub fn finish(mut self) -> (f64, f64, usize) {
+        self.close_run();
+        (self.moving, self.stopped, self.pauses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_slowdowns_count_as_moving() {
+        let mut p = PauseDetector::new(PauseOptions::cycling());
+        // 60s at 18 km/h, 3s almost still, 60s at 18 km/h.
+        (0..60).for_each(|_| p.push(1.0, 5.0));
+        (0..3).for_each(|_| p.push(1.0, 0.1));
+        (0..60).for_each(|_| p.push(1.0, 5.0));
+        assert_eq!(p.finish(), (123.0, 0.0, 0));
+    }
+
+    #[test]
+    fn traffic_lights_and_gaps_are_stops() {
+        let mut p = PauseDetector::new(PauseOptions::cycling());
+        (0..60).for_each(|_| p.push(1.0, 5.0));
+        // Red light.
+        (0..40).for_each(|_| p.push(1.0, 0.2));
+        (0..60).for_each(|_| p.push(1.0, 5.0));
+        // Device auto-paused: a single long interval with no movement.
+        p.push(300.0, 3.0);
+        (0..60).for_each(|_| p.push(1.0, 5.0));
+        // New segment after a coffee stop.
+        p.push_gap(900.0);
+        (0..60).for_each(|_| p.push(1.0, 5.0));
+        assert_eq!(p.finish(), (240.0, 1240.0, 3));
+    }
+
+    #[test]
+    fn sparse_recording_is_not_a_pause() {
+        let mut p = PauseDetector::new(PauseOptions::cycling());
+        p.push(60.0, 400.0);
+        assert_eq!(p.finish(), (60.0, 0.0, 0));
+    }
+
+    #[test]
+    fn thresholds_by_sport() {
+        assert_eq!(
+            PauseOptions::for_sport(Some("mountain_biking")),
+            PauseOptions::mountain_biking()
+        );
+        assert_eq!(
+            PauseOptions::for_sport(Some("Running")),
+            PauseOptions::running()
+        );
+        assert_eq!(PauseOptions::for_sport(None), PauseOptions::cycling());
+    }
+}
@@ -0,0 +1,364 @@
+//! Elevation profile drawn as SVG for the `group_elevation_profile` template group: a
+//! filled area, bands colored by grade, axes and the lowest and highest points.
+
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use anyhow::{Error, bail};
+use quick_xml::escape::escape;
+
+use crate::locale::Locale;
+use crate::units::Units;
+
+// Grades are measured over about this distance.
+const BAND_LENGTH: f64 = 100.0;
+// Band colors from the grade (in %) they start at; flatter and downhill parts keep the
+// area fill.
+const GRADE_BANDS: [(f64, &str); 4] = [
+    (3.0, "#FACC15"),
+    (6.0, "#F97316"),
+    (9.0, "#EF4444"),
+    (12.0, "#991B1B"),
+];
+const DEFAULT_LINE_COLOR: &str = "#FF2D55";
+
+/// What a generated profile shows, e.g. from the `data-options` attribute of its group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileOptions {
+    /// Area under the curve.
+    pub fill: bool,
+    /// Colors by grade under the curve.
+    pub bands: bool,
+    /// Distance and elevation ticks with labels.
+    pub axes: bool,
+    /// Lowest and highest points with their elevation.
+    pub min_max: bool,
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        ProfileOptions {
+            fill: true,
+            bands: true,
+            axes: true,
+            min_max: true,
+        }
+    }
+}
+
+impl FromStr for ProfileOptions {
+    type Err = Error;
+
+    /// Parts separated by spaces or commas: `fill`, `bands`, `axes`, `min-max` or `all`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut options = ProfileOptions {
+            fill: false,
+            bands: false,
+            axes: false,
+            min_max: false,
+        };
+        for token in s.split([' ', ',']).filter(|t| !t.is_empty()) {
+            match token {
+                "fill" => options.fill = true,
+                "bands" => options.bands = true,
+                "axes" => options.axes = true,
+                "min-max" => options.min_max = true,
+                "all" => options = ProfileOptions::default(),
+                _ => bail!(
+                    "unknown profile option '{token}', expected fill, bands, axes, min-max or all"
+                ),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Colors of a profile: the line, and the area under it (the line color, translucent,
+/// when `None`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileColors {
+    pub line: Option<String>,
+    pub fill: Option<String>,
+}
+
+/// Step of about `span / count` rounded to 1, 2 or 5 times a power of ten.
+fn nice_step(span: f64, count: f64) -> f64 {
+    let raw = (span / count).max(f64::EPSILON);
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// SVG elements of the profile of `points` (distance and elevation in metres) in a
+/// `width` x `height` box from (0, 0). Labels are in `units`; the text uses the fill of
+/// the parent.
+pub fn profile_svg(
+    points: &[(f64, f64)],
+    width: f64,
+    height: f64,
+    options: &ProfileOptions,
+    colors: &ProfileColors,
+    units: Units,
+    locale: Locale,
+) -> String {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return String::new();
+    };
+    let total = (last.0 - first.0).max(f64::EPSILON);
+    let line = escape(colors.line.as_deref().unwrap_or(DEFAULT_LINE_COLOR));
+    let font_size = (height / 12.0).min(width / 30.0);
+
+    // Elevation axis from and to whole ticks in the elevation unit.
+    let (e_min, e_max) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p.1), hi.max(p.1))
+    });
+    let e_unit = units.elevation_unit();
+    let e_step = nice_step((e_max - e_min).max(50.0) / e_unit, 4.0) * e_unit;
+    let axis_min = (e_min / e_step).floor() * e_step;
+    let axis_max = ((e_max / e_step).ceil() * e_step).max(axis_min + e_step);
+
+    let left = if options.axes { font_size * 3.2 } else { 0.0 };
+    let top = if options.min_max {
+        font_size * 1.5
+    } else {
+        0.0
+    };
+    let bottom = height - if options.axes { font_size * 1.6 } else { 0.0 };
+    let x = |d: f64| left + (d - first.0) / total * (width - left);
+    let y = |e: f64| bottom - (e - axis_min) / (axis_max - axis_min) * (bottom - top);
+    let outline = |points: &[(f64, f64)]| {
+        let mut d = String::new();
+        for (i, p) in points.iter().enumerate() {
+            let _ = write!(
+                d,
+                "{}{:.2},{:.2} ",
+                if i == 0 { 'M' } else { 'L' },
+                x(p.0),
+                y(p.1)
+            );
+        }
+        d
+    };
+    // Closed area between `points` and the bottom of the axis.
+    let area = |points: &[(f64, f64)]| {
+        let (a, b) = (points[0].0, points[points.len() - 1].0);
+        format!(
+            "{}L{:.2},{bottom:.2} L{:.2},{bottom:.2} Z",
+            outline(points),
+            x(b),
+            x(a)
+        )
+    };
+
+    let mut svg = String::new();
+    if options.axes {
+        let _ = write!(
+            svg,
+            r#"<g stroke="{line}" stroke-opacity="0.25" stroke-width="{:.2}">"#,
+            font_size / 12.0
+        );
+        let mut e = axis_min;
+        while e <= axis_max + e_step / 2.0 {
+            let _ = write!(
+                svg,
+                r#"<line x1="{left:.2}" y1="{0:.2}" x2="{width:.2}" y2="{0:.2}"/>"#,
+                y(e)
+            );
+            e += e_step;
+        }
+        svg.push_str("</g>");
+    }
+    if options.fill {
+        let _ = match &colors.fill {
+            Some(fill) => write!(
+                svg,
+                r#"<path d="{}" fill="{}"/>"#,
+                area(points),
+                escape(fill.as_str())
+            ),
+            None => write!(
+                svg,
+                r#"<path d="{}" fill="{line}" fill-opacity="0.3"/>"#,
+                area(points)
+            ),
+        };
+    }
+    if options.bands {
+        for (band, part) in grade_bands(points) {
+            let _ = write!(
+                svg,
+                r#"<path d="{}" fill="{}" fill-opacity="0.85"/>"#,
+                area(&part),
+                GRADE_BANDS[band].1
+            );
+        }
+    }
+    let _ = write!(
+        svg,
+        r#"<path d="{}" fill="none" stroke="{line}" stroke-width="{:.2}" stroke-linejoin="round" stroke-linecap="round"/>"#,
+        outline(points),
+        (height / 60.0).max(1.0)
+    );
+
+    if options.axes {
+        let _ = write!(svg, r#"<g font-size="{font_size:.2}">"#);
+        let mut e = axis_min;
+        while e <= axis_max + e_step / 2.0 {
+            let _ = write!(
+                svg,
+                r#"<text x="{:.2}" y="{:.2}" text-anchor="end">{}</text>"#,
+                left - font_size * 0.4,
+                y(e) + font_size * 0.35,
+                units.elevation(e)
+            );
+            e += e_step;
+        }
+        let d_unit = units.distance_unit();
+        let d_step = nice_step(total / d_unit, 5.0) * d_unit;
+        let mut d = 0.0;
+        while d <= total + d_step * 0.01 {
+            let label = if d_step < d_unit {
+                units.length(d, locale)
+            } else {
+                units.distance(d)
+            };
+            let anchor = if d == 0.0 {
+                "start"
+            } else if x(first.0 + d) > width - font_size * 2.0 {
+                "end"
+            } else {
+                "middle"
+            };
+            let _ = write!(
+                svg,
+                r#"<text x="{:.2}" y="{height:.2}" text-anchor="{anchor}">{}</text>"#,
+                x(first.0 + d),
+                label
+            );
+            d += d_step;
+        }
+        svg.push_str("</g>");
+    }
+
+    if options.min_max {
+        let lowest = points.iter().min_by(|a, b| a.1.total_cmp(&b.1));
+        let highest = points.iter().max_by(|a, b| a.1.total_cmp(&b.1));
+        let _ = write!(
+            svg,
+            r#"<g font-size="{font_size:.2}" font-weight="bold" text-anchor="middle">"#
+        );
+        for p in lowest.into_iter().chain(highest) {
+            let (px, py) = (x(p.0), y(p.1));
+            let _ = write!(
+                svg,
+                r##"<circle cx="{px:.2}" cy="{py:.2}" r="{:.2}" fill="{line}" stroke="#FFFFFF" stroke-width="{:.2}"/><text x="{:.2}" y="{:.2}">{}</text>"##,
+                font_size * 0.3,
+                font_size * 0.1,
+                px.clamp(left + font_size * 1.5, width - font_size * 1.5),
+                py - font_size * 0.6,
+                units.elevation(p.1)
+            );
+        }
+        svg.push_str("</g>");
+    }
+    svg
+}
+
+/// Parts of the profile steeper than the first band, with the index of their band.
+/// Consecutive parts share their end point.
+fn grade_bands(points: &[(f64, f64)]) -> Vec<(usize, Vec<(f64, f64)>)> {
+    let mut bands: Vec<(usize, Vec<(f64, f64)>)> = vec![];
+    let mut current: Option<(usize, Vec<(f64, f64)>)> = None;
+    let mut i = 0;
+    while i + 1 < points.len() {
+        let j = (i + 1..points.len())
+            .find(|j| points[*j].0 - points[i].0 >= BAND_LENGTH)
+            .unwrap_or(points.len() - 1);
+        let length = (points[j].0 - points[i].0).max(f64::EPSILON);
+        let grade = (points[j].1 - points[i].1) / length * 100.0;
+        let band = GRADE_BANDS.iter().rposition(|(from, _)| grade >= *from);
+        match (&mut current, band) {
+            (Some((b, part)), Some(band)) if *b == band => part.extend(&points[i + 1..=j]),
+            (_, band) => {
+                bands.extend(current.take());
+                current = band.map(|band| (band, points[i..=j].to_vec()));
+            }
+        }
+        i = j;
+    }
+    bands.extend(current);
+    bands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Flat for 1km, 5% for 1km, 10% for 500m, then down to the start.
+    fn points() -> Vec<(f64, f64)> {
+        let mut points = vec![];
+        for i in 0..=10 {
+            points.push((i as f64 * 100.0, 200.0));
+        }
+        for i in 1..=10 {
+            points.push((1000.0 + i as f64 * 100.0, 200.0 + i as f64 * 5.0));
+        }
+        for i in 1..=5 {
+            points.push((2000.0 + i as f64 * 100.0, 250.0 + i as f64 * 10.0));
+        }
+        points.push((3500.0, 200.0));
+        points
+    }
+
+    #[test]
+    fn bands_by_grade() {
+        let bands = grade_bands(&points());
+        assert_eq!(bands.len(), 2);
+        assert_eq!(bands[0].0, 0);
+        assert_eq!(bands[0].1.first(), Some(&(1000.0, 200.0)));
+        assert_eq!(bands[0].1.last(), Some(&(2000.0, 250.0)));
+        assert_eq!(bands[1].0, 2);
+        assert_eq!(bands[1].1.len(), 6);
+    }
+
+    #[test]
+    fn profile_with_axes_and_extremes() {
+        let colors = ProfileColors {
+            line: Some("#22C55E".to_string()),
+            fill: None,
+        };
+        let svg = profile_svg(
+            &points(),
+            600.0,
+            240.0,
+            &ProfileOptions::default(),
+            &colors,
+            Units::Metric,
+            Locale::En,
+        );
+        assert!(svg.contains(r##"fill="#22C55E" fill-opacity="0.3""##));
+        assert!(svg.contains(r##"fill="#EF4444""##) && !svg.contains(r##"fill="#991B1B""##));
+        // Ticks every 50m from 200m to 300m and every km along the track.
+        for label in [">250m<", ">0km<", ">3km<", ">300m</text></g>"] {
+            assert!(svg.contains(label), "{label} missing");
+        }
+        assert!(!svg.contains(">350m<") && !svg.contains(">4km<"));
+
+        let options: ProfileOptions = "fill, min-max".parse().unwrap();
+        assert!(options.fill && options.min_max && !options.axes && !options.bands);
+        let svg = profile_svg(
+            &points(),
+            600.0,
+            240.0,
+            &options,
+            &colors,
+            Units::Imperial,
+            Locale::En,
+        );
+        assert!(svg.contains(">984ft<") && !svg.contains("km<"));
+        assert!("grid".parse::<ProfileOptions>().is_err());
+    }
+}
@@ -1,7 +1,179 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use image::codecs::jpeg::JpegEncoder;
 use image::codecs::png::PngEncoder;
-use image::{ColorType, ImageEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::{ColorType, ExtendedColorType, ImageEncoder, RgbaImage};
+
+use anyhow::{Error, Result, anyhow};
+
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// Output file format of a rendered template.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    /// Quality from 1 to 100.
+    Jpeg { quality: u8 },
+    /// Lossless WebP, usually smaller than PNG.
+    Webp,
+    /// Vector PDF, for printing.
+    Pdf,
+    /// The filled-in template itself.
+    Svg,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 5] = [
+        OutputFormat::Png,
+        OutputFormat::Jpeg {
+            quality: DEFAULT_JPEG_QUALITY,
+        },
+        OutputFormat::Webp,
+        OutputFormat::Pdf,
+        OutputFormat::Svg,
+    ];
+
+    /// Format from a file extension like `png` or `JPG`.
+    pub fn from_extension(ext: &str) -> Option<OutputFormat> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY,
+            }),
+            "webp" => Some(OutputFormat::Webp),
+            "pdf" => Some(OutputFormat::Pdf),
+            "svg" => Some(OutputFormat::Svg),
+            _ => None,
+        }
+    }
+
+    /// Format from the extension of `path`, if it has a known one.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<OutputFormat> {
+        Self::from_extension(path.as_ref().extension()?.to_str()?)
+    }
+
+    /// Set the JPEG quality, other formats are unchanged.
+    pub fn with_quality(self, quality: u8) -> Self {
+        match self {
+            OutputFormat::Jpeg { .. } => OutputFormat::Jpeg { quality },
+            f => f,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg { .. } => "jpg",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Svg => "svg",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg { .. } => "image/jpeg",
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Pdf => "application/pdf",
+            OutputFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    /// Name for file dialogs and format pickers.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "PNG",
+            OutputFormat::Jpeg { .. } => "JPEG",
+            OutputFormat::Webp => "WebP",
+            OutputFormat::Pdf => "PDF",
+            OutputFormat::Svg => "SVG",
+        }
+    }
+}
+
+/// Size of a raster export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportSize {
+    /// Multiple of the template size, e.g. 2 for retina screens.
+    Scale(f32),
+    /// Target width in px, the height follows the template aspect ratio.
+    Width(u32),
+    /// Target height in px, the width follows the template aspect ratio.
+    Height(u32),
+    /// Exact image size, the template is fitted and centered.
+    Exact { width: u32, height: u32 },
+}
+
+impl Default for ExportSize {
+    fn default() -> Self {
+        ExportSize::Scale(1.0)
+    }
+}
+
+impl ExportSize {
+    /// Scale factor for a template of `width` x `height` px.
+    pub fn scale(&self, width: f32, height: f32) -> f32 {
+        let scale = match *self {
+            ExportSize::Scale(scale) => scale,
+            ExportSize::Width(w) => w as f32 / width,
+            ExportSize::Height(h) => h as f32 / height,
+            ExportSize::Exact {
+                width: w,
+                height: h,
+            } => (w as f32 / width).min(h as f32 / height),
+        };
+        if scale.is_finite() && scale > 0.0 {
+            scale
+        } else {
+            1.0
+        }
+    }
+
+    /// Size of the exported image for a template of `width` x `height` px.
+    pub fn pixels(&self, width: f32, height: f32) -> (u32, u32) {
+        match *self {
+            ExportSize::Exact { width, height } => (width.max(1), height.max(1)),
+            _ => {
+                let scale = self.scale(width, height);
+                (
+                    (width * scale).round().max(1.0) as u32,
+                    (height * scale).round().max(1.0) as u32,
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExportSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportSize::Scale(scale) => write!(f, "{scale}x"),
+            ExportSize::Width(w) => write!(f, "{w}px wide"),
+            ExportSize::Height(h) => write!(f, "{h}px high"),
+            ExportSize::Exact { width, height } => write!(f, "{width}x{height}px"),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
 
-use anyhow::{Result, anyhow};
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_extension(s)
+            .ok_or_else(|| anyhow!("unknown format '{s}', expected png, jpg, webp, pdf or svg"))
+    }
+}
 
 pub fn parse_svg_tree(svg_content: &str) -> Result<usvg::Tree> {
     let mut fontdb = usvg::fontdb::Database::new();
@@ -28,23 +200,174 @@ pub fn parse_svg_tree(svg_content: &str) -> Result<usvg::Tree> {
     usvg::Tree::from_str(svg_content, &options).map_err(|e| anyhow!("Failed to parse SVG: {e}"))
 }
 
+fn render_svg_to_pixmap(tree: &usvg::Tree, size: ExportSize) -> Result<tiny_skia::Pixmap> {
+    let (svg_w, svg_h) = (tree.size().width(), tree.size().height());
+    let scale = size.scale(svg_w, svg_h);
+    let (width, height) = size.pixels(svg_w, svg_h);
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| anyhow!("Failed to create pixmap {width}x{height}"))?;
+
+    // Only an exact size can leave a border around the template.
+    let dx = (width as f32 - svg_w * scale) / 2.0;
+    let dy = (height as f32 - svg_h * scale) / 2.0;
+    let transform = tiny_skia::Transform::from_row(scale, 0.0, 0.0, scale, dx, dy);
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    Ok(pixmap)
+}
+
 pub fn render_svg_to_png_bytes(svg_content: &str, scale: f32) -> Result<Vec<u8>> {
+    render_svg(svg_content, ExportSize::Scale(scale), OutputFormat::Png)
+}
+
+/// Straight (not premultiplied) RGBA bytes of `pixmap`, as the image encoders expect.
+fn demultiplied(pixmap: &tiny_skia::Pixmap) -> Vec<u8> {
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect()
+}
+
+/// Render a filled-in template to straight (not premultiplied) RGBA pixels.
+pub fn render_svg_to_rgba(svg_content: &str, size: ExportSize) -> Result<RgbaImage> {
+    let tree = parse_svg_tree(svg_content)?;
+    let pixmap = render_svg_to_pixmap(&tree, size)?;
+    RgbaImage::from_raw(pixmap.width(), pixmap.height(), demultiplied(&pixmap))
+        .ok_or_else(|| anyhow!("Failed to create image buffer"))
+}
+
+/// Render a filled-in template to `format`. `size` only applies to raster formats.
+pub fn render_svg(svg_content: &str, size: ExportSize, format: OutputFormat) -> Result<Vec<u8>> {
+    if format == OutputFormat::Svg {
+        return Ok(svg_content.as_bytes().to_vec());
+    }
+
     let tree = parse_svg_tree(svg_content)?;
+    if format == OutputFormat::Pdf {
+        return svg2pdf::to_pdf(
+            &tree,
+            svg2pdf::ConversionOptions::default(),
+            svg2pdf::PageOptions::default(),
+        )
+        .map_err(|e| anyhow!("Failed to convert to PDF: {e}"));
+    }
 
-    let original_size = tree.size();
-    let width = (original_size.width() * scale).round().max(1.0) as u32;
-    let height = (original_size.height() * scale).round().max(1.0) as u32;
+    let pixmap = render_svg_to_pixmap(&tree, size)?;
+    let (width, height) = (pixmap.width(), pixmap.height());
 
-    let mut pixmap = tiny_skia::Pixmap::new(width, height)
-        .ok_or_else(|| anyhow!("Failed to create pixmap {width}x{height}"))?;
+    let mut out = Vec::new();
+    match format {
+        OutputFormat::Jpeg { quality } => {
+            // No alpha in JPEG: flatten the premultiplied pixels on white.
+            let rgb: Vec<u8> = pixmap
+                .data()
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let bg = 255 - p[3];
+                    [p[0] + bg, p[1] + bg, p[2] + bg]
+                })
+                .collect();
+            JpegEncoder::new_with_quality(&mut out, quality.clamp(1, 100))
+                .write_image(&rgb, width, height, ExtendedColorType::Rgb8)
+                .map_err(|e| anyhow!("Failed to encode JPEG: {e}"))?;
+        }
+        OutputFormat::Webp => {
+            WebPEncoder::new_lossless(&mut out)
+                .write_image(
+                    &demultiplied(&pixmap),
+                    width,
+                    height,
+                    ExtendedColorType::Rgba8,
+                )
+                .map_err(|e| anyhow!("Failed to encode WebP: {e}"))?;
+        }
+        _ => {
+            PngEncoder::new(&mut out)
+                .write_image(
+                    &demultiplied(&pixmap),
+                    width,
+                    height,
+                    ColorType::Rgba8.into(),
+                )
+                .map_err(|e| anyhow!("Failed to encode PNG: {e}"))?;
+        }
+    }
+
+    Ok(out)
+}
 
-    let transform = tiny_skia::Transform::from_scale(scale, scale);
-    resvg::render(&tree, transform, &mut pixmap.as_mut());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut png = Vec::new();
-    PngEncoder::new(&mut png)
-        .write_image(pixmap.data(), width, height, ColorType::Rgba8.into())
-        .map_err(|e| anyhow!("Failed to encode PNG: {e}"))?;
+    const SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+        <rect x="0" y="0" width="10" height="10" fill="#ff0000"/>
+    </svg>"##;
 
-    Ok(png)
+    #[test]
+    fn format_from_path() {
+        assert_eq!(
+            OutputFormat::from_path("ride.JPEG"),
+            Some(OutputFormat::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY
+            })
+        );
+        assert_eq!(
+            OutputFormat::from_path("out/ride.webp"),
+            Some(OutputFormat::Webp)
+        );
+        assert_eq!(OutputFormat::from_path("ride"), None);
+        assert!("gif".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn renders_each_format() {
+        let magic: [(OutputFormat, &[u8]); 4] = [
+            (OutputFormat::Png, b"\x89PNG"),
+            (OutputFormat::Jpeg { quality: 80 }, b"\xff\xd8\xff"),
+            (OutputFormat::Webp, b"RIFF"),
+            (OutputFormat::Svg, b"<svg"),
+        ];
+        for (format, magic) in magic {
+            let data = render_svg(SVG, ExportSize::Scale(2.0), format).unwrap();
+            assert!(data.starts_with(magic), "{format}");
+        }
+    }
+
+    #[test]
+    fn export_sizes() {
+        assert_eq!(ExportSize::Scale(2.0).pixels(20.0, 10.0), (40, 20));
+        assert_eq!(ExportSize::Width(1080).pixels(20.0, 10.0), (1080, 540));
+        assert_eq!(ExportSize::Height(50).pixels(20.0, 10.0), (100, 50));
+        let exact = ExportSize::Exact {
+            width: 100,
+            height: 100,
+        };
+        assert_eq!(exact.pixels(20.0, 10.0), (100, 100));
+        assert_eq!(exact.scale(20.0, 10.0), 5.0);
+
+        let png = render_svg(SVG, ExportSize::Width(40), OutputFormat::Png).unwrap();
+        let img = image::load_from_memory(&png).unwrap();
+        assert_eq!((img.width(), img.height()), (40, 20));
+    }
+
+    #[test]
+    fn transparent_pixels_keep_their_color() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="4" height="4">
+            <rect width="4" height="4" fill="#ff8000" fill-opacity="0.5"/>
+        </svg>"##;
+        for format in [OutputFormat::Png, OutputFormat::Webp] {
+            let data = render_svg(svg, ExportSize::Scale(1.0), format).unwrap();
+            let img = image::load_from_memory(&data).unwrap().to_rgba8();
+            let p = img.get_pixel(1, 1).0;
+            assert_eq!(p[0], 255, "{format}");
+            assert!(p[1].abs_diff(128) <= 1, "{format}: {p:?}");
+            assert!(p[3].abs_diff(128) <= 1, "{format}: {p:?}");
+        }
+    }
 }
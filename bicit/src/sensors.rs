@@ -0,0 +1,279 @@
+use anyhow::{Result, anyhow};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+/// Optional sensor channels recorded together with a track point.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Sensors {
+    pub hr: Option<f64>,
+    pub cadence: Option<f64>,
+    pub power: Option<f64>,
+    pub temperature: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Channel {
+    Hr,
+    Cadence,
+    Power,
+    Temperature,
+}
+
+impl Channel {
+    fn from_local_name(name: &[u8]) -> Option<Channel> {
+        match name {
+            b"hr" => Some(Channel::Hr),
+            b"cad" => Some(Channel::Cadence),
+            b"power" | b"PowerInWatts" => Some(Channel::Power),
+            b"atemp" => Some(Channel::Temperature),
+            _ => None,
+        }
+    }
+}
+
+/// Parse Garmin `TrackPointExtension` values (hr, cad, atemp, power) from GPX data.
+///
+/// Returns one entry per `<trkpt>` in document order, so the result lines up with the
+/// points of `gpx::Gpx::tracks` -> `segments` -> `points`.
+pub fn parse_gpx_extensions(xml: &[u8]) -> Result<Vec<Sensors>> {
+    let mut reader = Reader::from_reader(xml);
+    reader.config_mut().trim_text_start = true;
+    reader.config_mut().trim_text_end = true;
+
+    let mut out: Vec<Sensors> = vec![];
+    let mut in_trkpt = false;
+    let mut channel: Option<Channel> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => match e.local_name().as_ref() {
+                b"trkpt" => {
+                    in_trkpt = true;
+                    out.push(Sensors::default());
+                }
+                name if in_trkpt => channel = Channel::from_local_name(name),
+                _ => {}
+            },
+            Ok(Event::Empty(e)) if e.local_name().as_ref() == b"trkpt" => {
+                out.push(Sensors::default());
+            }
+            Ok(Event::End(e)) => {
+                if e.local_name().as_ref() == b"trkpt" {
+                    in_trkpt = false;
+                }
+                channel = None;
+            }
+            Ok(Event::Text(e)) => {
+                if let (Some(ch), Some(s)) = (channel, out.last_mut())
+                    && let Ok(v) = std::str::from_utf8(&e).unwrap_or("").trim().parse::<f64>()
+                {
+                    match ch {
+                        Channel::Hr => s.hr = Some(v),
+                        Channel::Cadence => s.cadence = Some(v),
+                        Channel::Power => s.power = Some(v),
+                        Channel::Temperature => s.temperature = Some(v),
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                return Err(anyhow!(
+                    "error parsing GPX extensions at position {}: {e}",
+                    reader.buffer_position()
+                ));
+            }
+        }
+        buf.clear();
+    }
+
+    Ok(out)
+}
+
+/// Summary of the sensor channels of a whole activity. Every value is `None` when the
+/// channel was never recorded.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SensorSummary {
+    pub hr_avg: Option<f64>,
+    pub hr_max: Option<f64>,
+    pub cadence_avg: Option<f64>,
+    pub cadence_max: Option<f64>,
+    pub power_avg: Option<f64>,
+    pub power_max: Option<f64>,
+    pub power_np: Option<f64>,
+    pub temp_avg: Option<f64>,
+    pub temp_min: Option<f64>,
+    pub temp_max: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct ChannelStats {
+    sum: f64,
+    count: usize,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl ChannelStats {
+    fn push(&mut self, v: f64) {
+        self.sum += v;
+        self.count += 1;
+        self.min = Some(self.min.map_or(v, |m| m.min(v)));
+        self.max = Some(self.max.map_or(v, |m| m.max(v)));
+    }
+
+    fn avg(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Accumulates sensor values point by point and produces a [`SensorSummary`].
+#[derive(Debug, Default)]
+pub struct SensorAccumulator {
+    hr: ChannelStats,
+    cadence: ChannelStats,
+    power: ChannelStats,
+    temperature: ChannelStats,
+    // (unix seconds, watts), used for normalized power.
+    power_series: Vec<(i64, f64)>,
+}
+
+impl SensorAccumulator {
+    pub fn push(&mut self, time: Option<i64>, s: &Sensors) {
+        if let Some(v) = s.hr {
+            self.hr.push(v);
+        }
+        // Like most head units, average cadence ignores zeros (coasting).
+        if let Some(v) = s.cadence
+            && v > 0.0
+        {
+            self.cadence.push(v);
+        }
+        if let Some(v) = s.power {
+            self.power.push(v);
+            if let Some(t) = time {
+                self.power_series.push((t, v));
+            }
+        }
+        if let Some(v) = s.temperature {
+            self.temperature.push(v);
+        }
+    }
+
+    pub fn finish(self) -> SensorSummary {
+        SensorSummary {
+            hr_avg: self.hr.avg(),
+            hr_max: self.hr.max,
+            cadence_avg: self.cadence.avg(),
+            cadence_max: self.cadence.max,
+            power_avg: self.power.avg(),
+            power_max: self.power.max,
+            power_np: normalized_power(&self.power_series),
+            temp_avg: self.temperature.avg(),
+            temp_min: self.temperature.min,
+            temp_max: self.temperature.max,
+        }
+    }
+}
+
+// Gaps longer than this are treated as a recording pause and not filled.
+const NP_MAX_FILL_SECONDS: i64 = 5;
+const NP_WINDOW_SECONDS: usize = 30;
+
+/// Normalized power: 4th root of the mean of the 4th power of the 30s rolling average,
+/// computed on a 1Hz resampling of `samples` (`(unix seconds, watts)`).
+pub fn normalized_power(samples: &[(i64, f64)]) -> Option<f64> {
+    let mut per_second: Vec<f64> = vec![];
+    for w in samples.windows(2) {
+        let (t1, p1) = w[0];
+        let (t2, _) = w[1];
+        let gap = t2 - t1;
+        if gap <= 0 {
+            continue;
+        }
+        if gap <= NP_MAX_FILL_SECONDS {
+            per_second.extend(std::iter::repeat_n(p1, gap as usize));
+        } else {
+            per_second.push(p1);
+        }
+    }
+    if let Some((_, p)) = samples.last() {
+        per_second.push(*p);
+    }
+
+    if per_second.len() < NP_WINDOW_SECONDS {
+        return None;
+    }
+
+    let mut window_sum: f64 = per_second[..NP_WINDOW_SECONDS].iter().sum();
+    let mut sum4 = (window_sum / NP_WINDOW_SECONDS as f64).powi(4);
+    let mut count = 1;
+    for i in NP_WINDOW_SECONDS..per_second.len() {
+        window_sum += per_second[i] - per_second[i - NP_WINDOW_SECONDS];
+        sum4 += (window_sum / NP_WINDOW_SECONDS as f64).powi(4);
+        count += 1;
+    }
+
+    Some((sum4 / count as f64).powf(0.25))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn parse_track_point_extension() {
+        let xml = br#"<gpx xmlns:ns3="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+  <trk><trkseg>
+    <trkpt lat="1" lon="2"><extensions><ns3:TrackPointExtension>
+      <ns3:atemp>21.0</ns3:atemp><ns3:hr>101</ns3:hr><ns3:cad>85</ns3:cad>
+    </ns3:TrackPointExtension><power>250</power></extensions></trkpt>
+    <trkpt lat="1" lon="2"/>
+    <trkpt lat="1" lon="2"><ele>10</ele></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+        let s = parse_gpx_extensions(xml).unwrap();
+        assert_eq!(s.len(), 3);
+        assert_eq!(
+            s[0],
+            Sensors {
+                hr: Some(101.0),
+                cadence: Some(85.0),
+                power: Some(250.0),
+                temperature: Some(21.0),
+            }
+        );
+        assert_eq!(s[1], Sensors::default());
+        assert_eq!(s[2], Sensors::default());
+    }
+
+    #[test]
+    fn normalized_power_of_steady_effort() {
+        let samples: Vec<(i64, f64)> = (0..120).map(|t| (t, 200.0)).collect();
+        assert_approx_eq!(normalized_power(&samples).unwrap(), 200.0);
+        assert!(normalized_power(&samples[..10]).is_none());
+    }
+
+    #[test]
+    fn normalized_power_is_above_average_for_intervals() {
+        let samples: Vec<(i64, f64)> = (0..600)
+            .map(|t| (t, if (t / 60) % 2 == 0 { 300.0 } else { 100.0 }))
+            .collect();
+        let mut acc = SensorAccumulator::default();
+        for (t, p) in &samples {
+            acc.push(
+                Some(*t),
+                &Sensors {
+                    power: Some(*p),
+                    ..Default::default()
+                },
+            );
+        }
+        let summary = acc.finish();
+        assert_approx_eq!(summary.power_avg.unwrap(), 200.0);
+        assert!(summary.power_np.unwrap() > 200.0);
+        assert!(summary.hr_avg.is_none());
+    }
+}
@@ -0,0 +1,490 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use geo::{Distance, Geodesic};
+use geo_types::Point;
+
+use crate::activity::{Activity, Lap, Sample};
+use crate::climbs::{Climb, detect, grade_stats};
+use crate::elevation::{ElevationOptions, ElevationSample, process};
+use crate::pause::{PauseDetector, PauseOptions};
+use crate::sensors::{SensorAccumulator, SensorSummary, Sensors};
+use crate::time::timezone_at;
+use crate::zones::{ZoneAccumulator, ZoneOptions};
+
+// Max speed is computed on every n-th point, which filters a bit of GPS noise.
+const STEP: usize = 10;
+// Upper bound for the number of points kept for the elevation profile.
+const MAX_PROFILE_POINTS: usize = 1000;
+
+#[derive(Debug)]
+pub(crate) struct ElevPoint {
+    pub(crate) e: f64,
+    pub(crate) d: f64,
+}
+
+/// Stats computed from an activity, as consumed by the template values.
+#[derive(Debug)]
+pub struct ContextData {
+    pub(crate) track_name: String,
+    pub(crate) distance: f64,
+    pub(crate) speed: f64,
+    pub(crate) speed_max: f64,
+    /// Where the max speed was reached.
+    pub(crate) speed_max_point: Option<Point<f64>>,
+    pub(crate) speed_moving: f64,
+    pub(crate) time: Duration,
+    pub(crate) time_moving: Duration,
+    pub(crate) time_stopped: Duration,
+    pub(crate) pause_count: usize,
+    pub(crate) uphill: f64,
+    pub(crate) downhill: f64,
+    pub(crate) elevation: Vec<ElevPoint>,
+    pub(crate) elevation_max: f64,
+    pub(crate) elevation_min: f64,
+    /// Highest recorded point, with its elevation before smoothing.
+    pub(crate) highest_point: Option<(f64, Point<f64>)>,
+    /// Detected climbs, hardest first.
+    pub(crate) climbs: Vec<Climb>,
+    /// Vertical metres per hour over all the climbs.
+    pub(crate) vam: Option<f64>,
+    pub(crate) grade_avg: f64,
+    pub(crate) grade_max: f64,
+    pub(crate) sensors: SensorSummary,
+    /// Seconds in each heart rate zone, empty without a zone reference or heart rate.
+    pub(crate) hr_zones: Vec<f64>,
+    /// Seconds in each power zone, empty without an FTP or power.
+    pub(crate) power_zones: Vec<f64>,
+    pub(crate) start_time: Option<DateTime<Utc>>,
+    pub(crate) end_time: Option<DateTime<Utc>>,
+    /// Time zone at the start of the track.
+    pub(crate) timezone: Option<Tz>,
+    /// Laps recorded by the device, in order.
+    pub(crate) laps: Vec<Lap>,
+    pub coords: Vec<Point<f64>>,
+}
+
+/// Computes [`ContextData`] from a stream of samples.
+///
+/// Feed samples with [`StatsAccumulator::push`], calling [`StatsAccumulator::end_segment`]
+/// between segments, then call [`StatsAccumulator::finish`].
+#[derive(Debug)]
+pub struct StatsAccumulator {
+    elevation_options: ElevationOptions,
+    tot_distance: f64,
+    pause: PauseDetector,
+    uphill: f64,
+    downhill: f64,
+    speed_max: f64,
+    speed_max_point: Option<Point<f64>>,
+    // Highest raw sample, with its elevation.
+    highest: Option<(f64, Point<f64>)>,
+    elevation_max: f64,
+    elevation_min: f64,
+    elev: Vec<ElevPoint>,
+    climbs: Vec<Climb>,
+    up: f64,
+    up_distance: f64,
+    grade_max: f64,
+    coords: Vec<Point<f64>>,
+    sensors: SensorAccumulator,
+    zones: ZoneAccumulator,
+    laps: Vec<Lap>,
+
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    // Time of the last sample of the previous segment.
+    segment_end: Option<DateTime<Utc>>,
+
+    // Per-segment state.
+    last: Option<Sample>,
+    // Last sensor values with a time, credited to the zones until the next ones.
+    last_sensors: Option<(DateTime<Utc>, Sensors)>,
+    last_step: Option<Sample>,
+    segment_index: usize,
+    segment_elevation: Vec<ElevationSample>,
+}
+
+impl Default for StatsAccumulator {
+    fn default() -> Self {
+        StatsAccumulator::new(ElevationOptions::default(), PauseOptions::default())
+    }
+}
+
+impl StatsAccumulator {
+    pub fn new(elevation_options: ElevationOptions, pause_options: PauseOptions) -> Self {
+        StatsAccumulator {
+            elevation_options,
+            tot_distance: 0.0,
+            pause: PauseDetector::new(pause_options),
+            uphill: 0.0,
+            downhill: 0.0,
+            speed_max: 0.0,
+            speed_max_point: None,
+            highest: None,
+            elevation_max: 0.0,
+            elevation_min: 99999.0,
+            elev: vec![],
+            climbs: vec![],
+            up: 0.0,
+            up_distance: 0.0,
+            grade_max: 0.0,
+            coords: vec![],
+            sensors: SensorAccumulator::default(),
+            zones: ZoneAccumulator::default(),
+            laps: vec![],
+            start_time: None,
+            end_time: None,
+            segment_end: None,
+            last: None,
+            last_sensors: None,
+            last_step: None,
+            segment_index: 0,
+            segment_elevation: vec![],
+        }
+    }
+
+    /// Count the time in the heart rate and power zones of `options`.
+    pub fn with_zone_options(mut self, options: &ZoneOptions) -> Self {
+        self.zones = ZoneAccumulator::new(options);
+        self
+    }
+
+    /// Accumulate all the segments of `activity`, with its sensor records in time order,
+    /// and keep its laps.
+    pub fn push_activity(&mut self, activity: &Activity) {
+        self.laps.extend_from_slice(&activity.laps);
+        let mut records = activity.sensor_records.iter().peekable();
+        let mut flush = |acc: &mut Self, until: Option<DateTime<Utc>>| {
+            while let Some(r) = records.next_if(|r| until.is_none_or(|t| r.time < t)) {
+                acc.push_sensors(r.time, &r.sensors);
+            }
+        };
+        for (i, s) in activity.segments.iter().enumerate() {
+            for sample in s {
+                if let Some(t) = sample.time {
+                    flush(self, Some(t));
+                }
+                self.push(sample);
+            }
+            // Records up to the next segment belong to this one.
+            let next = activity.segments[i + 1..]
+                .iter()
+                .find_map(|s| s.first()?.time);
+            flush(self, next);
+            self.end_segment();
+        }
+    }
+
+    /// Add sensor values recorded without a position, e.g. a trackpoint without a GPS
+    /// fix: they count in the sensor averages and in the zones.
+    pub fn push_sensors(&mut self, time: DateTime<Utc>, sensors: &Sensors) {
+        self.sensors.push(Some(time.timestamp()), sensors);
+        self.push_zones(time, sensors);
+    }
+
+    fn push_zones(&mut self, time: DateTime<Utc>, sensors: &Sensors) {
+        if let Some((t, last)) = self.last_sensors {
+            self.zones
+                .push(&last, (time - t).num_milliseconds() as f64 / 1000.0);
+        }
+        self.last_sensors = Some((time, *sensors));
+    }
+
+    pub fn push(&mut self, sample: &Sample) {
+        match (self.last, self.segment_end, sample.time) {
+            (Some(last), _, _) => {
+                let d = Geodesic.distance(last.point, sample.point);
+                self.tot_distance += d;
+                if let (Some(t1), Some(t2)) = (last.time, sample.time) {
+                    self.pause
+                        .push((t2 - t1).num_milliseconds() as f64 / 1000.0, d);
+                }
+            }
+            (None, Some(t1), Some(t2)) => {
+                self.pause
+                    .push_gap((t2 - t1).num_milliseconds() as f64 / 1000.0);
+            }
+            _ => {}
+        }
+        self.last = Some(*sample);
+        if self.start_time.is_none() {
+            self.start_time = sample.time;
+        }
+        if sample.time.is_some() {
+            self.end_time = sample.time;
+        }
+
+        if let Some(e) = sample.elevation {
+            if self.highest.is_none_or(|(max, _)| e > max) {
+                self.highest = Some((e, sample.point));
+            }
+            self.segment_elevation.push(ElevationSample {
+                d: self.tot_distance,
+                t: sample
+                    .time
+                    .zip(self.start_time)
+                    .map(|(t, t0)| (t - t0).num_milliseconds() as f64 / 1000.0),
+                e,
+            });
+        }
+
+        self.coords.push(sample.point);
+        self.sensors
+            .push(sample.time.map(|t| t.timestamp()), &sample.sensors);
+        if let Some(t) = sample.time {
+            self.push_zones(t, &sample.sensors);
+        }
+
+        if self.segment_index.is_multiple_of(STEP) {
+            if let Some(prev) = self.last_step {
+                self.push_step(&prev, sample);
+            }
+            self.last_step = Some(*sample);
+        }
+        self.segment_index += 1;
+    }
+
+    /// Mark the end of a segment: no distance is counted across segments, and the time
+    /// until the next segment counts as stopped.
+    pub fn end_segment(&mut self) {
+        if let Some(t) = self.last.and_then(|s| s.time) {
+            self.segment_end = Some(t);
+        }
+        self.last = None;
+        self.last_sensors = None;
+        self.last_step = None;
+        self.segment_index = 0;
+
+        let raw = std::mem::take(&mut self.segment_elevation);
+        let (profile, uphill, downhill) = process(&raw, &self.elevation_options);
+        self.uphill += uphill;
+        self.downhill += downhill;
+        self.climbs.extend(detect(&profile));
+        let (up, up_distance, grade_max) = grade_stats(&profile);
+        self.up += up;
+        self.up_distance += up_distance;
+        self.grade_max = self.grade_max.max(grade_max);
+        for p in profile {
+            self.elevation_max = self.elevation_max.max(p.e);
+            self.elevation_min = self.elevation_min.min(p.e);
+            self.elev.push(ElevPoint { d: p.d, e: p.e });
+        }
+    }
+
+    fn push_step(&mut self, w1: &Sample, w2: &Sample) {
+        let d = Geodesic.distance(w1.point, w2.point);
+
+        if let (Some(t1), Some(t2)) = (w1.time, w2.time) {
+            let ptime = Duration::seconds((t2 - t1).num_seconds());
+
+            if ptime.num_seconds() > 0 {
+                let speed = (d.round() / ptime.num_seconds() as f64) * 3.6;
+                if speed > self.speed_max {
+                    self.speed_max = speed;
+                    self.speed_max_point = Some(w2.point);
+                }
+            }
+        }
+    }
+
+    pub fn finish(mut self, track_name: String) -> ContextData {
+        // Flush a segment the caller did not close.
+        if !self.segment_elevation.is_empty() {
+            self.end_segment();
+        }
+
+        let stride = self.elev.len().div_ceil(MAX_PROFILE_POINTS).max(1);
+        let elevation = self
+            .elev
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| i % stride == 0)
+            .map(|(_, p)| p)
+            .collect();
+
+        let (moving, stopped, pause_count) = self.pause.finish();
+        let tot_moving_time = Duration::seconds(moving.round() as i64);
+        let time_stopped = Duration::seconds(stopped.round() as i64);
+        let tot_time = tot_moving_time + time_stopped;
+
+        let speed = if tot_time.num_seconds() > 0 {
+            (self.tot_distance.round() / tot_time.num_seconds() as f64) * 3.6
+        } else {
+            0.0
+        };
+        let speed_moving = if tot_moving_time.num_seconds() > 0 {
+            (self.tot_distance.round() / tot_moving_time.num_seconds() as f64) * 3.6
+        } else {
+            0.0
+        };
+
+        let mut climbs = self.climbs;
+        climbs.sort_by(|a, b| b.score().total_cmp(&a.score()));
+        let (climb_gain, climb_time) = climbs
+            .iter()
+            .filter_map(|c| c.duration.map(|t| (c.gain, t)))
+            .fold((0.0, 0.0), |(g, t), (cg, ct)| (g + cg, t + ct));
+        let vam = (climb_time > 0.0).then(|| climb_gain / climb_time * 3600.0);
+        let grade_avg = if self.up_distance > 0.0 {
+            self.up / self.up_distance * 100.0
+        } else {
+            0.0
+        };
+
+        let (hr_zones, power_zones) = self.zones.finish();
+
+        let timezone = match (self.start_time, self.coords.first()) {
+            (Some(_), Some(p)) => timezone_at(*p),
+            _ => None,
+        };
+
+        ContextData {
+            track_name,
+            distance: self.tot_distance,
+            speed,
+            speed_max: self.speed_max,
+            speed_max_point: self.speed_max_point,
+            speed_moving,
+            time: tot_time,
+            time_moving: tot_moving_time,
+            time_stopped,
+            pause_count,
+            uphill: self.uphill,
+            downhill: self.downhill,
+            elevation,
+            elevation_max: self.elevation_max,
+            elevation_min: self.elevation_min,
+            highest_point: self.highest,
+            climbs,
+            vam,
+            grade_avg,
+            grade_max: self.grade_max,
+            sensors: self.sensors.finish(),
+            hr_zones,
+            power_zones,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            timezone,
+            laps: self.laps,
+            coords: self.coords,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::SensorRecord;
+    use crate::elevation::{Gain, Resample, Smoothing};
+
+    fn sample(i: i64, lat: f64, ele: f64) -> Sample {
+        Sample {
+            point: Point::new(10.0, lat),
+            time: DateTime::from_timestamp(1_600_000_000 + i, 0),
+            elevation: Some(ele),
+            sensors: Default::default(),
+        }
+    }
+
+    #[test]
+    fn no_distance_across_segments() {
+        let mut acc = StatsAccumulator::default();
+        acc.push(&sample(0, 45.0, 0.0));
+        acc.push(&sample(1, 45.001, 0.0));
+        acc.end_segment();
+        acc.push(&sample(100, 46.0, 0.0));
+        acc.push(&sample(101, 46.001, 0.0));
+        let d = acc.finish("x".to_string());
+        assert!((d.distance - 2.0 * 111.15).abs() < 1.0);
+        assert_eq!(d.coords.len(), 4);
+        // 1s moving, 99s between the segments, 1s moving.
+        assert_eq!(d.time.num_seconds(), 101);
+        assert_eq!(d.time_stopped.num_seconds(), 99);
+        assert_eq!(d.pause_count, 1);
+    }
+
+    #[test]
+    fn sensor_records_count_in_averages_and_zones() {
+        let hr = |i, bpm| {
+            let mut s = sample(i, 45.0 + i as f64 * 0.0001, 0.0);
+            s.sensors.hr = Some(bpm);
+            s
+        };
+        let activity = Activity {
+            segments: vec![vec![hr(0, 100.0), hr(20, 100.0)], vec![hr(60, 100.0)]],
+            sensor_records: vec![
+                SensorRecord {
+                    time: DateTime::from_timestamp(1_600_000_010, 0).unwrap(),
+                    sensors: Sensors {
+                        hr: Some(190.0),
+                        ..Default::default()
+                    },
+                },
+                // After the first segment, before the second one.
+                SensorRecord {
+                    time: DateTime::from_timestamp(1_600_000_030, 0).unwrap(),
+                    sensors: Sensors {
+                        hr: Some(190.0),
+                        ..Default::default()
+                    },
+                },
+            ],
+            ..Default::default()
+        };
+        let options = ZoneOptions::default().with_hr_max(200.0);
+        let mut acc = StatsAccumulator::default().with_zone_options(&options);
+        acc.push_activity(&activity);
+        let d = acc.finish("x".to_string());
+        assert_eq!(d.sensors.hr_avg, Some(136.0));
+        assert_eq!(d.sensors.hr_max, Some(190.0));
+        // 0-10s and 20-30s at 100 bpm, 10-20s at 190 bpm.
+        assert_eq!(d.hr_zones, [20.0, 0.0, 0.0, 0.0, 10.0]);
+        assert_eq!(d.coords.len(), 3);
+    }
+
+    #[test]
+    fn uphill_filters_jitter() {
+        let mut acc = StatsAccumulator::default();
+        for i in 0..201 {
+            // Noisy climb: +1m every ~11m with +-0.5m jitter.
+            let jitter = if i % 2 == 0 { 0.5 } else { -0.5 };
+            acc.push(&sample(i, 45.0 + i as f64 * 0.0001, i as f64 + jitter));
+        }
+        let d = acc.finish("x".to_string());
+        assert!((d.uphill - 200.0).abs() < 3.0, "uphill {}", d.uphill);
+        assert_eq!(d.downhill, 0.0);
+    }
+
+    #[test]
+    fn elevation_gain_for_t1() {
+        let bytes = std::fs::read("test/t1.gpx").unwrap();
+        let activity = crate::activity::read_gpx(&bytes).unwrap();
+        let gain = |opts: ElevationOptions| {
+            let mut acc = StatsAccumulator::new(opts, PauseOptions::default());
+            acc.push_activity(&activity);
+            let d = acc.finish("t1".to_string());
+            (d.uphill.round(), d.downhill.round())
+        };
+
+        assert_eq!(gain(ElevationOptions::default()), (26.0, 20.0));
+        assert_eq!(
+            gain(ElevationOptions {
+                resample: Resample::None,
+                smoothing: Smoothing::None,
+                gain: Gain::Raw,
+            }),
+            (93.0, 88.0)
+        );
+        assert_eq!(
+            gain(ElevationOptions {
+                resample: Resample::Time(10.0),
+                smoothing: Smoothing::Kalman {
+                    process_noise: 0.05,
+                    measurement_noise: 4.0,
+                },
+                gain: Gain::Hysteresis { threshold: 1.0 },
+            }),
+            (15.0, 10.0)
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! Aggregate of many activities, for weekly, monthly or yearly summary images.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result, anyhow};
+use chrono::{Datelike, Duration, NaiveDate};
+use galileo::Color;
+use geo_types::Point;
+
+use crate::context::{Context, ContextData};
+use crate::elevation::ElevationOptions;
+use crate::locale::Locale;
+use crate::map::MapOptions;
+use crate::time::{get_hhmmss, local_time};
+use crate::units::Units;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::map::render_tracks_map_href;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::markers::MapOverlay;
+
+const EXTENSIONS: [&str; 3] = ["gpx", "fit", "tcx"];
+
+/// Totals over many activities, usable as a template value and asset provider just like
+/// [`Context`].
+pub struct Summary {
+    title: Option<String>,
+    units: Units,
+    locale: Locale,
+    elevation_options: ElevationOptions,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    map_options: MapOptions,
+
+    rides: Vec<ContextData>,
+    map_href: RefCell<Option<String>>,
+    map_size: RefCell<Option<(u32, u32)>>,
+    map_track_color: RefCell<Option<Color>>,
+    map_error: RefCell<Option<String>>,
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Summary::new()
+    }
+}
+
+impl Summary {
+    pub fn new() -> Summary {
+        Summary {
+            title: None,
+            units: Units::default(),
+            locale: Locale::default(),
+            elevation_options: ElevationOptions::default(),
+            since: None,
+            until: None,
+            map_options: MapOptions::default(),
+            rides: vec![],
+            map_href: RefCell::new(None),
+            map_size: RefCell::new(None),
+            map_track_color: RefCell::new(None),
+            map_error: RefCell::new(None),
+        }
+    }
+
+    /// Title for `value_track_name`. Defaults to the month, the year or the date range
+    /// covered by the rides.
+    pub fn with_title(mut self, title: impl Into<String>) -> Summary {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn with_units(mut self, units: Units) -> Summary {
+        self.units = units;
+        self
+    }
+
+    pub fn with_locale(mut self, locale: Locale) -> Summary {
+        self.locale = locale;
+        self
+    }
+
+    pub fn with_elevation_options(mut self, options: ElevationOptions) -> Summary {
+        self.elevation_options = options;
+        self
+    }
+
+    /// Only keep rides started within `since..=until` (local dates). Applies to the next
+    /// loads.
+    pub fn with_period(mut self, since: Option<NaiveDate>, until: Option<NaiveDate>) -> Summary {
+        self.since = since;
+        self.until = until;
+        self
+    }
+
+    /// Tile source of `image_map`.
+    pub fn with_map_options(mut self, options: MapOptions) -> Summary {
+        self.map_options = options;
+        self
+    }
+
+    /// Why the last `image_map` render failed.
+    pub fn map_error(&self) -> Option<String> {
+        self.map_error.borrow().clone()
+    }
+
+    pub fn ride_count(&self) -> usize {
+        self.rides.len()
+    }
+
+    /// Load files, directories or `*` / `?` patterns on file names, e.g. `rides/2026-10-*.gpx`.
+    /// Returns the number of rides added.
+    pub fn load_inputs(&mut self, inputs: &[impl AsRef<str>]) -> Result<usize> {
+        let paths = expand_inputs(inputs)?;
+        let before = self.rides.len();
+        for path in paths {
+            let mut ctx =
+                Context::new(path.to_string_lossy()).with_elevation_options(self.elevation_options);
+            ctx.load()
+                .with_context(|| format!("error loading {}", path.display()))?;
+            if let Some(data) = ctx.into_data() {
+                self.push(data);
+            }
+        }
+        Ok(self.rides.len() - before)
+    }
+
+    /// Add an already computed ride, unless it is outside the period.
+    pub fn push(&mut self, data: ContextData) {
+        let date = local_date(&data);
+        let in_period = match date {
+            Some(date) => {
+                self.since.is_none_or(|s| date >= s) && self.until.is_none_or(|u| date <= u)
+            }
+            None => self.since.is_none() && self.until.is_none(),
+        };
+        if in_period {
+            self.rides.push(data);
+            self.rides.sort_by_key(|r| r.start_time);
+            self.cleanup_temp_files();
+        }
+    }
+
+    pub fn tracks(&self) -> Vec<&[Point<f64>]> {
+        self.rides.iter().map(|r| r.coords.as_slice()).collect()
+    }
+
+    fn title(&self) -> String {
+        if let Some(title) = &self.title {
+            return title.clone();
+        }
+        let dates: Vec<NaiveDate> = self.rides.iter().filter_map(local_date).collect();
+        let (Some(first), Some(last)) = (dates.first(), dates.last()) else {
+            return "-".to_string();
+        };
+        if first.year() == last.year() && first.month() == last.month() {
+            let month = self.locale.month_name(first.month());
+            let mut chars = month.chars();
+            let month: String = chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default();
+            format!("{} {}", month, first.year())
+        } else if first.year() == last.year() {
+            first.year().to_string()
+        } else {
+            format!("{} – {}", self.locale.date(first), self.locale.date(last))
+        }
+    }
+
+    pub fn get_string(&self, k: &str) -> Option<String> {
+        if let Some(label) = k.strip_prefix("label_") {
+            return self.locale.label(label).map(str::to_string);
+        }
+        if k == "value_map_attribution" {
+            return Some(
+                self.map_options
+                    .tiles
+                    .attribution()
+                    .unwrap_or_default()
+                    .to_string(),
+            );
+        }
+
+        let u = self.units;
+        let rides = &self.rides;
+        let sum = |f: fn(&ContextData) -> f64| rides.iter().map(f).sum::<f64>();
+        let sum_time = |f: fn(&ContextData) -> Duration| {
+            rides.iter().map(f).fold(Duration::zero(), |a, b| a + b)
+        };
+        let longest = rides
+            .iter()
+            .max_by(|a, b| a.distance.total_cmp(&b.distance));
+        let date = |d: Option<NaiveDate>| match d {
+            Some(d) => self.locale.date(&d),
+            None => "-".to_string(),
+        };
+
+        let v = match k {
+            "value_track_name" => return Some(self.title()),
+            "value_ride_count" => return Some(rides.len().to_string()),
+            "value_longest_ride_name" => {
+                return Some(longest.map_or("-".to_string(), |r| r.track_name.clone()));
+            }
+            "value_date_from" => return Some(date(rides.iter().find_map(local_date))),
+            "value_date_to" => return Some(date(rides.iter().rev().find_map(local_date))),
+            "value_distance" => u.distance(sum(|r| r.distance)),
+            "value_uphill" => u.elevation(sum(|r| r.uphill)),
+            "value_downhill" => u.elevation(sum(|r| r.downhill)),
+            "value_time" => get_hhmmss(sum_time(|r| r.time)),
+            "value_moving_time" => get_hhmmss(sum_time(|r| r.time_moving)),
+            "value_speed_moving" => {
+                let secs = sum_time(|r| r.time_moving).num_seconds();
+                if secs > 0 {
+                    u.speed(sum(|r| r.distance) / secs as f64 * 3.6, self.locale)
+                } else {
+                    "-".to_string()
+                }
+            }
+            "value_elevation_max" => match rides.iter().map(|r| r.elevation_max).reduce(f64::max) {
+                Some(e) => u.elevation(e),
+                None => "-".to_string(),
+            },
+            "value_longest_ride" => match longest {
+                Some(r) => u.distance(r.distance),
+                None => "-".to_string(),
+            },
+            _ => return None,
+        };
+        Some(v)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_image(
+        &self,
+        k: &str,
+        w_px: u32,
+        h_px: u32,
+        track_color: Option<Color>,
+    ) -> Option<String> {
+        if k != "image_map" || self.rides.is_empty() {
+            return None;
+        }
+
+        let needs_render = self.map_href.borrow().is_none()
+            || *self.map_size.borrow() != Some((w_px, h_px))
+            || *self.map_track_color.borrow() != track_color;
+        if needs_render {
+            let href = render_tracks_map_href(
+                &self.tracks(),
+                galileo_types::cartesian::Size::<u32>::new(w_px, h_px),
+                track_color,
+                &self.map_options,
+                &MapOverlay::default(),
+            );
+            *self.map_error.borrow_mut() = href.as_ref().err().map(|e| format!("{e:#}"));
+            *self.map_href.borrow_mut() = Some(href.ok()?);
+            *self.map_size.borrow_mut() = Some((w_px, h_px));
+            *self.map_track_color.borrow_mut() = track_color;
+        }
+
+        self.map_href.borrow().clone()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_image(
+        &self,
+        _k: &str,
+        _w_px: u32,
+        _h_px: u32,
+        _track_color: Option<Color>,
+    ) -> Option<String> {
+        None
+    }
+
+    pub fn cleanup_temp_files(&self) {
+        self.map_href.borrow_mut().take();
+        self.map_size.borrow_mut().take();
+        self.map_track_color.borrow_mut().take();
+    }
+}
+
+fn local_date(d: &ContextData) -> Option<NaiveDate> {
+    d.start_time.map(|t| local_time(t, d.timezone).date_naive())
+}
+
+fn is_activity_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.iter().any(|x| x.eq_ignore_ascii_case(e)))
+}
+
+/// `*` matches any run of characters, `?` a single one.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (p, n): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut pi, mut ni) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        match p.get(pi) {
+            Some('*') => {
+                star = Some((pi, ni));
+                pi += 1;
+            }
+            Some(c) if *c == '?' || *c == n[ni] => {
+                pi += 1;
+                ni += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    pi = sp + 1;
+                    ni = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+fn list_dir(dir: &Path, pattern: Option<&str>) -> Result<Vec<PathBuf>> {
+    let mut out = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("error reading {}", dir.display()))? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let matches = match pattern {
+            Some(p) => wildcard_match(p, name),
+            None => is_activity_file(&path),
+        };
+        if matches && path.is_file() {
+            out.push(path);
+        }
+    }
+    Ok(out)
+}
+
+/// Expand files, directories and file name patterns into a sorted list of files.
+pub fn expand_inputs(inputs: &[impl AsRef<str>]) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for input in inputs {
+        let path = Path::new(input.as_ref());
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        if name.contains(['*', '?']) {
+            let dir = match path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p,
+                _ => Path::new("."),
+            };
+            paths.extend(list_dir(dir, Some(name))?);
+        } else if path.is_dir() {
+            paths.extend(list_dir(path, None)?);
+        } else if path.is_file() {
+            paths.push(path.to_path_buf());
+        } else {
+            return Err(anyhow!("{} not found", path.display()));
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ride(start: u32, km: f64) -> ContextData {
+        let n = (km * 100.0) as usize;
+        let records: Vec<_> = (0..=n)
+            .map(|i| {
+                let lat = 45.0 + i as f64 * 10.0 / 111_195.0;
+                (start + i as u32 * 2, lat, 10.0, 100.0, 130, 180)
+            })
+            .collect();
+        let mut ctx = Context::new(format!("ride_{start}.fit"));
+        ctx.load_from_bytes(&crate::test_util::build_fit(&records))
+            .unwrap();
+        ctx.into_data().unwrap()
+    }
+
+    #[test]
+    fn totals_over_rides() {
+        // FIT timestamps count from 1989-12-31: 1_159_963_200 is 2026-10-03 12:00 UTC.
+        let mut s = Summary::new();
+        s.push(ride(1_159_963_200, 10.0));
+        s.push(ride(1_160_463_200, 25.0));
+        assert_eq!(s.get_string("value_ride_count").unwrap(), "2");
+        assert_eq!(s.get_string("value_distance").unwrap(), "35km");
+        assert_eq!(s.get_string("value_longest_ride").unwrap(), "25km");
+        assert_eq!(
+            s.get_string("value_longest_ride_name").unwrap(),
+            "ride_1160463200"
+        );
+        assert_eq!(s.get_string("value_track_name").unwrap(), "October 2026");
+        assert_eq!(s.tracks().len(), 2);
+    }
+
+    #[test]
+    fn period_filters_rides() {
+        let mut s = Summary::new().with_period(NaiveDate::from_ymd_opt(2026, 10, 5), None);
+        s.push(ride(1_159_963_200, 1.0));
+        s.push(ride(1_160_463_200, 1.0));
+        assert_eq!(s.ride_count(), 1);
+    }
+
+    #[test]
+    fn expand_patterns() {
+        assert!(wildcard_match("*.gpx", "t1.gpx"));
+        assert!(wildcard_match("t?.g*", "t1.gpx"));
+        assert!(!wildcard_match("*.fit", "t1.gpx"));
+        let paths = expand_inputs(&["test/*.gpx", "test"]).unwrap();
+        assert_eq!(paths, vec![PathBuf::from("test/t1.gpx")]);
+        assert!(expand_inputs(&["test/missing.gpx"]).is_err());
+    }
+}
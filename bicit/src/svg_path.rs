@@ -0,0 +1,495 @@
+//! SVG path data parsing, to find the box of the `path_*` template elements whatever
+//! the editor wrote: relative or absolute commands, implicit repeats, `h`/`v` lines,
+//! curves and arcs, with or without commas.
+
+use std::f64::consts::{PI, TAU};
+
+use anyhow::{Result, anyhow, bail};
+
+/// A path command with absolute coordinates; `H`/`V` are lines and the control points
+/// of `S`/`T` are resolved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    /// First and second control points, then the end point.
+    CubicTo(f64, f64, f64, f64, f64, f64),
+    /// Control point, then the end point.
+    QuadTo(f64, f64, f64, f64),
+    ArcTo {
+        rx: f64,
+        ry: f64,
+        /// Rotation of the x axis of the ellipse, in degrees.
+        rotation: f64,
+        large_arc: bool,
+        sweep: bool,
+        x: f64,
+        y: f64,
+    },
+    Close,
+}
+
+/// Axis-aligned bounds of a path, in user units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl BoundingBox {
+    fn new(x: f64, y: f64) -> Self {
+        BoundingBox {
+            min_x: x,
+            min_y: y,
+            max_x: x,
+            max_y: y,
+        }
+    }
+
+    fn include(&mut self, x: f64, y: f64) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+}
+
+struct Lexer<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl Lexer<'_> {
+    fn skip_separators(&mut self) {
+        while self
+            .s
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_whitespace() || *c == b',')
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.s.get(self.pos).copied()
+    }
+
+    /// The next command letter, if the next token is one.
+    fn command(&mut self) -> Option<u8> {
+        let c = self.peek().filter(u8::is_ascii_alphabetic)?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |lexer: &mut Self| {
+            let from = lexer.pos;
+            while lexer.s.get(lexer.pos).is_some_and(u8::is_ascii_digit) {
+                lexer.pos += 1;
+            }
+            lexer.pos > from
+        };
+        if matches!(self.s.get(self.pos), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        let mut mantissa = digits(self);
+        // "1.5.5" is two numbers.
+        if self.s.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            mantissa |= digits(self);
+        }
+        if !mantissa {
+            self.pos = start;
+            bail!("expected a number at position {start}");
+        }
+        if matches!(self.s.get(self.pos), Some(b'e' | b'E')) {
+            let mark = self.pos;
+            self.pos += 1;
+            if matches!(self.s.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                self.pos = mark;
+            }
+        }
+        std::str::from_utf8(&self.s[start..self.pos])?
+            .parse()
+            .map_err(|_| anyhow!("invalid number at position {start}"))
+    }
+
+    /// Arc flags are a single `0` or `1` and need no separator, e.g. `a5 5 0 0110 10`.
+    fn flag(&mut self) -> Result<bool> {
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => bail!("expected an arc flag at position {}", self.pos),
+        };
+        self.pos += 1;
+        Ok(flag)
+    }
+
+    fn point(&mut self) -> Result<(f64, f64)> {
+        Ok((self.number()?, self.number()?))
+    }
+}
+
+/// Segments of the path data `d`.
+pub fn parse(d: &str) -> Result<Vec<Segment>> {
+    let mut lexer = Lexer {
+        s: d.as_bytes(),
+        pos: 0,
+    };
+    let mut segments = vec![];
+    // Current point and start of the subpath.
+    let (mut cx, mut cy) = (0.0, 0.0);
+    let (mut sx, mut sy) = (0.0, 0.0);
+    // Last control point of a cubic or quadratic curve, reflected by `S` and `T`.
+    let mut cubic_ctrl: Option<(f64, f64)> = None;
+    let mut quad_ctrl: Option<(f64, f64)> = None;
+    // Command repeated when more coordinates follow without a letter.
+    let mut repeat: Option<u8> = None;
+
+    while lexer.peek().is_some() {
+        let pos = lexer.pos;
+        let command = match (lexer.command(), repeat) {
+            (Some(c), _) => c,
+            (None, Some(c)) => c,
+            (None, None) => bail!("expected a command at position {pos}"),
+        };
+        if segments.is_empty() && !matches!(command, b'M' | b'm') {
+            bail!(
+                "path data must start with a moveto, found '{}'",
+                command as char
+            );
+        }
+        let (ox, oy) = if command.is_ascii_lowercase() {
+            (cx, cy)
+        } else {
+            (0.0, 0.0)
+        };
+        let segment = match command.to_ascii_uppercase() {
+            b'M' => {
+                let (x, y) = lexer.point()?;
+                (sx, sy) = (ox + x, oy + y);
+                Segment::MoveTo(sx, sy)
+            }
+            b'L' => {
+                let (x, y) = lexer.point()?;
+                Segment::LineTo(ox + x, oy + y)
+            }
+            b'H' => Segment::LineTo(ox + lexer.number()?, cy),
+            b'V' => Segment::LineTo(cx, oy + lexer.number()?),
+            b'C' => {
+                let (x1, y1) = lexer.point()?;
+                let (x2, y2) = lexer.point()?;
+                let (x, y) = lexer.point()?;
+                Segment::CubicTo(ox + x1, oy + y1, ox + x2, oy + y2, ox + x, oy + y)
+            }
+            b'S' => {
+                let (x1, y1) =
+                    cubic_ctrl.map_or((cx, cy), |(px, py)| (2.0 * cx - px, 2.0 * cy - py));
+                let (x2, y2) = lexer.point()?;
+                let (x, y) = lexer.point()?;
+                Segment::CubicTo(x1, y1, ox + x2, oy + y2, ox + x, oy + y)
+            }
+            b'Q' => {
+                let (x1, y1) = lexer.point()?;
+                let (x, y) = lexer.point()?;
+                Segment::QuadTo(ox + x1, oy + y1, ox + x, oy + y)
+            }
+            b'T' => {
+                let (x1, y1) =
+                    quad_ctrl.map_or((cx, cy), |(px, py)| (2.0 * cx - px, 2.0 * cy - py));
+                let (x, y) = lexer.point()?;
+                Segment::QuadTo(x1, y1, ox + x, oy + y)
+            }
+            b'A' => {
+                let (rx, ry) = lexer.point()?;
+                let rotation = lexer.number()?;
+                let large_arc = lexer.flag()?;
+                let sweep = lexer.flag()?;
+                let (x, y) = lexer.point()?;
+                Segment::ArcTo {
+                    rx,
+                    ry,
+                    rotation,
+                    large_arc,
+                    sweep,
+                    x: ox + x,
+                    y: oy + y,
+                }
+            }
+            b'Z' => Segment::Close,
+            _ => bail!(
+                "unknown path command '{}' at position {pos}",
+                command as char
+            ),
+        };
+
+        (cubic_ctrl, quad_ctrl) = (None, None);
+        match segment {
+            Segment::MoveTo(x, y) | Segment::LineTo(x, y) | Segment::ArcTo { x, y, .. } => {
+                (cx, cy) = (x, y)
+            }
+            Segment::CubicTo(_, _, x2, y2, x, y) => {
+                cubic_ctrl = Some((x2, y2));
+                (cx, cy) = (x, y);
+            }
+            Segment::QuadTo(x1, y1, x, y) => {
+                quad_ctrl = Some((x1, y1));
+                (cx, cy) = (x, y);
+            }
+            Segment::Close => (cx, cy) = (sx, sy),
+        }
+        segments.push(segment);
+        repeat = match command {
+            b'M' => Some(b'L'),
+            b'm' => Some(b'l'),
+            b'Z' | b'z' => None,
+            c => Some(c),
+        };
+    }
+    Ok(segments)
+}
+
+/// Bounds of the shape drawn by the path data `d`, including the curves between their
+/// end points.
+pub fn bounding_box(d: &str) -> Result<BoundingBox> {
+    let mut bbox: Option<BoundingBox> = None;
+    let mut include = |x: f64, y: f64| match &mut bbox {
+        Some(b) => b.include(x, y),
+        None => bbox = Some(BoundingBox::new(x, y)),
+    };
+    let (mut cx, mut cy) = (0.0, 0.0);
+    let (mut sx, mut sy) = (0.0, 0.0);
+    for segment in parse(d)? {
+        match segment {
+            Segment::MoveTo(x, y) => {
+                (sx, sy) = (x, y);
+                include(x, y);
+            }
+            Segment::LineTo(x, y) => include(x, y),
+            Segment::CubicTo(x1, y1, x2, y2, x, y) => {
+                for t in cubic_extrema(cx, x1, x2, x)
+                    .into_iter()
+                    .chain(cubic_extrema(cy, y1, y2, y))
+                    .flatten()
+                {
+                    let at = |p0: f64, p1: f64, p2: f64, p3: f64| {
+                        let u = 1.0 - t;
+                        u * u * u * p0
+                            + 3.0 * u * u * t * p1
+                            + 3.0 * u * t * t * p2
+                            + t * t * t * p3
+                    };
+                    include(at(cx, x1, x2, x), at(cy, y1, y2, y));
+                }
+                include(x, y);
+            }
+            Segment::QuadTo(x1, y1, x, y) => {
+                for t in [quad_extremum(cx, x1, x), quad_extremum(cy, y1, y)]
+                    .into_iter()
+                    .flatten()
+                {
+                    let at = |p0: f64, p1: f64, p2: f64| {
+                        let u = 1.0 - t;
+                        u * u * p0 + 2.0 * u * t * p1 + t * t * p2
+                    };
+                    include(at(cx, x1, x), at(cy, y1, y));
+                }
+                include(x, y);
+            }
+            Segment::ArcTo {
+                rx,
+                ry,
+                rotation,
+                large_arc,
+                sweep,
+                x,
+                y,
+            } => {
+                for (px, py) in arc_extrema((cx, cy), rx, ry, rotation, large_arc, sweep, (x, y)) {
+                    include(px, py);
+                }
+                include(x, y);
+            }
+            Segment::Close => {}
+        }
+        (cx, cy) = match segment {
+            Segment::MoveTo(x, y)
+            | Segment::LineTo(x, y)
+            | Segment::CubicTo(_, _, _, _, x, y)
+            | Segment::QuadTo(_, _, x, y)
+            | Segment::ArcTo { x, y, .. } => (x, y),
+            Segment::Close => (sx, sy),
+        };
+    }
+    bbox.ok_or(anyhow!("empty path data"))
+}
+
+/// Parameters in (0, 1) where a cubic Bézier coordinate has a minimum or a maximum.
+fn cubic_extrema(p0: f64, p1: f64, p2: f64, p3: f64) -> [Option<f64>; 2] {
+    // The derivative is a quadratic in t.
+    let (d0, d1, d2) = (p1 - p0, p2 - p1, p3 - p2);
+    let (a, b, c) = (d0 - 2.0 * d1 + d2, 2.0 * (d1 - d0), d0);
+    let inside = |t: f64| (t > 0.0 && t < 1.0).then_some(t);
+    if a.abs() < 1e-12 {
+        return [(b.abs() > 1e-12).then(|| -c / b).and_then(inside), None];
+    }
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return [None, None];
+    }
+    let sqrt = disc.sqrt();
+    [
+        inside((-b + sqrt) / (2.0 * a)),
+        inside((-b - sqrt) / (2.0 * a)),
+    ]
+}
+
+/// Parameter in (0, 1) where a quadratic Bézier coordinate has a minimum or a maximum.
+fn quad_extremum(p0: f64, p1: f64, p2: f64) -> Option<f64> {
+    let den = p0 - 2.0 * p1 + p2;
+    if den.abs() < 1e-12 {
+        return None;
+    }
+    let t = (p0 - p1) / den;
+    (t > 0.0 && t < 1.0).then_some(t)
+}
+
+/// Points of an elliptical arc where x or y has a minimum or a maximum, from the center
+/// parameterization of the SVG implementation notes.
+fn arc_extrema(
+    from: (f64, f64),
+    rx: f64,
+    ry: f64,
+    rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    to: (f64, f64),
+) -> Vec<(f64, f64)> {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    // A zero radius is a line, equal end points draw nothing.
+    if rx == 0.0 || ry == 0.0 || from == to {
+        return vec![];
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2.0, (from.1 - to.1) / 2.0);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    // Radii too small to reach the end point are scaled up.
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coef = (num / den).max(0.0).sqrt();
+    if large_arc == sweep {
+        coef = -coef;
+    }
+    let (cx1, cy1) = (coef * rx * y1 / ry, -coef * ry * x1 / rx);
+    let cx = cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.0;
+    let cy = sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.0;
+
+    let start = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
+    let end = ((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx);
+    let mut delta = end - start;
+    if sweep && delta < 0.0 {
+        delta += TAU;
+    } else if !sweep && delta > 0.0 {
+        delta -= TAU;
+    }
+    let on_arc = |theta: f64| {
+        if delta >= 0.0 {
+            (theta - start).rem_euclid(TAU) <= delta
+        } else {
+            (start - theta).rem_euclid(TAU) <= -delta
+        }
+    };
+
+    let theta_x = (-ry * sin).atan2(rx * cos);
+    let theta_y = (ry * cos).atan2(rx * sin);
+    [theta_x, theta_x + PI, theta_y, theta_y + PI]
+        .into_iter()
+        .filter(|theta| on_arc(*theta))
+        .map(|theta| {
+            let (s, c) = theta.sin_cos();
+            (
+                cx + rx * cos * c - ry * sin * s,
+                cy + rx * sin * c + ry * cos * s,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn assert_bbox(d: &str, expected: [f64; 4]) {
+        let b = bounding_box(d).unwrap();
+        for (v, e) in [b.min_x, b.min_y, b.max_x, b.max_y]
+            .into_iter()
+            .zip(expected)
+        {
+            assert_approx_eq!(v, e, 1e-6);
+        }
+    }
+
+    #[test]
+    fn inkscape_path_data() {
+        // Relative moveto with implicit lineto, h/v and a closing line.
+        assert_bbox(
+            "m 2.64583,169.33333 10.58334,-31.75 h 50 v 10 z",
+            [2.64583, 137.58333, 63.22917, 169.33333],
+        );
+        // Absolute commands and a cubic bulging above its end points.
+        assert_bbox("M 10,100 C 10,0 110,0 110,100", [10.0, 25.0, 110.0, 100.0]);
+        let segments = parse("m 0,0 10,0 s 10,10 20,0 t 10,0").unwrap();
+        assert_eq!(
+            segments[2],
+            Segment::CubicTo(10.0, 0.0, 20.0, 10.0, 30.0, 0.0)
+        );
+        assert_eq!(segments[3], Segment::QuadTo(30.0, 0.0, 40.0, 0.0));
+    }
+
+    #[test]
+    fn illustrator_path_data() {
+        // No separators where the grammar allows it, exponents and compact arc flags.
+        assert_bbox("M5.2,174.6l132.3-34.4", [5.2, 140.2, 137.5, 174.6]);
+        assert_bbox("M0,0h1e2v.5-1.5", [0.0, -1.0, 100.0, 0.5]);
+        // A slight bulge under the chord.
+        assert_bbox("M0 0a50 50 0 0010 0z", [0.0, 0.0, 10.0, 0.2506281]);
+        // A half circle under its chord.
+        assert_bbox("M0,0A50,50,0,0,0,100,0", [0.0, 0.0, 100.0, 50.0]);
+    }
+
+    #[test]
+    fn invalid_path_data() {
+        for d in [
+            "",
+            "L 10,10",
+            "M 10",
+            "M 10,10 X 5",
+            "M 0,0 A 5 5 0 2 0 10 0",
+            "M 0,0 z 5",
+        ] {
+            assert!(bounding_box(d).is_err(), "{d:?}");
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! Reader for Garmin Training Center (TCX) activity files.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use geo_types::Point;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::activity::{Activity, Lap, Sample, SensorRecord};
+use crate::sensors::Sensors;
+
+#[derive(Debug, Default)]
+struct Trackpoint {
+    lat: Option<f64>,
+    lon: Option<f64>,
+    time: Option<DateTime<Utc>>,
+    elevation: Option<f64>,
+    sensors: Sensors,
+}
+
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Decode a TCX file. Consecutive laps continue the same segment, a second `<Track>`
+/// in a `<Lap>` (the recording was stopped) starts a new one. The lap totals go to
+/// [`Activity::laps`]. Trackpoints without a position only keep their
+/// sensor values, in [`Activity::sensor_records`].
+pub fn read_tcx(bytes: &[u8]) -> Result<Activity> {
+    let mut reader = Reader::from_reader(bytes);
+    reader.config_mut().trim_text_start = true;
+    reader.config_mut().trim_text_end = true;
+
+    let mut activity = Activity::default();
+    let mut stack: Vec<Vec<u8>> = vec![];
+    let mut segment: Vec<Sample> = vec![];
+    let mut point: Option<Trackpoint> = None;
+    let mut lap: Option<Lap> = None;
+    let mut lap_tracks = 0;
+    let mut found_root = false;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name = e.local_name().as_ref().to_vec();
+                match name.as_slice() {
+                    b"TrainingCenterDatabase" => found_root = true,
+                    b"Activity" => {
+                        if activity.sport.is_none()
+                            && let Some(sport) = e
+                                .attributes()
+                                .filter_map(|a| a.ok())
+                                .find(|a| a.key.local_name().as_ref() == b"Sport")
+                                .and_then(|a| a.unescape_value().ok())
+                        {
+                            activity.sport = Some(sport.to_lowercase());
+                        }
+                    }
+                    b"Lap" => {
+                        lap = Some(Lap {
+                            start_time: e
+                                .attributes()
+                                .filter_map(|a| a.ok())
+                                .find(|a| a.key.local_name().as_ref() == b"StartTime")
+                                .and_then(|a| a.unescape_value().ok())
+                                .and_then(|t| parse_time(&t)),
+                            ..Default::default()
+                        });
+                        lap_tracks = 0;
+                    }
+                    b"Track" => {
+                        if lap_tracks > 0 && !segment.is_empty() {
+                            activity.segments.push(std::mem::take(&mut segment));
+                        }
+                        lap_tracks += 1;
+                    }
+                    b"Trackpoint" => point = Some(Trackpoint::default()),
+                    _ => {}
+                }
+                stack.push(name);
+            }
+            Ok(Event::End(e)) => {
+                match e.local_name().as_ref() {
+                    b"Trackpoint" => match point.take() {
+                        Some(Trackpoint {
+                            lat: Some(lat),
+                            lon: Some(lon),
+                            time,
+                            elevation,
+                            sensors,
+                        }) => segment.push(Sample {
+                            point: Point::new(lon, lat),
+                            time,
+                            elevation,
+                            sensors,
+                        }),
+                        Some(Trackpoint {
+                            time: Some(time),
+                            sensors,
+                            ..
+                        }) if sensors != Sensors::default() => {
+                            activity.sensor_records.push(SensorRecord { time, sensors })
+                        }
+                        _ => {}
+                    },
+                    b"Lap" => {
+                        if let Some(lap) = lap.take() {
+                            activity.laps.push(lap);
+                        }
+                    }
+                    _ => {}
+                }
+                stack.pop();
+            }
+            Ok(Event::Text(e)) if point.is_none() && lap.is_some() => {
+                let text = std::str::from_utf8(&e).unwrap_or("").trim();
+                let mut names = stack.iter().rev().map(|n| n.as_slice());
+                let l = lap.as_mut().expect("checked by the match guard");
+                let value = text.parse().ok();
+                match (names.next(), names.next(), names.next()) {
+                    (Some(b"TotalTimeSeconds"), Some(b"Lap"), _) => l.total_time = value,
+                    (Some(b"DistanceMeters"), Some(b"Lap"), _) => l.distance = value,
+                    (Some(b"Calories"), Some(b"Lap"), _) => l.calories = value,
+                    (Some(b"Cadence"), Some(b"Lap"), _) => l.cadence_avg = value,
+                    (Some(b"Value"), Some(b"AverageHeartRateBpm"), Some(b"Lap")) => {
+                        l.hr_avg = value
+                    }
+                    (Some(b"Value"), Some(b"MaximumHeartRateBpm"), Some(b"Lap")) => {
+                        l.hr_max = value
+                    }
+                    (Some(b"AvgWatts"), Some(b"LX"), _) => l.power_avg = value,
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(e)) if point.is_some() => {
+                let text = std::str::from_utf8(&e).unwrap_or("").trim();
+                let parent = stack.iter().rev().nth(1).map(|n| n.as_slice());
+                let p = point.as_mut().expect("checked by the match guard");
+                match (stack.last().map(|n| n.as_slice()), parent) {
+                    (Some(b"Time"), Some(b"Trackpoint")) => p.time = parse_time(text),
+                    (Some(b"LatitudeDegrees"), _) => p.lat = text.parse().ok(),
+                    (Some(b"LongitudeDegrees"), _) => p.lon = text.parse().ok(),
+                    (Some(b"AltitudeMeters"), _) => p.elevation = text.parse().ok(),
+                    (Some(b"Value"), Some(b"HeartRateBpm")) => p.sensors.hr = text.parse().ok(),
+                    (Some(b"Cadence"), Some(b"Trackpoint")) => {
+                        p.sensors.cadence = text.parse().ok()
+                    }
+                    (Some(b"Watts"), _) => p.sensors.power = text.parse().ok(),
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => {
+                return Err(anyhow!(
+                    "error parsing TCX at position {}: {e}",
+                    reader.buffer_position()
+                ));
+            }
+        }
+        buf.clear();
+    }
+
+    if !found_root {
+        return Err(anyhow!("not a TCX file"));
+    }
+    if !segment.is_empty() {
+        activity.segments.push(segment);
+    }
+
+    Ok(activity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::StatsAccumulator;
+
+    const TCX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2"
+  xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2">
+  <Activities>
+    <Activity Sport="Biking">
+      <Id>2021-03-23T12:05:53.000Z</Id>
+      <Lap StartTime="2021-03-23T12:05:53.000Z">
+        <TotalTimeSeconds>8.0</TotalTimeSeconds>
+        <DistanceMeters>12.5</DistanceMeters>
+        <Calories>3</Calories>
+        <AverageHeartRateBpm><Value>99</Value></AverageHeartRateBpm>
+        <MaximumHeartRateBpm><Value>101</Value></MaximumHeartRateBpm>
+        <Cadence>70</Cadence>
+        <Track>
+          <Trackpoint>
+            <Time>2021-03-23T12:05:53.000Z</Time>
+            <Position>
+              <LatitudeDegrees>44.99751</LatitudeDegrees>
+              <LongitudeDegrees>10.41730</LongitudeDegrees>
+            </Position>
+            <AltitudeMeters>27.8</AltitudeMeters>
+            <HeartRateBpm><Value>101</Value></HeartRateBpm>
+            <Cadence>80</Cadence>
+            <Extensions><ns3:TPX><ns3:Watts>210</ns3:Watts></ns3:TPX></Extensions>
+          </Trackpoint>
+          <Trackpoint>
+            <Time>2021-03-23T12:05:57.000Z</Time>
+            <HeartRateBpm><Value>98</Value></HeartRateBpm>
+          </Trackpoint>
+        </Track>
+        <Extensions><ns3:LX><ns3:AvgWatts>180</ns3:AvgWatts></ns3:LX></Extensions>
+      </Lap>
+      <Lap StartTime="2021-03-23T12:06:01.000Z">
+        <Track>
+          <Trackpoint>
+            <Time>2021-03-23T12:06:01.000Z</Time>
+            <Position>
+              <LatitudeDegrees>44.99760</LatitudeDegrees>
+              <LongitudeDegrees>10.41740</LongitudeDegrees>
+            </Position>
+          </Trackpoint>
+        </Track>
+        <Track>
+          <Trackpoint>
+            <Time>2021-03-23T12:06:05.000Z</Time>
+            <Position>
+              <LatitudeDegrees>44.99770</LatitudeDegrees>
+              <LongitudeDegrees>10.41750</LongitudeDegrees>
+            </Position>
+          </Trackpoint>
+        </Track>
+      </Lap>
+    </Activity>
+  </Activities>
+</TrainingCenterDatabase>"#;
+
+    #[test]
+    fn read_laps_and_tracks() {
+        let a = read_tcx(TCX.as_bytes()).unwrap();
+        assert_eq!(a.sport.as_deref(), Some("biking"));
+        // The first two laps are one segment, the second track of lap 2 is another.
+        assert_eq!(a.segments.len(), 2);
+        assert_eq!(a.segments[0].len(), 2);
+        assert_eq!(a.segments[1].len(), 1);
+        // The second trackpoint has no position, only its sensor values are kept.
+        assert_eq!(a.sensor_records.len(), 1);
+        assert_eq!(a.sensor_records[0].time.timestamp(), 1616501157);
+        assert_eq!(a.sensor_records[0].sensors.hr, Some(98.0));
+
+        assert_eq!(a.laps.len(), 2);
+        assert_eq!(
+            a.laps[0],
+            Lap {
+                start_time: Some(a.segments[0][0].time.unwrap()),
+                total_time: Some(8.0),
+                distance: Some(12.5),
+                calories: Some(3.0),
+                hr_avg: Some(99.0),
+                hr_max: Some(101.0),
+                cadence_avg: Some(70.0),
+                power_avg: Some(180.0),
+            }
+        );
+        assert_eq!(a.laps[1].start_time, a.segments[0][1].time);
+        assert_eq!(a.laps[1].total_time, None);
+
+        let p = a.segments[0][0];
+        assert_eq!(p.point, Point::new(10.41730, 44.99751));
+        assert_eq!(p.elevation, Some(27.8));
+        assert_eq!(
+            p.sensors,
+            Sensors {
+                hr: Some(101.0),
+                cadence: Some(80.0),
+                power: Some(210.0),
+                temperature: None,
+            }
+        );
+        assert_eq!(p.time.unwrap().timestamp(), 1616501153);
+    }
+
+    #[test]
+    fn contiguous_laps_keep_distance() {
+        let lap = |start: u32, lats: [&str; 2]| {
+            let points: String = lats
+                .iter()
+                .enumerate()
+                .map(|(i, lat)| {
+                    format!(
+                        "<Trackpoint><Time>2021-03-23T12:06:{:02}Z</Time><Position>\
+                         <LatitudeDegrees>{lat}</LatitudeDegrees>\
+                         <LongitudeDegrees>10.0</LongitudeDegrees></Position></Trackpoint>",
+                        start as usize + i
+                    )
+                })
+                .collect();
+            format!("<Lap StartTime=\"2021-03-23T12:06:{start:02}Z\"><Track>{points}</Track></Lap>")
+        };
+        let tcx = format!(
+            "<TrainingCenterDatabase><Activities><Activity Sport=\"Biking\">{}{}</Activity>\
+             </Activities></TrainingCenterDatabase>",
+            lap(0, ["45.0000", "45.0001"]),
+            lap(2, ["45.0002", "45.0003"]),
+        );
+        let a = read_tcx(tcx.as_bytes()).unwrap();
+        assert_eq!(a.laps.len(), 2);
+
+        let mut acc = StatsAccumulator::default();
+        acc.push_activity(&a);
+        let d = acc.finish("x".to_string());
+        assert!((d.distance - 3.0 * 11.12).abs() < 0.1, "{}", d.distance);
+        assert_eq!(d.pause_count, 0);
+    }
+
+    #[test]
+    fn reject_gpx() {
+        assert!(read_tcx(br#"<gpx version="1.1"><trk/></gpx>"#).is_err());
+    }
+}
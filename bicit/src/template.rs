@@ -1,5 +1,5 @@
-use anyhow::Result;
-use galileo::Color;
+use anyhow::{Context as _, Result, anyhow, bail};
+pub use galileo::Color;
 use quick_xml::Reader;
 use quick_xml::Writer;
 use quick_xml::events::{BytesStart, BytesText, Event};
@@ -8,6 +8,11 @@ use std::io::Cursor;
 
 use crate::InputPath;
 use crate::context::Context;
+use crate::gradient::TrackColoring;
+use crate::markers::MarkerOptions;
+use crate::render::ExportSize;
+use crate::summary::Summary;
+use crate::tiles::TileSource;
 
 pub const TRANSPARENT_PNG_DATA_URL: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAQAAAC1HAwCAAAAC0lEQVR42mP8/x8AAwMCAO6qP6cAAAAASUVORK5CYII=";
 
@@ -18,9 +23,35 @@ pub struct MapImageRequest {
     pub track_color: Option<Color>,
 }
 
+/// Area of a `group_*` element, from its first `rect` child, with the settings of the
+/// group.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// The `data-options` attribute of the group.
+    pub options: Option<String>,
+    /// Fill and stroke colors of the `rect`, if any.
+    pub fill: Option<String>,
+    pub stroke: Option<String>,
+}
+
 pub trait ValueProvider {
     fn get_string(&self, k: &str) -> Option<String>;
     fn get_path(&self, k: &str, inp: &InputPath) -> Option<String>;
+
+    /// Position of a marker drawn over path `k`, relative to the start of the path.
+    fn get_path_marker(&self, _k: &str, _inp: &InputPath) -> Option<(f64, f64)> {
+        None
+    }
+
+    /// SVG content replacing the children of group `k`, drawn from (0, 0) to the size of
+    /// `area` and moved to its position.
+    fn get_group(&self, _k: &str, _area: &GroupBox) -> Option<String> {
+        None
+    }
 }
 
 pub trait AssetProvider {
@@ -41,6 +72,10 @@ impl ValueProvider for Context {
     fn get_path(&self, k: &str, inp: &InputPath) -> Option<String> {
         Context::get_path(self, k, inp)
     }
+
+    fn get_group(&self, k: &str, area: &GroupBox) -> Option<String> {
+        Context::get_group(self, k, area)
+    }
 }
 
 impl AssetProvider for Context {
@@ -55,6 +90,36 @@ impl AssetProvider for Context {
     }
 }
 
+impl ValueProvider for Summary {
+    fn get_string(&self, k: &str) -> Option<String> {
+        Summary::get_string(self, k)
+    }
+
+    fn get_path(&self, _k: &str, _inp: &InputPath) -> Option<String> {
+        None
+    }
+}
+
+impl AssetProvider for Summary {
+    fn get_image(
+        &self,
+        id: &str,
+        w_px: u32,
+        h_px: u32,
+        track_color: Option<Color>,
+    ) -> Option<String> {
+        Summary::get_image(self, id, w_px, h_px, track_color)
+    }
+}
+
+/// A `group_*` element of a template and the area of its first `rect`.
+#[derive(Debug, Clone)]
+struct GroupArea {
+    id: String,
+    area: GroupBox,
+    transform: Option<String>,
+}
+
 #[derive(Debug, Copy, Clone)]
 struct SvgMetrics {
     svg_px_w: f64,
@@ -156,12 +221,96 @@ impl Template {
         values: &V,
         assets: &A,
     ) -> Result<String> {
-        Ok(Template::apply_with_xml(&self.content, values, assets))
+        self.apply_with_scale(values, assets, 1.0)
+    }
+
+    /// Like `apply_with`, with embedded images requested for an export at `scale`.
+    pub fn apply_with_scale<V: ValueProvider, A: AssetProvider>(
+        &self,
+        values: &V,
+        assets: &A,
+        scale: f32,
+    ) -> Result<String> {
+        Template::apply_with_xml(&self.content, values, assets, scale as f64)
     }
 
     /// Extract the desired `image_map` render request for this template.
     pub fn desired_map_image_request(&self) -> Option<MapImageRequest> {
-        Template::desired_map_image_request_xml(&self.content)
+        self.desired_map_image_request_with_scale(1.0)
+    }
+
+    /// The `image_map` render request for an export at `scale`.
+    pub fn desired_map_image_request_with_scale(&self, scale: f32) -> Option<MapImageRequest> {
+        Template::desired_map_image_request_xml(&self.content, scale as f64)
+    }
+
+    /// Width and height of the template in px, from the root `svg` element.
+    pub fn size_px(&self) -> Option<(f32, f32)> {
+        let mut reader = Reader::from_str(&self.content);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) if e.name() == QName(b"svg") => {
+                    let w = parse_svg_length_to_px(&Template::get_attribute(&e, b"width")?)?;
+                    let h = parse_svg_length_to_px(&Template::get_attribute(&e, b"height")?)?;
+                    return Some((w as f32, h as f32));
+                }
+                Ok(Event::Eof) | Err(_) => return None,
+                Ok(_) => {}
+            }
+        }
+    }
+
+    /// Map style asked by the `data-map-style` attribute of `image_map`, e.g. `dark` or
+    /// a tile URL.
+    pub fn map_style(&self) -> Option<TileSource> {
+        self.map_attribute(b"data-map-style")?.parse().ok()
+    }
+
+    /// Vector map colors from the `data-map-colors` attribute of `image_map`, e.g.
+    /// `background:#111827;water:#1E1B4B`, see [`VectorStyle`](crate::vector::VectorStyle).
+    pub fn map_colors(&self) -> Option<String> {
+        self.map_attribute(b"data-map-colors")
+    }
+
+    /// Map markers from the `data-map-markers` attribute of `image_map`, e.g.
+    /// `start finish distance:5`, see [`MarkerOptions`].
+    pub fn map_markers(&self) -> Option<MarkerOptions> {
+        self.map_attribute(b"data-map-markers")?.parse().ok()
+    }
+
+    /// Track coloring from the `data-color-by` attribute of `image_map`, e.g. `speed`,
+    /// with the colors of `data-color-ramp` when valid, e.g. `#2563EB,#EF4444`.
+    pub fn track_coloring(&self) -> Option<TrackColoring> {
+        let by = self.map_attribute(b"data-color-by")?.parse().ok()?;
+        let ramp = self
+            .map_attribute(b"data-color-ramp")
+            .and_then(|ramp| ramp.parse().ok())
+            .unwrap_or_default();
+        Some(TrackColoring { by, ramp })
+    }
+
+    fn map_attribute(&self, name: &[u8]) -> Option<String> {
+        let mut reader = Reader::from_str(&self.content);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) | Ok(Event::Empty(e))
+                    if Template::get_attribute(&e, b"id").as_deref() == Some("image_map") =>
+                {
+                    return Template::get_attribute(&e, name);
+                }
+                Ok(Event::Eof) | Err(_) => return None,
+                Ok(_) => {}
+            }
+        }
+    }
+
+    /// Scale factor to export this template at `size`, 1 when the template has no size.
+    pub fn export_scale(&self, size: ExportSize) -> f32 {
+        match (size, self.size_px()) {
+            (ExportSize::Scale(scale), _) => scale,
+            (_, Some((w, h))) => size.scale(w, h),
+            (_, None) => 1.0,
+        }
     }
 
     fn get_attribute(e: &BytesStart, name: &[u8]) -> Option<String> {
@@ -242,29 +391,38 @@ impl Template {
         assets: &A,
         metrics: Option<&SvgMetrics>,
         track_color: Option<Color>,
-    ) -> Option<String> {
+    ) -> Result<Option<String>> {
         let id_t = Template::get_attribute(e, b"id");
         if let Some(id) = id_t {
             match e.name().as_ref() {
                 b"tspan" => {
                     if let Some(v) = values.get_string(&id) {
-                        return Some(v);
+                        return Ok(Some(v));
                     }
                 }
-                b"path" => {
-                    let pathd = Template::get_attribute(e, b"d");
-                    if let Some(pathd_t) = pathd {
-                        let inp = InputPath::new(&pathd_t).unwrap();
+                b"path" => match Template::input_path(e) {
+                    Ok(Some(inp)) => {
                         if let Some(v) = values.get_path(&id, &inp) {
-                            return Some(v);
+                            return Ok(Some(v));
                         }
                     }
-                }
+                    Ok(None) => {}
+                    // Other paths are left as they are, whatever their data.
+                    Err(err) if id.starts_with("path_") => {
+                        return Err(err.context(format!("invalid path '{id}'")));
+                    }
+                    Err(_) => {}
+                },
                 b"image" => {
-                    let wd = Template::get_attribute(e, b"width")?;
-                    let hd = Template::get_attribute(e, b"height")?;
-                    let w_units: f64 = wd.parse().ok()?;
-                    let h_units: f64 = hd.parse().ok()?;
+                    let (Some(wd), Some(hd)) = (
+                        Template::get_attribute(e, b"width"),
+                        Template::get_attribute(e, b"height"),
+                    ) else {
+                        return Ok(None);
+                    };
+                    let (Ok(w_units), Ok(h_units)) = (wd.parse::<f64>(), hd.parse::<f64>()) else {
+                        return Ok(None);
+                    };
 
                     // Render the embedded bitmap at the pixel size it will be displayed at
                     // in the final exported PNG. This avoids resampling blur on map labels.
@@ -282,16 +440,154 @@ impl Template {
                     let color_override = if id == "image_map" { track_color } else { None };
 
                     if let Some(v) = assets.get_image(&id, w_px, h_px, color_override) {
-                        return Some(v);
+                        return Ok(Some(v));
                     }
                 }
                 _ => {}
             }
         }
-        None
+        Ok(None)
+    }
+
+    /// Box of a path element, from its `data-bbox="x y width height"` attribute or else
+    /// from its data. `None` without either.
+    fn input_path(e: &BytesStart) -> Result<Option<InputPath>> {
+        if let Some(bbox) = Template::get_attribute(e, b"data-bbox") {
+            let (x, y, w, h) = parse_viewbox(&bbox)
+                .ok_or_else(|| anyhow!("invalid data-bbox '{bbox}', expected x y width height"))?;
+            return Ok(Some(InputPath::from_box(x, y, w, h)));
+        }
+        match Template::get_attribute(e, b"d") {
+            Some(d) => Ok(Some(InputPath::new(&d)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Areas of the `group_*` elements of the template, from their first `rect`.
+    fn group_areas(xml: &str) -> Vec<GroupArea> {
+        let mut reader = Reader::from_str(xml);
+        let mut areas: Vec<GroupArea> = vec![];
+        // Id, depth and options of the group being read.
+        let mut group: Option<(String, usize, Option<String>)> = None;
+        let mut depth = 0;
+
+        loop {
+            let rect = match reader.read_event() {
+                Ok(Event::Start(e)) => {
+                    depth += 1;
+                    let id = Template::get_attribute(&e, b"id");
+                    if group.is_none()
+                        && e.name() == QName(b"g")
+                        && let Some(id) = id.filter(|id| id.starts_with("group_"))
+                    {
+                        group = Some((id, depth, Template::get_attribute(&e, b"data-options")));
+                        continue;
+                    }
+                    e
+                }
+                Ok(Event::Empty(e)) => e,
+                Ok(Event::End(_)) => {
+                    if group.as_ref().is_some_and(|(_, d, _)| *d == depth) {
+                        group = None;
+                    }
+                    depth -= 1;
+                    continue;
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                Ok(_) => continue,
+            };
+            let Some((id, _, options)) = &group else {
+                continue;
+            };
+            if rect.name() != QName(b"rect") || areas.iter().any(|a| a.id == *id) {
+                continue;
+            }
+            let length = |name: &[u8]| -> Option<f64> {
+                Template::get_attribute(&rect, name).and_then(|v| v.trim().parse().ok())
+            };
+            if let (Some(width), Some(height)) = (length(b"width"), length(b"height")) {
+                areas.push(GroupArea {
+                    id: id.clone(),
+                    area: GroupBox {
+                        x: length(b"x").unwrap_or(0.0),
+                        y: length(b"y").unwrap_or(0.0),
+                        width,
+                        height,
+                        options: options.clone(),
+                        fill: Template::get_paint(&rect, "fill"),
+                        stroke: Template::get_paint(&rect, "stroke"),
+                    },
+                    transform: Template::get_attribute(&rect, b"transform"),
+                });
+            }
+        }
+        areas
+    }
+
+    /// Color of the `fill` or `stroke` attribute or style property, `None` without one.
+    fn get_paint(e: &BytesStart, name: &str) -> Option<String> {
+        let value = Template::get_attribute(e, name.as_bytes()).or_else(|| {
+            let style = Template::get_attribute(e, b"style")?;
+            style.split(';').find_map(|property| {
+                let (key, value) = property.split_once(':')?;
+                (key.trim() == name).then(|| value.to_string())
+            })
+        })?;
+        Some(value.trim().to_string()).filter(|v| !v.is_empty() && v != "none")
+    }
+
+    /// Generated children of a `group_*` element, moved to the area of its `rect`.
+    fn group_content<V: ValueProvider>(
+        e: &BytesStart,
+        areas: &[GroupArea],
+        values: &V,
+    ) -> Option<String> {
+        let id = Template::get_attribute(e, b"id")?;
+        let group = areas.iter().find(|a| a.id == id)?;
+        let content = values.get_group(&id, &group.area)?;
+        let translate = format!("translate({} {})", group.area.x, group.area.y);
+        let transform = match &group.transform {
+            Some(transform) => format!("{transform} {translate}"),
+            None => translate,
+        };
+        Some(format!(
+            r#"<g transform="{}">{content}</g>"#,
+            quick_xml::escape::escape(transform.as_str())
+        ))
+    }
+
+    /// A `circle` over the path, if the provider has a marker for it.
+    fn path_marker<V: ValueProvider>(e: &BytesStart, values: &V) -> Option<BytesStart<'static>> {
+        let id = Template::get_attribute(e, b"id")?;
+        let inp = Template::input_path(e).ok()??;
+        let (dx, dy) = values.get_path_marker(&id, &inp)?;
+        let (sx, sy) = inp.ss.split_once(',')?;
+        let (sx, sy): (f64, f64) = (sx.parse().ok()?, sy.parse().ok()?);
+
+        let color = Template::get_attribute(e, b"stroke")
+            .filter(|s| s.starts_with('#'))
+            .or_else(|| {
+                Template::get_attribute(e, b"style")
+                    .and_then(|style| Template::extract_hex_stroke_from_style(&style))
+            })
+            .unwrap_or_else(|| "#FF2D55".to_string());
+        let r = inp.height.abs().max(1.0) / 16.0;
+
+        let mut circle = BytesStart::new("circle");
+        circle.push_attribute(("id", format!("{id}_marker").as_str()));
+        circle.push_attribute(("cx", (sx + dx).to_string().as_str()));
+        circle.push_attribute(("cy", (sy + dy).to_string().as_str()));
+        circle.push_attribute(("r", r.to_string().as_str()));
+        circle.push_attribute(("fill", color.as_str()));
+        circle.push_attribute(("stroke", "#FFFFFF"));
+        circle.push_attribute(("stroke-width", (r / 3.0).to_string().as_str()));
+        if let Some(transform) = Template::get_attribute(e, b"transform") {
+            circle.push_attribute(("transform", transform.as_str()));
+        }
+        Some(circle)
     }
 
-    fn desired_map_image_request_xml(xml: &str) -> Option<MapImageRequest> {
+    fn desired_map_image_request_xml(xml: &str, scale: f64) -> Option<MapImageRequest> {
         let mut reader = Reader::from_str(xml);
         reader.config_mut().trim_text_start = true;
         reader.config_mut().trim_text_end = true;
@@ -319,9 +615,10 @@ impl Template {
                             ));
 
                             if let (Some(svg_px_w), Some(svg_px_h)) = (svg_px_w, svg_px_h) {
+                                // Images are rendered at the exported size.
                                 svg_metrics = Some(SvgMetrics {
-                                    svg_px_w,
-                                    svg_px_h,
+                                    svg_px_w: svg_px_w * scale,
+                                    svg_px_h: svg_px_h * scale,
                                     viewbox_w: vb.2,
                                     viewbox_h: vb.3,
                                 });
@@ -371,12 +668,14 @@ impl Template {
         xml: &str,
         values: &V,
         assets: &A,
-    ) -> String {
+        scale: f64,
+    ) -> Result<String> {
         let mut reader = Reader::from_str(xml);
         reader.config_mut().trim_text_start = true;
         reader.config_mut().trim_text_end = true;
 
         let track_color = Template::extract_track_color_from_template(xml);
+        let group_areas = Template::group_areas(xml);
 
         let mut writer = Writer::new(Cursor::new(Vec::new()));
         let mut change_text: Option<String> = None;
@@ -404,8 +703,8 @@ impl Template {
 
                             if let (Some(svg_px_w), Some(svg_px_h)) = (svg_px_w, svg_px_h) {
                                 svg_metrics = Some(SvgMetrics {
-                                    svg_px_w,
-                                    svg_px_h,
+                                    svg_px_w: svg_px_w * scale,
+                                    svg_px_h: svg_px_h * scale,
                                     viewbox_w: vb.2,
                                     viewbox_h: vb.3,
                                 });
@@ -413,16 +712,35 @@ impl Template {
                         }
                     }
 
-                    writer.write_event(Event::Start(e.to_owned())).unwrap();
+                    writer.write_event(Event::Start(e.to_owned()))?;
+                }
+                Ok(Event::Start(e)) if e.name() == QName(b"g") => {
+                    writer.write_event(Event::Start(e.to_owned()))?;
+                    // Generated content replaces the children.
+                    if let Some(content) = Template::group_content(&e, &group_areas, values) {
+                        reader.read_to_end(e.name())?;
+                        writer.write_event(Event::Text(BytesText::from_escaped(content)))?;
+                        writer.write_event(Event::End(e.to_end()))?;
+                    }
                 }
                 Ok(Event::Start(e)) if e.name() == QName(b"tspan") => {
-                    change_text =
-                        Template::handle_xml(&e, values, assets, svg_metrics.as_ref(), track_color);
-                    writer.write_event(Event::Start(e.to_owned())).unwrap();
+                    change_text = Template::handle_xml(
+                        &e,
+                        values,
+                        assets,
+                        svg_metrics.as_ref(),
+                        track_color,
+                    )?;
+                    writer.write_event(Event::Start(e.to_owned()))?;
                 }
                 Ok(Event::Empty(e)) if e.name() == QName(b"path") => {
-                    let pd =
-                        Template::handle_xml(&e, values, assets, svg_metrics.as_ref(), track_color);
+                    let pd = Template::handle_xml(
+                        &e,
+                        values,
+                        assets,
+                        svg_metrics.as_ref(),
+                        track_color,
+                    )?;
                     if let Some(pd) = pd {
                         let mut elem = BytesStart::new("path");
                         elem.extend_attributes(
@@ -431,14 +749,22 @@ impl Template {
                                 .filter(|attr| attr.key != QName(b"d")),
                         );
                         elem.push_attribute(("d", pd.as_str()));
-                        writer.write_event(Event::Empty(elem)).unwrap();
+                        writer.write_event(Event::Empty(elem))?;
                     } else {
-                        writer.write_event(Event::Empty(e.to_owned())).unwrap();
+                        writer.write_event(Event::Empty(e.to_owned()))?;
+                    }
+                    if let Some(marker) = Template::path_marker(&e, values) {
+                        writer.write_event(Event::Empty(marker))?;
                     }
                 }
                 Ok(Event::Empty(e)) if e.name() == QName(b"image") => {
-                    let pd =
-                        Template::handle_xml(&e, values, assets, svg_metrics.as_ref(), track_color);
+                    let pd = Template::handle_xml(
+                        &e,
+                        values,
+                        assets,
+                        svg_metrics.as_ref(),
+                        track_color,
+                    )?;
                     if let Some(pd) = pd {
                         let mut elem = BytesStart::new("image");
                         elem.extend_attributes(e.attributes().filter_map(|attr| attr.ok()).filter(
@@ -448,9 +774,9 @@ impl Template {
                             },
                         ));
                         elem.push_attribute(("xlink:href", pd.as_str()));
-                        writer.write_event(Event::Empty(elem)).unwrap();
+                        writer.write_event(Event::Empty(elem))?;
                     } else {
-                        writer.write_event(Event::Empty(e.to_owned())).unwrap();
+                        writer.write_event(Event::Empty(e.to_owned()))?;
                     }
                 }
                 Ok(Event::Text(e)) => {
@@ -458,15 +784,18 @@ impl Template {
                         Some(s) => Event::Text(BytesText::new(&s).into_owned()),
                         None => Event::Text(e.into_owned()),
                     };
-                    writer.write_event(event).unwrap();
+                    writer.write_event(event)?;
                 }
                 Ok(Event::Eof) => break,
-                Ok(e) => writer.write_event(e.into_owned()).unwrap(),
-                Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
+                Ok(e) => writer.write_event(e.into_owned())?,
+                Err(e) => bail!(
+                    "error parsing the template at position {}: {e}",
+                    reader.buffer_position()
+                ),
             }
         }
 
-        String::from_utf8(writer.into_inner().into_inner()).expect("invalid UTF8")
+        String::from_utf8(writer.into_inner().into_inner()).context("invalid UTF-8 in the template")
     }
 }
 
@@ -475,6 +804,9 @@ mod tests {
     use super::*;
     use assert_approx_eq::assert_approx_eq;
 
+    use crate::gradient::ColorBy;
+    use crate::map::MapOptions;
+
     #[test]
     fn svg_length_to_px() {
         assert_approx_eq!(parse_svg_length_to_px("1080").unwrap(), 1080.0);
@@ -499,6 +831,45 @@ mod tests {
         assert_eq!((w_px, h_px), (600, 720));
     }
 
+    #[test]
+    fn map_request_follows_export_scale() {
+        let template = Template::new(
+            r#"<svg width='1080' height='1920' viewBox='0 0 285.75 508'>
+  <image id='image_map' width='158.75' height='190.5'/>
+</svg>"#,
+        );
+        assert_eq!(template.size_px(), Some((1080.0, 1920.0)));
+
+        let scale = template.export_scale(ExportSize::Width(2160));
+        assert_approx_eq!(scale, 2.0);
+        let request = template
+            .desired_map_image_request_with_scale(scale)
+            .unwrap();
+        assert_eq!((request.w_px, request.h_px), (1200, 1440));
+    }
+
+    #[test]
+    fn map_style_from_template() {
+        let template = Template::new(
+            r#"<svg width='100' height='100'>
+  <image id='image_map' width='10' height='10' data-map-style='dark'/>
+</svg>"#,
+        );
+        assert_eq!(template.map_style().unwrap().to_string(), "dark");
+        assert_eq!(template.map_colors(), None);
+        let template =
+            Template::new("<svg><image id='image_map' data-map-colors='water:#1E1B4B'/></svg>");
+        assert_eq!(template.map_colors().as_deref(), Some("water:#1E1B4B"));
+        let template =
+            Template::new("<svg><image id='image_map' data-map-markers='start finish'/></svg>");
+        let markers = template.map_markers().unwrap();
+        assert!(markers.start && markers.finish && markers.distance.is_none());
+        assert_eq!(
+            Template::new("<svg><image id='image_map'/></svg>").map_style(),
+            None
+        );
+    }
+
     #[test]
     fn extract_track_color_from_stroke_attr() {
         let svg = r#"<svg width='1080' height='1080' viewBox='0 0 285.75 285.75'>
@@ -566,4 +937,42 @@ oke-width:0.264583"
         let result = template.apply_context(&context).unwrap();
         assert_eq!(result, exp);
     }
+
+    #[test]
+    fn paths_from_any_editor() {
+        // Inkscape h/v segments, Illustrator compact data, a data-bbox over bad data.
+        let xml = r#"<svg><path id="path_elevation" d="m 10,200 h 200 v -100"/><path id="path_hr" d="M10,200l200-100"/><path id="path_speed" data-bbox="10 100 200 100" d="?"/><path id="path3" d="M 0 0 L"/></svg>"#;
+        let mut context = Context::new("test/t1.gpx");
+        context.load().unwrap();
+        let result = Template::new(xml).apply_context(&context).unwrap();
+        assert!(result.contains(r#"id="path_elevation" d="m 10,200 l "#));
+        assert!(result.contains(r#"id="path_hr" d="M 10,200 l "#));
+        assert!(result.contains(r#"data-bbox="10 100 200 100" d="M 10,200 l "#));
+        // Other paths are kept, even with invalid data.
+        assert!(result.contains(r#"<path id="path3" d="M 0 0 L"/>"#));
+
+        let xml = r#"<svg><path id="path_elevation" d="M 10"/></svg>"#;
+        let err = Template::new(xml).apply_context(&context).unwrap_err();
+        assert!(format!("{err:#}").contains("invalid path 'path_elevation'"));
+    }
+
+    #[test]
+    fn group_children_are_replaced() {
+        let xml = r##"<svg><image id="image_map" data-color-by="elevation" data-color-ramp="#000000,#FFFFFF"/><g id="group_map_legend" fill="#FFFFFF"><rect x="10" y="20" width="140" height="35" transform="rotate(90)"/><text>Legend</text></g><g id="group_other"><rect width="1" height="1"/></g></svg>"##;
+        let template = Template::new(xml);
+        let coloring = template.track_coloring().unwrap();
+        assert_eq!(coloring.by, ColorBy::Elevation);
+        assert_eq!(coloring.ramp.colors().len(), 2);
+
+        let mut context = Context::new("test/t1.gpx");
+        context.load().unwrap();
+        context.set_map_options(MapOptions::default().with_template_settings(&template));
+        let result = template.apply_context(&context).unwrap();
+        assert!(result.contains(
+            r##"<g id="group_map_legend" fill="#FFFFFF"><g transform="rotate(90) translate(10 20)"><defs>"##
+        ));
+        assert!(result.contains(">Elevation</text>") && !result.contains("Legend"));
+        // Groups without generated content are kept.
+        assert!(result.contains(r#"<g id="group_other"><rect width="1" height="1"/></g>"#));
+    }
 }
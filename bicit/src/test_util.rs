@@ -0,0 +1,34 @@
+//! Fixtures shared by the tests of several modules.
+
+use crate::fit::{MESG_RECORD, SEMICIRCLES_TO_DEGREES};
+
+/// Tiny FIT writer for tests: one `record` definition and a list of
+/// `(timestamp, lat, lon, altitude, hr, power)` data messages.
+pub(crate) fn build_fit(records: &[(u32, f64, f64, f64, u8, u16)]) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![];
+    // Definition message for local type 0 -> global 20 (record), little endian.
+    data.extend([0x40, 0, 0]);
+    data.extend(MESG_RECORD.to_le_bytes());
+    data.push(6);
+    data.extend([
+        253, 4, 0x86, 0, 4, 0x85, 1, 4, 0x85, 2, 2, 0x84, 3, 1, 0x02, 7, 2, 0x84,
+    ]);
+    for (t, lat, lon, alt, hr, power) in records {
+        data.push(0x00);
+        data.extend(t.to_le_bytes());
+        data.extend(((lat / SEMICIRCLES_TO_DEGREES) as i32).to_le_bytes());
+        data.extend(((lon / SEMICIRCLES_TO_DEGREES) as i32).to_le_bytes());
+        data.extend((((alt + 500.0) * 5.0) as u16).to_le_bytes());
+        data.push(*hr);
+        data.extend(power.to_le_bytes());
+    }
+
+    let mut out = vec![14, 0x10];
+    out.extend(2132u16.to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(b".FIT");
+    out.extend([0, 0]);
+    out.extend(data);
+    out.extend([0, 0]);
+    out
+}
@@ -0,0 +1,850 @@
+//! Tile sources and a CPU compositor that stitches raster tiles into a track map,
+//! without a GPU. Vector tiles are drawn by galileo, see [`vector`](crate::vector).
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Error, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use galileo::Color;
+use geo_types::Point;
+use tiny_skia::{
+    FillRule, FilterQuality, LineCap, LineJoin, Paint, PathBuilder, Pixmap, PixmapPaint, Stroke,
+    Transform,
+};
+
+use crate::gradient::{TrackGradient, color_runs};
+use crate::map::MapOptions;
+use crate::markers::{MapOverlay, draw_markers};
+
+// Half of the Web Mercator world width, in metres.
+const HALF_WORLD: f64 = 20_037_508.342_789_244;
+// Web Mercator resolution of zoom level 17, the most detailed zoom used for track maps.
+const MIN_RESOLUTION: f64 = HALF_WORLD * 2.0 / 256.0 / 131_072.0;
+
+const OSM_ATTRIBUTION: &str = "© OpenStreetMap contributors";
+/// Track color when the template has none.
+pub(crate) const DEFAULT_TRACK_COLOR: Color = Color::rgba(255, 45, 85, 255);
+
+const OMT_ATTRIBUTION: &str = "© OpenMapTiles © OpenStreetMap contributors";
+const OSM_URL: &str = "https://tile.openstreetmap.org/{z}/{x}/{y}.png";
+// Online tiles are fetched up to this zoom, one more than the track map minimum.
+#[cfg(not(target_arch = "wasm32"))]
+const ONLINE_MAX_ZOOM: u8 = 18;
+#[cfg(not(target_arch = "wasm32"))]
+const CACHE_DIR: &str = ".tile_cache/cpu";
+
+/// An XYZ tile server, e.g. `https://{s}.tile.example.com/{z}/{x}/{y}.png?key={key}`.
+///
+/// `{s}` is replaced by one of the subdomains and `{key}` by the API key of the
+/// [`MapOptions`](crate::map::MapOptions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileUrl {
+    /// Style name for built-in styles.
+    pub name: Option<String>,
+    pub url: String,
+    pub subdomains: Vec<String>,
+    pub attribution: Option<String>,
+}
+
+/// Built-in styles: `(name, url, subdomains, attribution)`.
+const STYLES: [(&str, &str, &str, &str); 5] = [
+    (
+        "topo",
+        "https://{s}.tile.opentopomap.org/{z}/{x}/{y}.png",
+        "abc",
+        "© OpenStreetMap contributors, SRTM | © OpenTopoMap (CC-BY-SA)",
+    ),
+    (
+        "cycle",
+        "https://{s}.tile-cyclosm.openstreetmap.fr/cyclosm/{z}/{x}/{y}.png",
+        "abc",
+        "© OpenStreetMap contributors | CyclOSM",
+    ),
+    (
+        "satellite",
+        "https://server.arcgisonline.com/ArcGIS/rest/services/World_Imagery/MapServer/tile/{z}/{y}/{x}",
+        "",
+        "Tiles © Esri",
+    ),
+    (
+        "dark",
+        "https://{s}.basemaps.cartocdn.com/dark_all/{z}/{x}/{y}.png",
+        "abcd",
+        "© OpenStreetMap contributors © CARTO",
+    ),
+    (
+        "light",
+        "https://{s}.basemaps.cartocdn.com/light_all/{z}/{x}/{y}.png",
+        "abcd",
+        "© OpenStreetMap contributors © CARTO",
+    ),
+];
+
+impl TileUrl {
+    pub fn new(url: impl Into<String>) -> TileUrl {
+        TileUrl {
+            name: None,
+            url: url.into(),
+            subdomains: vec![],
+            attribution: None,
+        }
+    }
+
+    /// Built-in style by name: `topo`, `cycle`, `satellite`, `dark` or `light`.
+    pub fn style(name: &str) -> Option<TileUrl> {
+        let (name, url, subdomains, attribution) = STYLES
+            .into_iter()
+            .find(|(n, ..)| n.eq_ignore_ascii_case(name))?;
+        Some(TileUrl {
+            name: Some(name.to_string()),
+            url: url.to_string(),
+            subdomains: subdomains.chars().map(String::from).collect(),
+            attribution: Some(attribution.to_string()),
+        })
+    }
+
+    pub fn with_subdomains(
+        mut self,
+        subdomains: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.subdomains = subdomains.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_attribution(mut self, attribution: impl Into<String>) -> Self {
+        self.attribution = Some(attribution.into());
+        self
+    }
+
+    pub fn needs_api_key(&self) -> bool {
+        self.url.contains("{key}")
+    }
+
+    /// URL of one tile. Subdomains are spread over neighbouring tiles.
+    pub fn tile_url(&self, z: u32, x: u32, y: u32, api_key: Option<&str>) -> String {
+        let mut url = self
+            .url
+            .replace("{z}", &z.to_string())
+            .replace("{x}", &x.to_string())
+            .replace("{y}", &y.to_string())
+            .replace("{key}", api_key.unwrap_or_default());
+        if !self.subdomains.is_empty() {
+            let s = &self.subdomains[(x as usize + y as usize) % self.subdomains.len()];
+            url = url.replace("{s}", s);
+        }
+        url
+    }
+}
+
+/// Where map tiles come from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TileSource {
+    /// OpenStreetMap tiles, downloaded and cached in `.tile_cache` on native.
+    #[default]
+    Osm,
+    /// Any XYZ tile server, also cached on native.
+    Url(TileUrl),
+    /// An MBTiles (SQLite) file.
+    MbTiles(PathBuf),
+    /// A local `{z}/{x}/{y}.png` directory, `.jpg` and `.webp` tiles work too.
+    Directory(PathBuf),
+    /// Mapbox vector tiles (OpenMapTiles schema) from a tile URL, drawn on the GPU with
+    /// the [`VectorStyle`](crate::vector::VectorStyle).
+    Vector(Box<TileSource>),
+}
+
+impl TileSource {
+    /// `osm` and the built-in [`TileUrl::style`] names.
+    pub const STYLES: [&str; 6] = ["osm", "topo", "cycle", "satellite", "dark", "light"];
+
+    /// Whether maps render without network access.
+    pub fn is_offline(&self) -> bool {
+        matches!(self, TileSource::MbTiles(_) | TileSource::Directory(_))
+    }
+
+    pub fn is_vector(&self) -> bool {
+        matches!(self, TileSource::Vector(_))
+    }
+
+    /// Tile server of the online raster sources.
+    pub fn url(&self) -> Option<TileUrl> {
+        match self {
+            TileSource::Osm => Some(TileUrl {
+                name: Some("osm".to_string()),
+                url: OSM_URL.to_string(),
+                subdomains: vec![],
+                attribution: Some(OSM_ATTRIBUTION.to_string()),
+            }),
+            TileSource::Url(url) => Some(url.clone()),
+            TileSource::MbTiles(_) | TileSource::Directory(_) | TileSource::Vector(_) => None,
+        }
+    }
+
+    /// Credits to show on the image, for `value_map_attribution`.
+    pub fn attribution(&self) -> Option<&str> {
+        match self {
+            TileSource::Osm => Some(OSM_ATTRIBUTION),
+            TileSource::Url(url) => url.attribution.as_deref(),
+            TileSource::MbTiles(_) | TileSource::Directory(_) => None,
+            TileSource::Vector(source) => source.attribution().or(Some(OMT_ATTRIBUTION)),
+        }
+    }
+}
+
+impl fmt::Display for TileSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileSource::Osm => f.write_str("osm"),
+            TileSource::Url(url) => f.write_str(url.name.as_ref().unwrap_or(&url.url)),
+            TileSource::MbTiles(path) | TileSource::Directory(path) => {
+                write!(f, "{}", path.display())
+            }
+            TileSource::Vector(source) => write!(f, "vector:{source}"),
+        }
+    }
+}
+
+impl FromStr for TileSource {
+    type Err = Error;
+
+    /// A style name, a URL template, a `.mbtiles` file or a tile directory: an existing
+    /// one, a path with a separator or any path with a `dir:` prefix. URLs of `.pbf` and
+    /// `.mvt` tiles, or with a `vector:` prefix, are vector tiles.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(anyhow!("empty tile source"));
+        }
+        if let Some(dir) = s.strip_prefix("dir:") {
+            return Ok(TileSource::Directory(PathBuf::from(dir)));
+        }
+        if let Some(source) = s.strip_prefix("vector:") {
+            return match source.parse()? {
+                TileSource::Osm | TileSource::Url(TileUrl { name: Some(_), .. }) => {
+                    Err(anyhow!("{source} is a raster style"))
+                }
+                TileSource::MbTiles(_) | TileSource::Directory(_) => Err(anyhow!(
+                    "vector tiles are loaded from a tile URL, not {source}"
+                )),
+                TileSource::Vector(source) => Ok(TileSource::Vector(source)),
+                source => Ok(TileSource::Vector(Box::new(source))),
+            };
+        }
+        if s.eq_ignore_ascii_case("osm") {
+            return Ok(TileSource::Osm);
+        }
+        if let Some(style) = TileUrl::style(s) {
+            return Ok(TileSource::Url(style));
+        }
+        // URLs, or paths on the same server like `/tiles/{z}/{x}/{y}.png` on wasm.
+        if s.contains("://") || s.contains("{z}") {
+            if !(s.contains("{z}") && s.contains("{x}") && s.contains("{y}")) {
+                return Err(anyhow!("tile URL '{s}' needs {{z}}, {{x}} and {{y}}"));
+            }
+            let url = TileUrl::new(s);
+            let source = TileSource::Url(match s.contains("{s}") {
+                true => url.with_subdomains(["a", "b", "c"]),
+                false => url,
+            });
+            let path = s.split('?').next().unwrap_or_default();
+            return Ok(match path.ends_with(".pbf") || path.ends_with(".mvt") {
+                true => TileSource::Vector(Box::new(source)),
+                false => source,
+            });
+        }
+        let path = PathBuf::from(s);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("mbtiles") => Ok(TileSource::MbTiles(path)),
+            _ if path.exists() || s.contains(['/', '\\']) => Ok(TileSource::Directory(path)),
+            _ => Err(anyhow!(
+                "unknown tile source '{s}', expected {}, a tile URL, an .mbtiles file or a \
+                 tile directory",
+                Self::STYLES.join(", ")
+            )),
+        }
+    }
+}
+
+/// An open tile source.
+enum TileReader {
+    /// Tiles downloaded once and kept in a cache directory, `.tile_cache/cpu` by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    Http {
+        url: TileUrl,
+        api_key: Option<String>,
+        agent: ureq::Agent,
+        cache: PathBuf,
+    },
+    #[cfg(not(target_arch = "wasm32"))]
+    MbTiles(rusqlite::Connection),
+    Directory(PathBuf),
+}
+
+impl TileReader {
+    fn open(options: &MapOptions) -> Result<TileReader> {
+        Self::open_source(&options.tiles, options.api_key.as_deref(), None)
+    }
+
+    /// Downloaded tiles are kept under `cache_dir`, `.tile_cache/cpu` by default. There
+    /// are no online sources on wasm.
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn open_source(
+        source: &TileSource,
+        api_key: Option<&str>,
+        cache_dir: Option<&Path>,
+    ) -> Result<TileReader> {
+        match source {
+            #[cfg(not(target_arch = "wasm32"))]
+            TileSource::Osm | TileSource::Url(_) => {
+                let url = source.url().expect("online source");
+                if url.needs_api_key() && api_key.is_none() {
+                    return Err(anyhow!("tile URL {} needs an API key", url.url));
+                }
+                let agent = ureq::Agent::config_builder()
+                    .timeout_global(Some(Duration::from_secs(30)))
+                    .user_agent(concat!("bicit/", env!("CARGO_PKG_VERSION")))
+                    .build()
+                    .into();
+                let cache = cache_dir
+                    .unwrap_or(Path::new(CACHE_DIR))
+                    .join(cache_name(&url));
+                Ok(TileReader::Http {
+                    url,
+                    api_key: api_key.map(String::from),
+                    agent,
+                    cache,
+                })
+            }
+            #[cfg(target_arch = "wasm32")]
+            TileSource::Osm | TileSource::Url(_) => {
+                Err(anyhow!("{source} tiles can't be stitched on wasm"))
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            TileSource::MbTiles(path) => {
+                let flags = rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY;
+                let conn = rusqlite::Connection::open_with_flags(path, flags)
+                    .map_err(|e| anyhow!("error opening {}: {e}", path.display()))?;
+                Ok(TileReader::MbTiles(conn))
+            }
+            #[cfg(target_arch = "wasm32")]
+            TileSource::MbTiles(_) => Err(anyhow!("MBTiles are not supported on wasm")),
+            TileSource::Directory(path) if path.is_dir() => Ok(TileReader::Directory(path.clone())),
+            TileSource::Directory(path) => {
+                Err(anyhow!("tile directory {} not found", path.display()))
+            }
+            TileSource::Vector(_) => Err(anyhow!("{source} tiles are only drawn on the GPU")),
+        }
+    }
+
+    /// Least and most detailed zoom levels available.
+    fn zoom_levels(&self) -> Result<(u8, u8)> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            TileReader::Http { .. } => Ok((0, ONLINE_MAX_ZOOM)),
+            #[cfg(not(target_arch = "wasm32"))]
+            TileReader::MbTiles(conn) => {
+                let (min, max): (Option<u8>, Option<u8>) = conn.query_row(
+                    "SELECT MIN(zoom_level), MAX(zoom_level) FROM tiles",
+                    [],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )?;
+                min.zip(max).ok_or(anyhow!("no tiles in MBTiles file"))
+            }
+            TileReader::Directory(path) => {
+                let levels: Vec<u8> = fs::read_dir(path)?
+                    .filter_map(|e| e.ok()?.file_name().to_str()?.parse::<u8>().ok())
+                    .collect();
+                levels
+                    .iter()
+                    .min()
+                    .zip(levels.iter().max())
+                    .map(|(min, max)| (*min, *max))
+                    .ok_or(anyhow!("no zoom directories in {}", path.display()))
+            }
+        }
+    }
+
+    /// Path of a downloaded tile in the cache, `None` for offline sources.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn cache_file(&self, z: u8, x: u32, y: u32) -> Option<PathBuf> {
+        match self {
+            TileReader::Http { cache, .. } => Some(cache.join(format!("{z}/{x}/{y}.tile"))),
+            _ => None,
+        }
+    }
+
+    /// A tile downloaded before, without going online.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn cached_tile(&self, z: u8, x: u32, y: u32) -> Option<Vec<u8>> {
+        fs::read(self.cache_file(z, x, y)?).ok()
+    }
+
+    /// Encoded tile image, `None` when the source doesn't have it.
+    fn tile(&self, z: u8, x: u32, y: u32) -> Result<Option<Vec<u8>>> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            TileReader::Http {
+                url,
+                api_key,
+                agent,
+                ..
+            } => {
+                if let Some(tile) = self.cached_tile(z, x, y) {
+                    return Ok(Some(tile));
+                }
+                let file = self.cache_file(z, x, y).expect("online source");
+                let tile_url = url.tile_url(z.into(), x, y, api_key.as_deref());
+                let tile = match agent.get(&tile_url).call() {
+                    Ok(mut response) => response.body_mut().read_to_vec()?,
+                    Err(ureq::Error::StatusCode(404)) => return Ok(None),
+                    Err(e) => return Err(anyhow!("error downloading {tile_url}: {e}")),
+                };
+                // A failing cache only costs a download next time.
+                if let Some(dir) = file.parent()
+                    && fs::create_dir_all(dir).is_ok()
+                {
+                    let _ = fs::write(&file, &tile);
+                }
+                Ok(Some(tile))
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            TileReader::MbTiles(conn) => {
+                use rusqlite::OptionalExtension;
+                // MBTiles rows are TMS, counted from the south.
+                let row = (1u32 << z) - 1 - y;
+                let tile = conn
+                    .query_row(
+                        "SELECT tile_data FROM tiles \
+                         WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+                        (z, x, row),
+                        |r| r.get(0),
+                    )
+                    .optional()?;
+                Ok(tile)
+            }
+            TileReader::Directory(path) => {
+                for ext in ["png", "jpg", "jpeg", "webp"] {
+                    let file = path.join(format!("{z}/{x}/{y}.{ext}"));
+                    if file.is_file() {
+                        return Ok(Some(fs::read(file)?));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Where a map framed on some tracks is centered and its scale, with the same margin
+/// as the track maps. Maps WGS84 points to image pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapFrame {
+    /// EPSG:3857 center.
+    pub center: (f64, f64),
+    /// EPSG:3857 metres per pixel.
+    pub resolution: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn web_mercator(p: Point<f64>) -> (f64, f64) {
+    const R: f64 = 6_378_137.0;
+    let lat = p.y().clamp(-85.051_128, 85.051_128).to_radians();
+    (
+        R * p.x().to_radians(),
+        R * (std::f64::consts::FRAC_PI_4 + lat / 2.0).tan().ln(),
+    )
+}
+
+impl MapFrame {
+    pub fn for_tracks(tracks: &[&[Point<f64>]], width: u32, height: u32) -> Option<MapFrame> {
+        let mut points = tracks
+            .iter()
+            .flat_map(|t| t.iter())
+            .map(|p| web_mercator(*p));
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), (x, y)| {
+            ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+        });
+        let resolution = ((max.0 - min.0) / width.max(1) as f64)
+            .max((max.1 - min.1) / height.max(1) as f64)
+            * 1.1;
+        Some(MapFrame {
+            center: ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0),
+            resolution: resolution.max(MIN_RESOLUTION),
+            width,
+            height,
+        })
+    }
+
+    /// Pixel position of a WGS84 lon/lat point, from the top left corner.
+    pub fn to_pixel(&self, p: Point<f64>) -> (f32, f32) {
+        let (x, y) = web_mercator(p);
+        self.projected_to_pixel(x, y)
+    }
+
+    fn projected_to_pixel(&self, x: f64, y: f64) -> (f32, f32) {
+        (
+            ((x - self.center.0) / self.resolution + self.width as f64 / 2.0) as f32,
+            (self.height as f64 / 2.0 - (y - self.center.1) / self.resolution) as f32,
+        )
+    }
+
+    /// Least detailed zoom whose tiles don't need upscaling, within the `(min, max)` zoom
+    /// levels of the source.
+    fn zoom(&self, (min_zoom, max_zoom): (u8, u8)) -> u8 {
+        let z = (HALF_WORLD * 2.0 / 256.0 / self.resolution).log2().ceil();
+        (z.max(0.0) as u8).clamp(min_zoom, max_zoom)
+    }
+
+    /// Tiles of zoom `z` covering the frame: `(x, y, top left pixel, size in pixels)`.
+    fn tiles(&self, z: u8) -> Vec<(u32, u32, (f32, f32), f32)> {
+        let n = 1u32 << z;
+        let span = HALF_WORLD * 2.0 / n as f64;
+        let half_w = self.width as f64 / 2.0 * self.resolution;
+        let half_h = self.height as f64 / 2.0 * self.resolution;
+        let tile_index = |m: f64| ((m / span).floor().max(0.0) as u32).min(n - 1);
+        let (x0, x1) = (
+            tile_index(self.center.0 - half_w + HALF_WORLD),
+            tile_index(self.center.0 + half_w + HALF_WORLD),
+        );
+        let (y0, y1) = (
+            tile_index(HALF_WORLD - self.center.1 - half_h),
+            tile_index(HALF_WORLD - self.center.1 + half_h),
+        );
+        let size = (span / self.resolution) as f32;
+        (x0..=x1)
+            .flat_map(|x| (y0..=y1).map(move |y| (x, y)))
+            .map(|(x, y)| {
+                let top_left = self
+                    .projected_to_pixel(x as f64 * span - HALF_WORLD, HALF_WORLD - y as f64 * span);
+                (x, y, top_left, size)
+            })
+            .collect()
+    }
+}
+
+/// Cache subdirectory of a tile server: the style name or the URL host.
+#[cfg(not(target_arch = "wasm32"))]
+fn cache_name(url: &TileUrl) -> String {
+    if let Some(name) = &url.name {
+        return name.clone();
+    }
+    let host = url.url.split("://").nth(1).unwrap_or(&url.url);
+    let host = host.split('/').next().unwrap_or_default();
+    host.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// The tiles covering `frame`, missing tiles are left blank.
+pub fn stitch_tiles(frame: &MapFrame, options: &MapOptions) -> Result<Pixmap> {
+    let reader = TileReader::open(options)?;
+    let z = frame.zoom(reader.zoom_levels()?);
+
+    let mut pixmap = Pixmap::new(frame.width, frame.height).ok_or(anyhow!(
+        "invalid map size {}x{}",
+        frame.width,
+        frame.height
+    ))?;
+    pixmap.fill(tiny_skia::Color::from_rgba8(229, 227, 223, 255));
+
+    let paint = PixmapPaint {
+        quality: FilterQuality::Bicubic,
+        ..Default::default()
+    };
+    for (x, y, (left, top), size) in frame.tiles(z) {
+        let Some(bytes) = reader.tile(z, x, y)? else {
+            continue;
+        };
+        let tile = decode_tile(&bytes)?;
+        let scale = size / tile.width() as f32;
+        let transform = Transform::from_row(scale, 0.0, 0.0, scale, left, top);
+        pixmap.draw_pixmap(0, 0, tile.as_ref(), &paint, transform, None);
+    }
+    Ok(pixmap)
+}
+
+fn decode_tile(bytes: &[u8]) -> Result<Pixmap> {
+    let img = image::load_from_memory(bytes)?.to_rgba8();
+    let (w, h) = img.dimensions();
+    let mut data = img.into_raw();
+    for p in data.chunks_exact_mut(4) {
+        let a = p[3] as u16;
+        for c in &mut p[..3] {
+            *c = ((*c as u16 * a + 127) / 255) as u8;
+        }
+    }
+    let size = tiny_skia::IntSize::from_wh(w, h).ok_or(anyhow!("empty tile"))?;
+    Pixmap::from_vec(data, size).ok_or(anyhow!("invalid tile {w}x{h}"))
+}
+
+/// Cased track lines like the GPU map: black outline and colored inner stroke.
+pub fn draw_tracks(
+    pixmap: &mut Pixmap,
+    frame: &MapFrame,
+    tracks: &[&[Point<f64>]],
+    color: Option<Color>,
+) {
+    let mut pb = PathBuilder::new();
+    for track in tracks {
+        for (i, p) in track.iter().enumerate() {
+            let (x, y) = frame.to_pixel(*p);
+            match i {
+                0 => pb.move_to(x, y),
+                _ => pb.line_to(x, y),
+            }
+        }
+    }
+    let Some(path) = pb.finish() else {
+        return;
+    };
+
+    let mut paint = Paint {
+        anti_alias: true,
+        ..Default::default()
+    };
+    let mut stroke = Stroke {
+        width: 10.0,
+        line_cap: LineCap::Round,
+        line_join: LineJoin::Round,
+        ..Default::default()
+    };
+    paint.set_color_rgba8(0, 0, 0, 200);
+    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+    stroke.width = 6.0;
+    paint.set_color(skia_color(color));
+    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+}
+
+/// Inner stroke of the tracks in the colors of `gradient`, over [`draw_tracks`].
+fn draw_gradient(
+    pixmap: &mut Pixmap,
+    frame: &MapFrame,
+    tracks: &[&[Point<f64>]],
+    gradient: &TrackGradient,
+) {
+    let mut paint = Paint {
+        anti_alias: true,
+        ..Default::default()
+    };
+    let stroke = Stroke {
+        width: 6.0,
+        line_cap: LineCap::Round,
+        line_join: LineJoin::Round,
+        ..Default::default()
+    };
+    for (color, points) in color_runs(tracks, Some(gradient), DEFAULT_TRACK_COLOR) {
+        let mut pb = PathBuilder::new();
+        for (i, p) in points.iter().enumerate() {
+            let (x, y) = frame.to_pixel(*p);
+            match i {
+                0 => pb.move_to(x, y),
+                _ => pb.line_to(x, y),
+            }
+        }
+        if let Some(path) = pb.finish() {
+            paint.set_color(skia_color(Some(color)));
+            pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+        }
+    }
+}
+
+/// White dot with a colored ring, e.g. the current position of a replay.
+pub fn draw_dot(pixmap: &mut Pixmap, frame: &MapFrame, p: Point<f64>, color: Option<Color>) {
+    let (x, y) = frame.to_pixel(p);
+    let Some(dot) = PathBuilder::from_circle(x, y, 9.0) else {
+        return;
+    };
+    let mut paint = Paint {
+        anti_alias: true,
+        ..Default::default()
+    };
+    paint.set_color_rgba8(255, 255, 255, 255);
+    pixmap.fill_path(&dot, &paint, FillRule::Winding, Transform::identity(), None);
+    let stroke = Stroke {
+        width: 4.0,
+        ..Default::default()
+    };
+    paint.set_color(skia_color(color));
+    pixmap.stroke_path(&dot, &paint, &stroke, Transform::identity(), None);
+}
+
+fn skia_color(color: Option<Color>) -> tiny_skia::Color {
+    let c = color.unwrap_or(DEFAULT_TRACK_COLOR);
+    tiny_skia::Color::from_rgba8(c.r(), c.g(), c.b(), c.a())
+}
+
+/// `data:image/png;base64,...` href of a pixmap.
+pub fn pixmap_href(pixmap: &Pixmap) -> Result<String> {
+    let png = pixmap.encode_png()?;
+    Ok(format!("data:image/png;base64,{}", BASE64.encode(png)))
+}
+
+/// Track map rendered on the CPU, same framing and track style as the GPU one.
+pub fn render_tracks_map_href(
+    tracks: &[&[Point<f64>]],
+    width: u32,
+    height: u32,
+    track_color: Option<Color>,
+    options: &MapOptions,
+    overlay: &MapOverlay,
+) -> Result<String> {
+    let frame = MapFrame::for_tracks(tracks, width, height)
+        .ok_or(anyhow!("error building map: no coordinates"))?;
+    let mut pixmap = stitch_tiles(&frame, options)?;
+    draw_tracks(&mut pixmap, &frame, tracks, track_color);
+    if let Some(gradient) = &overlay.gradient {
+        draw_gradient(&mut pixmap, &frame, tracks, gradient);
+    }
+    draw_markers(&mut pixmap, &frame, &overlay.markers)?;
+    pixmap_href(&pixmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_frame_centers_the_track() {
+        let track = [Point::new(11.0, 46.0), Point::new(11.1, 46.05)];
+        let frame = MapFrame::for_tracks(&[&track], 1000, 500).unwrap();
+        let (x1, y1) = frame.to_pixel(track[0]);
+        let (x2, y2) = frame.to_pixel(track[1]);
+        assert!((x1 + x2 - 1000.0).abs() < 0.5 && (y1 + y2 - 500.0).abs() < 0.5);
+        // West to east, south to north; 10% margin on the limiting (vertical) side.
+        assert!(x1 < x2 && y1 > y2);
+        assert!((y1 - y2 - 500.0 / 1.1).abs() < 0.5);
+    }
+
+    #[test]
+    fn stitches_local_tiles() {
+        // Test tiles: zoom 1 is red, green, blue and yellow from NW to SE.
+        let track = [Point::new(-60.0, -40.0), Point::new(60.0, 40.0)];
+        let frame = MapFrame::for_tracks(&[&track], 200, 200).unwrap();
+        let options = MapOptions::default().with_tiles("test/tiles".parse().unwrap());
+        let pixmap = stitch_tiles(&frame, &options).unwrap();
+        let px = |x, y| {
+            let c = pixmap.pixel(x, y).unwrap();
+            (c.red(), c.green(), c.blue())
+        };
+        assert_eq!(px(20, 20), (255, 0, 0));
+        assert_eq!(px(180, 20), (0, 255, 0));
+        assert_eq!(px(20, 180), (0, 0, 255));
+        assert_eq!(px(180, 180), (255, 255, 0));
+
+        let href =
+            render_tracks_map_href(&[&track], 200, 200, None, &options, &MapOverlay::default())
+                .unwrap();
+        assert!(href.starts_with("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn parse_offline_sources() {
+        assert!(matches!(
+            "test/tiles".parse().unwrap(),
+            TileSource::Directory(_)
+        ));
+        assert_eq!(
+            "dir:tiles".parse::<TileSource>().unwrap(),
+            TileSource::Directory(PathBuf::from("tiles"))
+        );
+        assert!(matches!(
+            "region.MBTiles".parse().unwrap(),
+            TileSource::MbTiles(_)
+        ));
+        // A mistyped style is not a directory.
+        let err = "darkk".parse::<TileSource>().unwrap_err().to_string();
+        assert!(err.contains("osm, topo, cycle"), "{err}");
+    }
+
+    #[test]
+    fn zoom_within_source_levels() {
+        let track = [Point::new(-60.0, -40.0), Point::new(60.0, 40.0)];
+        let frame = MapFrame::for_tracks(&[&track], 200, 200).unwrap();
+        assert_eq!(frame.zoom((0, 18)), 2);
+        assert_eq!(frame.zoom((4, 18)), 4);
+        assert_eq!(frame.zoom((0, 0)), 0);
+    }
+
+    #[test]
+    fn tile_url_styles() {
+        let dark: TileSource = "dark".parse().unwrap();
+        assert_eq!(dark.to_string(), "dark");
+        assert!(!dark.is_offline());
+        let TileSource::Url(url) = dark else {
+            panic!("not a URL source");
+        };
+        assert_eq!(
+            url.tile_url(3, 1, 2, None),
+            "https://d.basemaps.cartocdn.com/dark_all/3/1/2.png"
+        );
+
+        let custom: TileSource = "https://tiles.example.com/{z}/{x}/{y}.png?key={key}"
+            .parse()
+            .unwrap();
+        let TileSource::Url(url) = custom else {
+            panic!("not a URL source");
+        };
+        assert!(url.needs_api_key());
+        assert_eq!(
+            url.tile_url(1, 0, 1, Some("abc")),
+            "https://tiles.example.com/1/0/1.png?key=abc"
+        );
+        assert!(
+            "https://tiles.example.com/{z}.png"
+                .parse::<TileSource>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn online_tiles_are_cached() {
+        let url = TileUrl::new("https://tiles.example.com/{z}/{x}/{y}.png");
+        assert_eq!(cache_name(&url), "tiles.example.com");
+
+        let cache_dir = std::env::temp_dir().join(format!("bicit-tiles-{}", std::process::id()));
+        let dir = cache_dir.join("tiles.example.com/1/0");
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy("test/tiles/1/0/0.png", dir.join("0.tile")).unwrap();
+
+        let reader =
+            TileReader::open_source(&TileSource::Url(url), None, Some(&cache_dir)).unwrap();
+        let cached = reader.tile(1, 0, 0);
+        let missing = reader.cached_tile(1, 1, 1);
+        fs::remove_dir_all(&cache_dir).unwrap();
+
+        assert_eq!(
+            cached.unwrap().unwrap(),
+            fs::read("test/tiles/1/0/0.png").unwrap()
+        );
+        // Not cached: this one would be downloaded.
+        assert_eq!(missing, None);
+        assert_eq!(
+            reader.cache_file(1, 1, 1),
+            Some(cache_dir.join("tiles.example.com/1/1/1.tile"))
+        );
+    }
+
+    #[test]
+    fn mbtiles_rows_are_flipped() {
+        let source: TileSource = "test/tiles.mbtiles".parse().unwrap();
+        assert!(matches!(source, TileSource::MbTiles(_)));
+        let reader = TileReader::open(&MapOptions::default().with_tiles(source)).unwrap();
+        assert_eq!(reader.zoom_levels().unwrap(), (0, 1));
+        assert_eq!(
+            reader.tile(1, 0, 1).unwrap().unwrap(),
+            fs::read("test/tiles/1/0/1.png").unwrap()
+        );
+        assert!(reader.tile(1, 5, 0).unwrap().is_none());
+    }
+}
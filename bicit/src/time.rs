@@ -1,4 +1,9 @@
-use chrono::Duration;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use chrono_tz::Tz;
+use geo_types::Point;
+use tzf_rs::DefaultFinder;
 
 pub fn get_hhmmss(duration: Duration) -> String {
     let totsec = duration.num_seconds();
@@ -8,3 +13,19 @@ pub fn get_hhmmss(duration: Duration) -> String {
 
     format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
 }
+
+/// IANA time zone at a WGS84 lon/lat point.
+pub fn timezone_at(point: Point<f64>) -> Option<Tz> {
+    // Loading the zone boundaries is slow, do it once.
+    static FINDER: OnceLock<DefaultFinder> = OnceLock::new();
+    let finder = FINDER.get_or_init(DefaultFinder::new);
+    finder.get_tz_name(point.x(), point.y()).parse().ok()
+}
+
+/// `t` in time zone `tz`, UTC when unknown (e.g. tracks on the open sea).
+pub fn local_time(t: DateTime<Utc>, tz: Option<Tz>) -> DateTime<FixedOffset> {
+    match tz {
+        Some(tz) => t.with_timezone(&tz).fixed_offset(),
+        None => t.fixed_offset(),
+    }
+}
@@ -0,0 +1,167 @@
+//! Unit systems used when turning stats into template strings.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Error, anyhow};
+
+use crate::locale::Locale;
+
+const M_PER_MILE: f64 = 1609.344;
+const M_PER_NAUTICAL_MILE: f64 = 1852.0;
+const M_PER_FOOT: f64 = 0.3048;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Units {
+    /// km, km/h, m, °C.
+    #[default]
+    Metric,
+    /// mi, mph, ft, °F.
+    Imperial,
+    /// nmi, kn, m, °C.
+    Nautical,
+    /// UK style: mi and mph, but metres and °C.
+    Mixed,
+}
+
+impl Units {
+    pub const ALL: [Units; 4] = [
+        Units::Metric,
+        Units::Imperial,
+        Units::Nautical,
+        Units::Mixed,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Units::Metric => "metric",
+            Units::Imperial => "imperial",
+            Units::Nautical => "nautical",
+            Units::Mixed => "mixed",
+        }
+    }
+
+    /// Format a distance given in metres.
+    pub fn distance(&self, m: f64) -> String {
+        match self {
+            Units::Metric => format!("{:.0}km", m / 1000.0),
+            Units::Imperial | Units::Mixed => format!("{:.0}mi", m / M_PER_MILE),
+            Units::Nautical => format!("{:.0}nmi", m / M_PER_NAUTICAL_MILE),
+        }
+    }
+
+    /// Metres in the distance unit: km, mi or nmi.
+    pub fn distance_unit(&self) -> f64 {
+        match self {
+            Units::Metric => 1000.0,
+            Units::Imperial | Units::Mixed => M_PER_MILE,
+            Units::Nautical => M_PER_NAUTICAL_MILE,
+        }
+    }
+
+    /// Metres in the elevation unit: m or ft.
+    pub fn elevation_unit(&self) -> f64 {
+        match self {
+            Units::Imperial => M_PER_FOOT,
+            Units::Metric | Units::Nautical | Units::Mixed => 1.0,
+        }
+    }
+
+    /// Format a shorter distance given in metres, with one decimal.
+    pub fn length(&self, m: f64, locale: Locale) -> String {
+        match self {
+            Units::Metric => format!("{}km", locale.decimal(m / 1000.0, 1)),
+            Units::Imperial | Units::Mixed => format!("{}mi", locale.decimal(m / M_PER_MILE, 1)),
+            Units::Nautical => format!("{}nmi", locale.decimal(m / M_PER_NAUTICAL_MILE, 1)),
+        }
+    }
+
+    /// Format a speed given in km/h, with one decimal.
+    pub fn speed(&self, kmh: f64, locale: Locale) -> String {
+        match self {
+            Units::Metric => format!("{}km/h", locale.decimal(kmh, 1)),
+            Units::Imperial | Units::Mixed => {
+                format!("{}mph", locale.decimal(kmh * 1000.0 / M_PER_MILE, 1))
+            }
+            Units::Nautical => {
+                format!(
+                    "{}kn",
+                    locale.decimal(kmh * 1000.0 / M_PER_NAUTICAL_MILE, 1)
+                )
+            }
+        }
+    }
+
+    /// Format an elevation or climb given in metres.
+    pub fn elevation(&self, m: f64) -> String {
+        match self {
+            Units::Imperial => format!("{:.0}ft", m / M_PER_FOOT),
+            Units::Metric | Units::Nautical | Units::Mixed => format!("{:.0}m", m),
+        }
+    }
+
+    /// Format a climbing rate given in metres per hour.
+    pub fn vam(&self, m_per_h: f64) -> String {
+        match self {
+            Units::Imperial => format!("{:.0}ft/h", m_per_h / M_PER_FOOT),
+            Units::Metric | Units::Nautical | Units::Mixed => format!("{:.0}m/h", m_per_h),
+        }
+    }
+
+    /// Format a temperature given in °C, `-` when missing.
+    pub fn temperature(&self, c: Option<f64>) -> String {
+        match (self, c) {
+            (_, None) => "-".to_string(),
+            (Units::Imperial, Some(c)) => format!("{:.0}°F", c * 9.0 / 5.0 + 32.0),
+            (_, Some(c)) => format!("{:.0}°C", c),
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Units {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Units::ALL
+            .into_iter()
+            .find(|u| u.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                anyhow!("unknown units '{s}', expected metric, imperial, nautical or mixed")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imperial_conversions() {
+        let u = Units::Imperial;
+        assert_eq!(u.distance(160934.4), "100mi");
+        assert_eq!(u.speed(32.18688, Locale::En), "20.0mph");
+        assert_eq!(u.elevation(1000.0), "3281ft");
+        assert_eq!(u.temperature(Some(20.0)), "68°F");
+    }
+
+    #[test]
+    fn nautical_and_mixed() {
+        assert_eq!(Units::Nautical.distance(18520.0), "10nmi");
+        assert_eq!(Units::Nautical.speed(18.52, Locale::En), "10.0kn");
+        assert_eq!(Units::Nautical.length(1852.0, Locale::It), "1,0nmi");
+        assert_eq!(Units::Mixed.distance(16093.44), "10mi");
+        assert_eq!(Units::Mixed.elevation(100.0), "100m");
+    }
+
+    #[test]
+    fn parse_names() {
+        assert_eq!("Imperial".parse::<Units>().unwrap(), Units::Imperial);
+        assert!("furlongs".parse::<Units>().is_err());
+    }
+}
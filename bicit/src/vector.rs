@@ -0,0 +1,296 @@
+//! Vector tile basemaps: Mapbox vector tiles (OpenMapTiles schema) drawn by galileo's
+//! vector tile layer, with a bundled style whose colors templates can change.
+//!
+//! Galileo draws all the features of a layer at once, so nothing can go between the
+//! land and the labels of one layer. The basemap is two layers over the same tiles
+//! instead: [`VectorStyle::base_style`] under the track and
+//! [`VectorStyle::label_style`] over it. Galileo draws on the GPU only and loads
+//! vector tiles from a URL, so vector maps need both.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow, bail};
+use galileo::Color;
+use galileo::layer::vector_tile_layer::style::{
+    StyleRule, VectorTileLabelSymbol, VectorTileLineSymbol, VectorTilePolygonSymbol,
+    VectorTileStyle, VectorTileSymbol,
+};
+use galileo::layer::vector_tile_layer::{VectorTileLayer, VectorTileLayerBuilder};
+use galileo::render::text::{
+    FontStyle, FontWeight, HorizontalAlignment, TextStyle, VerticalAlignment,
+};
+use galileo::tile_schema::{TileSchema, TileSchemaBuilder};
+
+use crate::map::MapOptions;
+use crate::tiles::TileSource;
+
+// Vector tiles usually stop at zoom 14 and are drawn overzoomed beyond.
+const VECTOR_MAX_ZOOM: u32 = 14;
+// Minor roads are only drawn from this zoom on.
+const MINOR_ROADS_ZOOM: u32 = 12;
+
+/// Basemap colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VectorStyle {
+    pub background: Color,
+    pub water: Color,
+    pub park: Color,
+    pub building: Color,
+    pub road: Color,
+    pub major_road: Color,
+    pub boundary: Color,
+    pub label: Color,
+    pub label_halo: Color,
+}
+
+impl Default for VectorStyle {
+    /// A light style close to the OpenStreetMap colors.
+    fn default() -> Self {
+        VectorStyle {
+            background: Color::rgba(242, 239, 233, 255),
+            water: Color::rgba(170, 211, 223, 255),
+            park: Color::rgba(205, 235, 176, 255),
+            building: Color::rgba(217, 208, 201, 255),
+            road: Color::rgba(255, 255, 255, 255),
+            major_road: Color::rgba(252, 214, 164, 255),
+            boundary: Color::rgba(158, 156, 171, 255),
+            label: Color::rgba(51, 51, 51, 255),
+            label_halo: Color::rgba(255, 255, 255, 255),
+        }
+    }
+}
+
+impl VectorStyle {
+    /// Color names of [`VectorStyle::with_colors`].
+    pub const KEYS: [&str; 9] = [
+        "background",
+        "water",
+        "park",
+        "building",
+        "road",
+        "major-road",
+        "boundary",
+        "label",
+        "label-halo",
+    ];
+
+    /// Colors from a `name:#rrggbb` list separated by `;`, e.g.
+    /// `background:#111827; water:#1E1B4B`. Other colors are left unchanged.
+    pub fn with_colors(mut self, colors: &str) -> Result<Self> {
+        for entry in colors.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry.split_once(':').ok_or(anyhow!(
+                "invalid map color '{entry}', expected name:#rrggbb"
+            ))?;
+            let color = Color::try_from_hex(value.trim())
+                .ok_or(anyhow!("invalid map color '{}'", value.trim()))?;
+            let slot = match key.trim() {
+                "background" => &mut self.background,
+                "water" => &mut self.water,
+                "park" => &mut self.park,
+                "building" => &mut self.building,
+                "road" => &mut self.road,
+                "major-road" => &mut self.major_road,
+                "boundary" => &mut self.boundary,
+                "label" => &mut self.label,
+                "label-halo" => &mut self.label_halo,
+                key => bail!(
+                    "unknown map color '{key}', expected one of {}",
+                    Self::KEYS.join(", ")
+                ),
+            };
+            *slot = color;
+        }
+        Ok(self)
+    }
+
+    /// Land, water, roads and boundaries, drawn under the track.
+    pub fn base_style(&self) -> VectorTileStyle {
+        let minor_roads = tile_schema().lod_resolution(MINOR_ROADS_ZOOM);
+        let polygon =
+            |color| VectorTileSymbol::Polygon(VectorTilePolygonSymbol { fill_color: color });
+        let line = |color, width| {
+            VectorTileSymbol::Line(VectorTileLineSymbol {
+                width,
+                stroke_color: color,
+            })
+        };
+        let mut rules = vec![
+            rule("water", None, polygon(self.water)),
+            rule("waterway", None, line(self.water, 1.5)),
+            rule("landcover", None, polygon(self.park)),
+            rule("park", None, polygon(self.park)),
+        ];
+        for class in ["park", "grass", "cemetery", "pitch"] {
+            rules.push(rule("landuse", Some(class), polygon(self.park)));
+        }
+        rules.push(rule("building", None, polygon(self.building)));
+        // The first matching rule wins, so major roads come before the other roads.
+        for class in ["motorway", "trunk", "primary"] {
+            rules.push(rule(
+                "transportation",
+                Some(class),
+                line(self.major_road, 3.0),
+            ));
+        }
+        rules.push(StyleRule {
+            max_resolution: minor_roads,
+            ..rule("transportation", None, line(self.road, 1.5))
+        });
+        rules.push(rule("boundary", None, line(self.boundary, 1.0)));
+        VectorTileStyle {
+            rules,
+            background: self.background,
+        }
+    }
+
+    /// City, town and village names, drawn over the track.
+    pub fn label_style(&self) -> VectorTileStyle {
+        let rules = [("city", 16.0), ("town", 13.0), ("village", 11.0)]
+            .into_iter()
+            .map(|(class, font_size)| {
+                let symbol = VectorTileSymbol::Label(VectorTileLabelSymbol {
+                    pattern: "{name}".to_string(),
+                    text_style: TextStyle {
+                        font_family: vec!["sans-serif".to_string()],
+                        font_size,
+                        font_color: self.label,
+                        horizontal_alignment: HorizontalAlignment::Center,
+                        vertical_alignment: VerticalAlignment::Middle,
+                        weight: FontWeight::NORMAL,
+                        style: FontStyle::Normal,
+                        outline_width: 1.5,
+                        outline_color: self.label_halo,
+                    },
+                });
+                rule("place", Some(class), symbol)
+            })
+            .collect();
+        VectorTileStyle {
+            rules,
+            background: Color::rgba(0, 0, 0, 0),
+        }
+    }
+}
+
+fn tile_schema() -> TileSchema {
+    TileSchemaBuilder::web_mercator(0..=VECTOR_MAX_ZOOM)
+        .build()
+        .expect("vector tile schema is valid")
+}
+
+/// Features of `layer` with this `class`, or all of them.
+fn rule(layer: &str, class: Option<&str>, symbol: VectorTileSymbol) -> StyleRule {
+    StyleRule {
+        layer_name: Some(layer.to_string()),
+        properties: class
+            .map(|c| HashMap::from([("class".to_string(), c.to_string())]))
+            .unwrap_or_default(),
+        min_resolution: None,
+        max_resolution: None,
+        symbol,
+    }
+}
+
+/// Galileo layer of the vector tiles of `options` drawn with `style`.
+fn vector_layer(options: &MapOptions, style: VectorTileStyle) -> Result<VectorTileLayer> {
+    let TileSource::Vector(source) = &options.tiles else {
+        bail!("{} is not a vector tile source", options.tiles);
+    };
+    let TileSource::Url(url) = source.as_ref() else {
+        bail!("vector tiles of {source} can't be loaded, use a tile URL");
+    };
+    if url.needs_api_key() && options.api_key.is_none() {
+        return Err(anyhow!("tile URL {} needs an API key", url.url));
+    }
+    let url = url.clone();
+    let api_key = options.api_key.clone();
+    let builder = VectorTileLayerBuilder::new_rest(move |index| {
+        url.tile_url(
+            u32::try_from(index.z).unwrap_or_default(),
+            u32::try_from(index.x).unwrap_or_default(),
+            u32::try_from(index.y).unwrap_or_default(),
+            api_key.as_deref(),
+        )
+    })
+    .with_style(style)
+    .with_tile_schema(tile_schema());
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = builder.with_file_cache_checked(".tile_cache");
+    builder
+        .build()
+        .map_err(|e| anyhow!("error creating {} layer: {e}", options.tiles))
+}
+
+/// The basemap layer drawn under the track and the label layer drawn over it.
+pub fn vector_layers(options: &MapOptions) -> Result<(VectorTileLayer, VectorTileLayer)> {
+    let style = &options.vector_style;
+    Ok((
+        vector_layer(options, style.base_style())?,
+        vector_layer(options, style.label_style())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styles_follow_template_colors() {
+        let style = VectorStyle::default()
+            .with_colors("water: #1E1B4B; label:#E5E7EB")
+            .unwrap();
+        let base = style.base_style();
+        let water = &base.rules[0];
+        assert_eq!(water.layer_name.as_deref(), Some("water"));
+        assert!(matches!(
+            &water.symbol,
+            VectorTileSymbol::Polygon(p) if p.fill_color == Color::rgba(30, 27, 75, 255)
+        ));
+        // No labels under the track, only labels over it.
+        assert!(
+            base.rules
+                .iter()
+                .all(|r| r.layer_name.as_deref() != Some("place"))
+        );
+        let labels = style.label_style();
+        assert_eq!(labels.rules.len(), 3);
+        assert!(labels.rules.iter().all(|r| matches!(
+            &r.symbol,
+            VectorTileSymbol::Label(l) if l.text_style.font_color == Color::rgba(229, 231, 235, 255)
+        )));
+        assert_eq!(labels.background.a(), 0);
+
+        let roads: Vec<_> = (base.rules.iter())
+            .filter(|r| r.layer_name.as_deref() == Some("transportation"))
+            .collect();
+        assert_eq!(roads[0].properties["class"], "motorway");
+        assert!(roads.last().unwrap().properties.is_empty());
+        assert!(roads.last().unwrap().max_resolution.is_some());
+    }
+
+    #[test]
+    fn parse_vector_sources_and_colors() {
+        let url: TileSource = "https://tiles.example.com/{z}/{x}/{y}.pbf?key={key}"
+            .parse()
+            .unwrap();
+        assert!(url.is_vector() && !url.is_offline());
+        let prefixed: TileSource = "vector:https://tiles.example.com/{z}/{x}/{y}"
+            .parse()
+            .unwrap();
+        assert!(prefixed.is_vector());
+        assert_eq!(
+            prefixed.to_string(),
+            "vector:https://tiles.example.com/{z}/{x}/{y}"
+        );
+        assert!("vector:dark".parse::<TileSource>().is_err());
+        assert!("vector:tiles.mbtiles".parse::<TileSource>().is_err());
+
+        let options = MapOptions::default().with_tiles(url);
+        // No API key.
+        assert!(vector_layers(&options).is_err());
+
+        assert!(VectorStyle::default().with_colors("sky:#000000").is_err());
+        assert!(VectorStyle::default().with_colors("water:blue").is_err());
+    }
+}
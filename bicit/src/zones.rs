@@ -0,0 +1,282 @@
+//! Heart rate and power zones from a reference (max heart rate, lactate threshold heart
+//! rate or FTP), the time spent in each zone and a bar chart of it for the
+//! `group_hr_zones` and `group_power_zones` template groups.
+
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use anyhow::{Error, anyhow};
+
+use crate::sensors::Sensors;
+use crate::time::get_hhmmss;
+
+// Intervals between two samples longer than this (seconds) are a recording gap and not
+// counted in any zone.
+const MAX_GAP: f64 = 30.0;
+
+// Upper bounds of each zone but the last, as fractions of the reference.
+const HR_MAX_5: [f64; 4] = [0.60, 0.70, 0.80, 0.90];
+const HR_MAX_7: [f64; 6] = [0.60, 0.70, 0.80, 0.85, 0.90, 0.95];
+// Friel zones, 5a, 5b and 5c are zones 5 to 7 of the 7-zone model.
+const LTHR_5: [f64; 4] = [0.81, 0.90, 0.94, 1.00];
+const LTHR_7: [f64; 6] = [0.81, 0.90, 0.94, 1.00, 1.03, 1.06];
+// Coggan zones, the 5-zone model ends at VO2 max.
+const FTP_5: [f64; 4] = [0.55, 0.75, 0.90, 1.05];
+const FTP_7: [f64; 6] = [0.55, 0.75, 0.90, 1.05, 1.20, 1.50];
+
+// Bar colors of the 7-zone model, the 5-zone model skips the 4th and the last.
+const ZONE_COLORS: [&str; 7] = [
+    "#9CA3AF", "#3B82F6", "#22C55E", "#FACC15", "#F97316", "#EF4444", "#A21CAF",
+];
+const ZONE_COLORS_5: [usize; 5] = [0, 1, 2, 4, 5];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZoneModel {
+    #[default]
+    Five,
+    Seven,
+}
+
+impl ZoneModel {
+    pub fn count(&self) -> usize {
+        match self {
+            ZoneModel::Five => 5,
+            ZoneModel::Seven => 7,
+        }
+    }
+}
+
+impl FromStr for ZoneModel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "5" => Ok(ZoneModel::Five),
+            "7" => Ok(ZoneModel::Seven),
+            _ => Err(anyhow!("unknown zone model '{s}', expected 5 or 7")),
+        }
+    }
+}
+
+/// What heart rate zones are relative to, in bpm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HrReference {
+    Max(f64),
+    /// Lactate threshold heart rate.
+    Lthr(f64),
+}
+
+/// Zone references of the athlete. Without a reference there are no zones.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZoneOptions {
+    pub hr: Option<HrReference>,
+    /// Functional threshold power, in W.
+    pub ftp: Option<f64>,
+    pub model: ZoneModel,
+}
+
+impl ZoneOptions {
+    pub fn with_hr_max(mut self, bpm: f64) -> Self {
+        self.hr = Some(HrReference::Max(bpm));
+        self
+    }
+
+    pub fn with_lthr(mut self, bpm: f64) -> Self {
+        self.hr = Some(HrReference::Lthr(bpm));
+        self
+    }
+
+    pub fn with_ftp(mut self, watts: f64) -> Self {
+        self.ftp = Some(watts);
+        self
+    }
+
+    pub fn with_model(mut self, model: ZoneModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Upper bound in bpm of each heart rate zone but the last.
+    pub fn hr_bounds(&self) -> Option<Vec<f64>> {
+        let (reference, fractions): (_, &[f64]) = match (self.hr?, self.model) {
+            (HrReference::Max(bpm), ZoneModel::Five) => (bpm, &HR_MAX_5),
+            (HrReference::Max(bpm), ZoneModel::Seven) => (bpm, &HR_MAX_7),
+            (HrReference::Lthr(bpm), ZoneModel::Five) => (bpm, &LTHR_5),
+            (HrReference::Lthr(bpm), ZoneModel::Seven) => (bpm, &LTHR_7),
+        };
+        Some(fractions.iter().map(|f| f * reference).collect())
+    }
+
+    /// Upper bound in W of each power zone but the last.
+    pub fn power_bounds(&self) -> Option<Vec<f64>> {
+        let fractions: &[f64] = match self.model {
+            ZoneModel::Five => &FTP_5,
+            ZoneModel::Seven => &FTP_7,
+        };
+        let ftp = self.ftp?;
+        Some(fractions.iter().map(|f| f * ftp).collect())
+    }
+}
+
+/// Index of the zone of `value`, a value on a bound is in the upper zone.
+pub fn zone_index(bounds: &[f64], value: f64) -> usize {
+    bounds.iter().take_while(|b| value >= **b).count()
+}
+
+#[derive(Debug)]
+struct Zones {
+    bounds: Vec<f64>,
+    seconds: Vec<f64>,
+}
+
+impl Zones {
+    fn new(bounds: Vec<f64>) -> Self {
+        let seconds = vec![0.0; bounds.len() + 1];
+        Zones { bounds, seconds }
+    }
+
+    fn push(&mut self, value: Option<f64>, seconds: f64) {
+        if let Some(v) = value {
+            self.seconds[zone_index(&self.bounds, v)] += seconds;
+        }
+    }
+
+    /// Seconds per zone, empty when no value was recorded.
+    fn finish(self) -> Vec<f64> {
+        if self.seconds.iter().sum::<f64>() > 0.0 {
+            self.seconds
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Time in the heart rate and power zones of a [`ZoneOptions`].
+#[derive(Debug, Default)]
+pub(crate) struct ZoneAccumulator {
+    hr: Option<Zones>,
+    power: Option<Zones>,
+}
+
+impl ZoneAccumulator {
+    pub(crate) fn new(options: &ZoneOptions) -> Self {
+        ZoneAccumulator {
+            hr: options.hr_bounds().map(Zones::new),
+            power: options.power_bounds().map(Zones::new),
+        }
+    }
+
+    /// Credit the `seconds` until the next sample to the zones of `sensors`.
+    pub(crate) fn push(&mut self, sensors: &Sensors, seconds: f64) {
+        if !(0.0..=MAX_GAP).contains(&seconds) {
+            return;
+        }
+        if let Some(zones) = &mut self.hr {
+            zones.push(sensors.hr, seconds);
+        }
+        if let Some(zones) = &mut self.power {
+            zones.push(sensors.power, seconds);
+        }
+    }
+
+    /// Seconds per heart rate zone and per power zone.
+    pub(crate) fn finish(self) -> (Vec<f64>, Vec<f64>) {
+        (
+            self.hr.map(Zones::finish).unwrap_or_default(),
+            self.power.map(Zones::finish).unwrap_or_default(),
+        )
+    }
+}
+
+/// SVG elements of a bar per zone in a `width` x `height` box from (0, 0), with the zone
+/// name, the time and the share of the total. The text uses the fill of the parent.
+pub fn zones_svg(seconds: &[f64], width: f64, height: f64) -> String {
+    if seconds.is_empty() {
+        return String::new();
+    }
+    let total = seconds.iter().sum::<f64>().max(f64::EPSILON);
+    let longest = seconds.iter().copied().fold(f64::EPSILON, f64::max);
+    let row = height / seconds.len() as f64;
+    let font_size = (row * 0.55).min(width / 14.0);
+    let left = font_size * 1.8;
+    let right = font_size * 8.0;
+    let bar_width = (width - left - right).max(0.0);
+
+    let mut svg = format!(r#"<g font-size="{font_size:.2}">"#);
+    for (i, s) in seconds.iter().enumerate() {
+        let color = match seconds.len() {
+            5 => ZONE_COLORS[ZONE_COLORS_5[i]],
+            _ => ZONE_COLORS[i.min(ZONE_COLORS.len() - 1)],
+        };
+        let y = i as f64 * row;
+        let text_y = y + row / 2.0 + font_size * 0.35;
+        let _ = write!(
+            svg,
+            r#"<text x="0" y="{text_y:.2}">Z{}</text><rect x="{left:.2}" y="{:.2}" width="{:.2}" height="{:.2}" rx="{:.2}" fill="{color}"/><text x="{width:.2}" y="{text_y:.2}" text-anchor="end">{} <tspan fill-opacity="0.7">{:.0}%</tspan></text>"#,
+            i + 1,
+            y + row * 0.2,
+            bar_width * s / longest,
+            row * 0.6,
+            row * 0.1,
+            get_hhmmss(chrono::Duration::seconds(s.round() as i64)),
+            s / total * 100.0
+        );
+    }
+    svg.push_str("</g>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zone_bounds_from_references() {
+        let options = ZoneOptions::default().with_hr_max(200.0);
+        assert_eq!(options.hr_bounds().unwrap(), [120.0, 140.0, 160.0, 180.0]);
+        assert_eq!(options.power_bounds(), None);
+
+        let options = options
+            .with_lthr(170.0)
+            .with_ftp(250.0)
+            .with_model("7".parse().unwrap());
+        let hr = options.hr_bounds().unwrap();
+        assert_eq!(hr.len(), 6);
+        assert_eq!(zone_index(&hr, 170.0), 4);
+        assert_eq!(zone_index(&hr, 120.0), 0);
+        let power = options.power_bounds().unwrap();
+        assert_eq!(zone_index(&power, 400.0), 6);
+        assert!("6".parse::<ZoneModel>().is_err());
+    }
+
+    #[test]
+    fn time_in_zones() {
+        let options = ZoneOptions::default().with_hr_max(200.0).with_ftp(200.0);
+        let mut acc = ZoneAccumulator::new(&options);
+        let hr = |bpm| Sensors {
+            hr: Some(bpm),
+            ..Default::default()
+        };
+        acc.push(&hr(110.0), 10.0);
+        acc.push(&hr(150.0), 5.0);
+        acc.push(&hr(150.0), 5.0);
+        acc.push(&hr(195.0), 3.0);
+        // A recording gap.
+        acc.push(&hr(195.0), 600.0);
+        acc.push(&Sensors::default(), 10.0);
+        let (hr, power) = acc.finish();
+        assert_eq!(hr, [10.0, 0.0, 10.0, 0.0, 3.0]);
+        // No power meter.
+        assert!(power.is_empty());
+    }
+
+    #[test]
+    fn bar_chart() {
+        let svg = zones_svg(&[60.0, 0.0, 180.0, 3600.0, 0.0], 300.0, 100.0);
+        assert_eq!(svg.matches("<rect").count(), 5);
+        assert!(svg.contains(">Z5</text>"));
+        assert!(svg.contains(r##"fill="#F97316""##));
+        assert!(svg.contains(r#">01:00:00 <tspan fill-opacity="0.7">94%</tspan>"#));
+        assert_eq!(zones_svg(&[], 300.0, 100.0), "");
+    }
+}